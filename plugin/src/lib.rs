@@ -8,12 +8,18 @@ use rea_rs::{
 use rea_rs_macros::reaper_extension_plugin;
 use reaper_levitanus::{
     // ffmpeg::{gui::gui, render_video},
+    chapters::{export_chapters, import_chapters},
     envelope_snap::register_envelope_actions,
-    ffmpeg_new::ffmpeg_gui,
+    ffmpeg_new::{binaries::FfmpegBinaries, ffmpeg_gui},
     normalization::normalize_all_takes_on_selected_items,
+    otio_auto_export::{set_auto_export_overwrite_policy, toggle_otio_auto_export},
+    otio_conform::conform_to_picture_change,
     otio_export::{
-        export_otio_project, export_youtube_timecodes, set_project_fps, OtioFpsPolicy,
+        export_otio_project, export_youtube_timecodes, set_project_beat_grid, set_project_fps,
+        OtioFpsPolicy,
     },
+    subtitles::{export_subtitles, import_subtitles, set_subtitle_track},
+    transitions::set_selected_items_transitions,
 };
 
 use std::error::Error;
@@ -23,6 +29,7 @@ fn plugin_main(context: PluginContext) -> Result<(), Box<dyn Error>> {
     env_logger::init();
     log!(Level::Info, "reaper_levitanus extension... ");
     Reaper::init_global(context);
+    FfmpegBinaries::load_settings();
     // Swell::load(plugin_context);
     let rpr = Reaper::get_mut();
     let res = rpr.register_action(
@@ -104,6 +111,17 @@ fn plugin_main(context: PluginContext) -> Result<(), Box<dyn Error>> {
         Ok(_) => (),
     }
 
+    let res = rpr.register_action(
+        "LEVITANUS_OTIO_BEAT_GRID",
+        "set OTIO beat markers",
+        |_: i32| set_project_beat_grid(),
+        None,
+    );
+    match res {
+        Err(err) => error_box("can not register OTIO beat markers", err.to_string()),
+        Ok(_) => (),
+    }
+
     let res = rpr.register_action(
         "LEVITANUS_OTIO_YOUTUBE_TIMECODES",
         "export YouTube timecodes from markers",
@@ -115,6 +133,105 @@ fn plugin_main(context: PluginContext) -> Result<(), Box<dyn Error>> {
         Ok(_) => (),
     }
 
+    let res = rpr.register_action(
+        "LEVITANUS_EXPORT_CHAPTERS",
+        "export chapters from markers or regions",
+        |_: i32| export_chapters(),
+        None,
+    );
+    match res {
+        Err(err) => error_box("can not register chapters export", err.to_string()),
+        Ok(_) => (),
+    }
+
+    let res = rpr.register_action(
+        "LEVITANUS_IMPORT_CHAPTERS",
+        "import markers from chapters file",
+        |_: i32| import_chapters(),
+        None,
+    );
+    match res {
+        Err(err) => error_box("can not register chapters import", err.to_string()),
+        Ok(_) => (),
+    }
+
+    let res = rpr.register_action(
+        "LEVITANUS_SUBTITLE_TRACK",
+        "set selected track as subtitle track",
+        |_: i32| set_subtitle_track(),
+        None,
+    );
+    match res {
+        Err(err) => error_box("can not register subtitle track", err.to_string()),
+        Ok(_) => (),
+    }
+
+    let res = rpr.register_action(
+        "LEVITANUS_EXPORT_SUBTITLES",
+        "export subtitles",
+        |_: i32| export_subtitles(),
+        None,
+    );
+    match res {
+        Err(err) => error_box("can not register subtitles export", err.to_string()),
+        Ok(_) => (),
+    }
+
+    let res = rpr.register_action(
+        "LEVITANUS_IMPORT_SUBTITLES",
+        "import subtitles",
+        |_: i32| import_subtitles(),
+        None,
+    );
+    match res {
+        Err(err) => error_box("can not register subtitles import", err.to_string()),
+        Ok(_) => (),
+    }
+
+    let res = rpr.register_action(
+        "LEVITANUS_ITEM_TRANSITIONS",
+        "set fade transitions of selected items",
+        |_: i32| set_selected_items_transitions(),
+        None,
+    );
+    match res {
+        Err(err) => error_box("can not register item transitions", err.to_string()),
+        Ok(_) => (),
+    }
+
+    let res = rpr.register_action(
+        "LEVITANUS_OTIO_AUTO_EXPORT",
+        "toggle OTIO auto export",
+        |_: i32| toggle_otio_auto_export(),
+        None,
+    );
+    match res {
+        Err(err) => error_box("can not register OTIO auto export", err.to_string()),
+        Ok(_) => (),
+    }
+
+    let res = rpr.register_action(
+        "LEVITANUS_OTIO_AUTO_EXPORT_OVERWRITE",
+        "set OTIO auto export overwrite policy",
+        |_: i32| set_auto_export_overwrite_policy(),
+        None,
+    );
+    match res {
+        Err(err) => error_box("can not register OTIO auto export policy", err.to_string()),
+        Ok(_) => (),
+    }
+
+    let res = rpr.register_action(
+        "LEVITANUS_OTIO_CONFORM",
+        "conform items to picture change (OTIO/EDL)",
+        |_: i32| conform_to_picture_change(),
+        None,
+    );
+    match res {
+        Err(err) => error_box("can not register picture change conform", err.to_string()),
+        Ok(_) => (),
+    }
+
     Ok(())
 }
 
@@ -0,0 +1,952 @@
+use std::{
+    collections::HashSet,
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use lazy_static::lazy_static;
+use log::info;
+use rea_rs::{
+    Color, ExtState, MessageBoxType, MessageBoxValue, Position, Project, Reaper, UndoFlags,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::ffmpeg_new::binaries::ffprobe_command;
+use crate::otio_export::{build_render_target_plan, RenderBound, OTIO_EXT_SECTION};
+
+const CHAPTER_SETTINGS_KEY: &str = "chapter_export";
+const YOUTUBE_MIN_CHAPTERS: usize = 3;
+const YOUTUBE_MIN_CHAPTER_LENGTH: f64 = 10.0;
+const YOUTUBE_INTRO_TITLE: &str = "Intro";
+/// Chapter files with these extensions are parsed as text, all other files
+/// are probed for embedded chapters.
+const TEXT_CHAPTER_EXTENSIONS: [&str; 5] = ["txt", "vtt", "ffmetadata", "ini", "md"];
+
+lazy_static! {
+    static ref TIMECODE_RE: Regex =
+        Regex::new(r"(?:(?<h>\d{1,2}):)?(?<m>\d{1,2}):(?<s>\d{2})(?:[.,](?<frac>\d+))?")
+            .expect("can not compile timecode regex");
+}
+
+/// Chapter relative to render bound start, in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Chapter {
+    pub(crate) start: f64,
+    pub(crate) end: f64,
+    pub(crate) title: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChapterFormat {
+    /// `MM:SS - name` lines.
+    YouTube,
+    WebVtt,
+    /// `[CHAPTER]` blocks of ffmpeg metadata file.
+    FfMetadata,
+    PodloveJson,
+    /// Matroska chapters XML, accepted by mkvmerge and MP4 tools.
+    ChapterXml,
+    Csv,
+}
+impl ChapterFormat {
+    const ALL: [ChapterFormat; 6] = [
+        ChapterFormat::YouTube,
+        ChapterFormat::WebVtt,
+        ChapterFormat::FfMetadata,
+        ChapterFormat::PodloveJson,
+        ChapterFormat::ChapterXml,
+        ChapterFormat::Csv,
+    ];
+
+    /// Short name, used in user input.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::YouTube => "youtube",
+            Self::WebVtt => "vtt",
+            Self::FfMetadata => "ffmetadata",
+            Self::PodloveJson => "podlove",
+            Self::ChapterXml => "xml",
+            Self::Csv => "csv",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.key() == key)
+    }
+
+    /// Replaces extension of render target.
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::YouTube => "txt",
+            Self::WebVtt => "chapters.vtt",
+            Self::FfMetadata => "ffmetadata",
+            Self::PodloveJson => "chapters.json",
+            Self::ChapterXml => "chapters.xml",
+            Self::Csv => "chapters.csv",
+        }
+    }
+}
+
+/// Chapter read from file. End is known only for some formats.
+#[derive(Debug, Clone, PartialEq)]
+struct ParsedChapter {
+    start: f64,
+    end: Option<f64>,
+    title: String,
+}
+
+/// Violation of YouTube chapter rules.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum YoutubeChapterIssue {
+    FirstNotAtZero(f64),
+    TooFew(usize),
+    TooShort { title: String, length: f64 },
+    DuplicateName(String),
+}
+impl YoutubeChapterIssue {
+    /// Whether [fix_youtube_chapters] can fix the issue.
+    fn is_fixable(&self) -> bool {
+        matches!(self, Self::FirstNotAtZero(_) | Self::TooShort { .. })
+    }
+}
+impl fmt::Display for YoutubeChapterIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FirstNotAtZero(start) => write!(
+                f,
+                "first chapter starts at {} instead of 00:00",
+                format_youtube_timecode(*start)
+            ),
+            Self::TooFew(n) => write!(
+                f,
+                "only {n} chapter(s), at least {YOUTUBE_MIN_CHAPTERS} required"
+            ),
+            Self::TooShort { title, length } => write!(
+                f,
+                "chapter \"{title}\" is {length:.1}s long, at least {YOUTUBE_MIN_CHAPTER_LENGTH}s required"
+            ),
+            Self::DuplicateName(title) => write!(f, "chapter name \"{title}\" is used more than once"),
+        }
+    }
+}
+
+/// Last chosen chapter export, stored in project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ChapterExportSettings {
+    formats: Vec<ChapterFormat>,
+    regions_as_chapters: bool,
+}
+impl Default for ChapterExportSettings {
+    fn default() -> Self {
+        Self {
+            formats: vec![ChapterFormat::YouTube],
+            regions_as_chapters: false,
+        }
+    }
+}
+
+pub fn export_chapters() -> Result<(), Box<dyn Error>> {
+    let rpr = Reaper::get();
+    let pr = rpr.current_project();
+    let mut state: ExtState<ChapterExportSettings, Project> = ExtState::new(
+        OTIO_EXT_SECTION,
+        CHAPTER_SETTINGS_KEY,
+        Some(ChapterExportSettings::default()),
+        true,
+        &pr,
+        None,
+    );
+    let previous = state.get().ok().flatten().unwrap_or_default();
+
+    let formats_caption = "formats: youtube vtt ffmetadata podlove xml csv";
+    let regions_caption = "regions as chapters (y/n)";
+    let response = rpr.get_user_inputs(
+        "Export chapters (empty = last used)",
+        vec![formats_caption, regions_caption],
+        None,
+    )?;
+    let formats_input = response
+        .get(formats_caption)
+        .map(|v| v.trim().to_lowercase())
+        .unwrap_or_default();
+    let formats = if formats_input.is_empty() {
+        previous.formats
+    } else {
+        formats_input
+            .split_whitespace()
+            .map(|key| {
+                ChapterFormat::from_key(key).ok_or_else(|| format!("unknown chapter format: {key}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    let regions_as_chapters = match response
+        .get(regions_caption)
+        .map(|v| v.trim().to_lowercase())
+        .unwrap_or_default()
+        .as_str()
+    {
+        "" => previous.regions_as_chapters,
+        "y" | "yes" => true,
+        "n" | "no" => false,
+        other => return Err(format!("can not parse answer: {other}").into()),
+    };
+    let settings = ChapterExportSettings {
+        formats,
+        regions_as_chapters,
+    };
+    state.set(settings.clone());
+
+    for plan in build_render_target_plan(&pr)? {
+        let chapters = collect_chapters(&pr, &plan.bound, settings.regions_as_chapters);
+        for format in settings.formats.iter() {
+            let chapters = match format {
+                ChapterFormat::YouTube => {
+                    match review_youtube_chapters(&plan.render_target, chapters.clone())? {
+                        Some(chapters) => chapters,
+                        None => continue,
+                    }
+                }
+                _ => chapters.clone(),
+            };
+            let output_path = chapters_output_path(&plan.render_target, *format);
+            std::fs::write(&output_path, render_chapters(&chapters, *format))?;
+            info!("chapters exported: {}", output_path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Shows YouTube rules violations of render target and asks what to do.
+///
+/// Returns chapters to write, fixed or not, or `None` if user skipped the target.
+pub(crate) fn review_youtube_chapters(
+    render_target: &Path,
+    chapters: Vec<Chapter>,
+) -> anyhow::Result<Option<Vec<Chapter>>> {
+    let issues = validate_youtube_chapters(&chapters);
+    if issues.is_empty() {
+        return Ok(Some(chapters));
+    }
+    let report = issues
+        .iter()
+        .map(|issue| format!("- {issue}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let fixable = issues.iter().any(YoutubeChapterIssue::is_fixable);
+    let question = if fixable {
+        "Yes: fix (merge short chapters, start at 00:00)\nNo: write as is\nCancel: skip"
+    } else {
+        "Yes/No: write as is\nCancel: skip"
+    };
+    let response = Reaper::get().show_message_box(
+        "YouTube chapters",
+        format!(
+            "{}\n\n{report}\n\n{question}",
+            chapters_output_path(render_target, ChapterFormat::YouTube).display()
+        ),
+        MessageBoxType::YesNoCancel,
+    )?;
+    Ok(match response {
+        MessageBoxValue::Yes if fixable => match fix_youtube_chapters(chapters.clone()) {
+            Ok(fixed) => Some(fixed),
+            Err(error) => {
+                let response = Reaper::get().show_message_box(
+                    "YouTube chapters",
+                    format!(
+                        "{}\n\ncan not be fixed: {error}\n\nYes/No: write as is\nCancel: skip",
+                        chapters_output_path(render_target, ChapterFormat::YouTube).display()
+                    ),
+                    MessageBoxType::YesNoCancel,
+                )?;
+                match response {
+                    MessageBoxValue::Yes | MessageBoxValue::No => Some(chapters),
+                    _ => None,
+                }
+            }
+        },
+        MessageBoxValue::Yes | MessageBoxValue::No => Some(chapters),
+        _ => None,
+    })
+}
+
+pub(crate) fn validate_youtube_chapters(chapters: &[Chapter]) -> Vec<YoutubeChapterIssue> {
+    let mut issues = Vec::new();
+    if let Some(first) = chapters.first() {
+        if first.start > 0.0 {
+            issues.push(YoutubeChapterIssue::FirstNotAtZero(first.start));
+        }
+    }
+    if chapters.len() < YOUTUBE_MIN_CHAPTERS {
+        issues.push(YoutubeChapterIssue::TooFew(chapters.len()));
+    }
+    for chapter in chapters {
+        let length = chapter.end - chapter.start;
+        if length < YOUTUBE_MIN_CHAPTER_LENGTH {
+            issues.push(YoutubeChapterIssue::TooShort {
+                title: chapter.title.clone(),
+                length,
+            });
+        }
+    }
+    let mut seen = HashSet::new();
+    let mut reported = HashSet::new();
+    for chapter in chapters {
+        let key = chapter.title.trim().to_lowercase();
+        if !seen.insert(key.clone()) && reported.insert(key) {
+            issues.push(YoutubeChapterIssue::DuplicateName(chapter.title.clone()));
+        }
+    }
+    issues
+}
+
+/// Merges too short chapters into previous ones (the first one into the
+/// next), then starts chapters at zero: the first chapter is extended back,
+/// if intro before it would be too short, or intro chapter is inserted.
+///
+/// Fails, if too few chapters remain. Duplicate names can not be fixed
+/// automatically.
+pub(crate) fn fix_youtube_chapters(mut chapters: Vec<Chapter>) -> anyhow::Result<Vec<Chapter>> {
+    while chapters.len() > 1 {
+        let Some(idx) = chapters
+            .iter()
+            .position(|c| c.end - c.start < YOUTUBE_MIN_CHAPTER_LENGTH)
+        else {
+            break;
+        };
+        let short = chapters.remove(idx);
+        if idx == 0 {
+            chapters[0].start = short.start;
+        } else {
+            chapters[idx - 1].end = short.end;
+        }
+    }
+    let first_start = chapters.first().map_or(0.0, |first| first.start);
+    if first_start >= YOUTUBE_MIN_CHAPTER_LENGTH {
+        chapters.insert(
+            0,
+            Chapter {
+                start: 0.0,
+                end: first_start,
+                title: YOUTUBE_INTRO_TITLE.to_string(),
+            },
+        );
+    } else if let Some(first) = chapters.first_mut() {
+        first.start = 0.0;
+    }
+    if chapters.len() < YOUTUBE_MIN_CHAPTERS {
+        return Err(anyhow!("{}", YoutubeChapterIssue::TooFew(chapters.len())));
+    }
+    Ok(chapters)
+}
+
+/// Markers (or regions) inside render bound as chapters.
+///
+/// Marker chapter lasts until the next one or the bound end. Region chapter
+/// is clipped by bound.
+pub(crate) fn collect_chapters(
+    pr: &Project,
+    bound: &RenderBound,
+    regions_as_chapters: bool,
+) -> Vec<Chapter> {
+    let start = bound.start.as_duration().as_secs_f64();
+    let end = bound.end.as_duration().as_secs_f64();
+
+    let mut chapters = Vec::new();
+    for marker in pr
+        .iter_markers_and_regions()
+        .filter(|m| m.is_region == regions_as_chapters)
+    {
+        let pos = marker.position.as_duration().as_secs_f64();
+        let chapter_end = if marker.is_region {
+            marker.rgn_end.as_duration().as_secs_f64().min(end)
+        } else {
+            end
+        };
+        let within = if marker.is_region {
+            pos < end && chapter_end > start
+        } else {
+            pos >= start && pos <= end
+        };
+        if !within {
+            continue;
+        }
+        let title = if marker.name.trim().is_empty() {
+            if marker.is_region { "Region" } else { "Marker" }.to_string()
+        } else {
+            marker.name
+        };
+        chapters.push(Chapter {
+            start: (pos - start).max(0.0),
+            end: chapter_end - start,
+            title,
+        });
+    }
+    chapters.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    if !regions_as_chapters {
+        for idx in 1..chapters.len() {
+            chapters[idx - 1].end = chapters[idx].start;
+        }
+    }
+    chapters
+}
+
+pub(crate) fn chapters_output_path(render_target: &Path, format: ChapterFormat) -> PathBuf {
+    render_target.with_extension(format.extension())
+}
+
+pub(crate) fn render_chapters(chapters: &[Chapter], format: ChapterFormat) -> String {
+    match format {
+        ChapterFormat::YouTube => chapters
+            .iter()
+            .map(|c| format!("{} - {}", format_youtube_timecode(c.start), c.title))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ChapterFormat::WebVtt => {
+            let mut out = String::from("WEBVTT\n");
+            for (idx, chapter) in chapters.iter().enumerate() {
+                out.push_str(&format!(
+                    "\n{}\n{} --> {}\n{}\n",
+                    idx + 1,
+                    format_clock(chapter.start, '.'),
+                    format_clock(chapter.end, '.'),
+                    chapter.title.replace("-->", "->")
+                ));
+            }
+            out
+        }
+        ChapterFormat::FfMetadata => {
+            let mut out = String::from(";FFMETADATA1\n");
+            for chapter in chapters {
+                out.push_str(&format!(
+                    "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+                    (chapter.start * 1000.0).round() as u64,
+                    (chapter.end * 1000.0).round() as u64,
+                    escape_ffmetadata(&chapter.title)
+                ));
+            }
+            out
+        }
+        ChapterFormat::PodloveJson => {
+            let value = chapters
+                .iter()
+                .map(|c| json!({"start": format_clock(c.start, '.'), "title": c.title}))
+                .collect::<Vec<_>>();
+            serde_json::to_string_pretty(&value).unwrap_or_default()
+        }
+        ChapterFormat::ChapterXml => {
+            let mut out = String::from(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <!DOCTYPE Chapters SYSTEM \"matroskachapters.dtd\">\n\
+                 <Chapters>\n  <EditionEntry>\n",
+            );
+            for chapter in chapters {
+                out.push_str(&format!(
+                    "    <ChapterAtom>\n      \
+                     <ChapterTimeStart>{}</ChapterTimeStart>\n      \
+                     <ChapterTimeEnd>{}</ChapterTimeEnd>\n      \
+                     <ChapterDisplay>\n        \
+                     <ChapterString>{}</ChapterString>\n      \
+                     </ChapterDisplay>\n    </ChapterAtom>\n",
+                    format_clock(chapter.start, '.'),
+                    format_clock(chapter.end, '.'),
+                    escape_xml(&chapter.title)
+                ));
+            }
+            out.push_str("  </EditionEntry>\n</Chapters>\n");
+            out
+        }
+        ChapterFormat::Csv => {
+            let mut out = String::from("start,end,title\n");
+            for chapter in chapters {
+                out.push_str(&format!(
+                    "{},{},\"{}\"\n",
+                    format_clock(chapter.start, '.'),
+                    format_clock(chapter.end, '.'),
+                    chapter.title.replace('"', "\"\"")
+                ));
+            }
+            out
+        }
+    }
+}
+
+/// Creates markers or regions from chapter file or media file with chapters.
+///
+/// Chapters are offset to the time selection, or to the first render bound.
+pub fn import_chapters() -> Result<(), Box<dyn Error>> {
+    let rpr = Reaper::get();
+    let mut pr = rpr.current_project();
+    let file_caption = "file (timecodes/vtt/ffmetadata/video)";
+    let regions_caption = "as regions (y/n)";
+    let response = rpr.get_user_inputs(
+        "Import chapters",
+        vec![file_caption, regions_caption],
+        None,
+    )?;
+    let path = response
+        .get(file_caption)
+        .map(|v| v.trim().trim_matches('"'))
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .ok_or("chapter file is not specified")?;
+    let as_regions = matches!(
+        response
+            .get(regions_caption)
+            .map(|v| v.trim().to_lowercase())
+            .as_deref(),
+        Some("y" | "yes")
+    );
+
+    let parsed = read_chapter_source(&path)?;
+    if parsed.is_empty() {
+        return Err(format!("no chapters found in {}", path.display()).into());
+    }
+
+    let (origin, bound_end) = import_origin(&pr);
+    let chapters = complete_chapter_ends(parsed, bound_end.map(|end| end - origin));
+
+    pr.begin_undo_block();
+    let result = add_chapter_markers(&mut pr, origin, &chapters, as_regions);
+    pr.end_undo_block("Import chapters", UndoFlags::all());
+    rpr.update_arrange();
+    result?;
+    info!("imported {} chapters from {}", chapters.len(), path.display());
+    Ok(())
+}
+
+/// Where imported timed content starts and ends: time selection, if any,
+/// or the first render bound.
+pub(crate) fn import_origin(pr: &Project) -> (f64, Option<f64>) {
+    let ts = pr.get_time_selection();
+    if ts.get_end() > ts.get_start() {
+        return (
+            ts.get_start().as_duration().as_secs_f64(),
+            Some(ts.get_end().as_duration().as_secs_f64()),
+        );
+    }
+    match build_render_target_plan(pr).ok().and_then(|p| p.into_iter().next()) {
+        Some(plan) => (
+            plan.bound.start.as_duration().as_secs_f64(),
+            Some(plan.bound.end.as_duration().as_secs_f64()),
+        ),
+        None => (0.0, None),
+    }
+}
+
+fn add_chapter_markers(
+    pr: &mut Project,
+    origin: f64,
+    chapters: &[Chapter],
+    as_regions: bool,
+) -> anyhow::Result<()> {
+    for chapter in chapters {
+        let start = Position::from(origin + chapter.start);
+        if as_regions && chapter.end > chapter.start {
+            pr.add_region(
+                start,
+                Position::from(origin + chapter.end),
+                Some(chapter.title.as_str()),
+                None::<Color>,
+                None::<usize>,
+            )
+            .map_err(|e| anyhow!("can not add region {}: {e}", chapter.title))?;
+        } else {
+            pr.add_marker(start, Some(chapter.title.as_str()), None::<Color>, None::<usize>)
+                .map_err(|e| anyhow!("can not add marker {}: {e}", chapter.title))?;
+        }
+    }
+    Ok(())
+}
+
+/// Chapter without end lasts until the next one. The last one lasts until
+/// `fallback_end`, or has zero length, if it is unknown.
+fn complete_chapter_ends(mut parsed: Vec<ParsedChapter>, fallback_end: Option<f64>) -> Vec<Chapter> {
+    parsed.sort_by(|a, b| a.start.total_cmp(&b.start));
+    let starts = parsed.iter().map(|c| c.start).collect::<Vec<_>>();
+    parsed
+        .into_iter()
+        .enumerate()
+        .map(|(idx, c)| {
+            let end = c
+                .end
+                .or_else(|| starts.get(idx + 1).copied())
+                .or(fallback_end)
+                .unwrap_or(c.start)
+                .max(c.start);
+            Chapter {
+                start: c.start,
+                end,
+                title: c.title,
+            }
+        })
+        .collect()
+}
+
+fn read_chapter_source(path: &Path) -> anyhow::Result<Vec<ParsedChapter>> {
+    let is_text = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| TEXT_CHAPTER_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false);
+    if !is_text {
+        return probe_chapters(path);
+    }
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("can not read chapters '{}': {e}", path.display()))?;
+    let content = content.trim_start_matches('\u{feff}').trim_start();
+    if content.starts_with("WEBVTT") {
+        Ok(parse_webvtt_chapters(content))
+    } else if content.starts_with(";FFMETADATA") {
+        Ok(parse_ffmetadata_chapters(content))
+    } else {
+        Ok(parse_timecode_list(content))
+    }
+}
+
+fn probe_chapters(path: &Path) -> anyhow::Result<Vec<ParsedChapter>> {
+    let output = ffprobe_command()
+        .args(["-v", "error", "-print_format", "json", "-show_chapters"])
+        .arg(path)
+        .output()
+        .map_err(|e| anyhow!("can not run ffprobe: {e}"))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "ffprobe can not read chapters of '{}': {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let value: Value = serde_json::from_slice(&output.stdout)?;
+    let seconds = |chapter: &Value, key: &str| {
+        chapter
+            .get(key)
+            .and_then(Value::as_str)
+            .and_then(|v| v.parse::<f64>().ok())
+    };
+    Ok(value
+        .get("chapters")
+        .and_then(Value::as_array)
+        .map(|chapters| {
+            chapters
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, chapter)| {
+                    Some(ParsedChapter {
+                        start: seconds(chapter, "start_time")?,
+                        end: seconds(chapter, "end_time"),
+                        title: chapter
+                            .get("tags")
+                            .and_then(|t| t.get("title"))
+                            .and_then(Value::as_str)
+                            .map(String::from)
+                            .unwrap_or_else(|| format!("Chapter {}", idx + 1)),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Parses `HH:MM:SS.mmm`, `MM:SS` and similar clock values.
+pub(crate) fn parse_clock(value: &str) -> Option<f64> {
+    let caps = TIMECODE_RE.captures(value.trim())?;
+    let number = |name: &str| {
+        caps.name(name)
+            .map(|m| m.as_str().parse::<f64>().unwrap_or(0.0))
+            .unwrap_or(0.0)
+    };
+    let frac = caps
+        .name("frac")
+        .and_then(|m| format!("0.{}", m.as_str()).parse::<f64>().ok())
+        .unwrap_or(0.0);
+    Some(number("h") * 3600.0 + number("m") * 60.0 + number("s") + frac)
+}
+
+/// YouTube-like lists: one chapter per line, timecode before or after title.
+fn parse_timecode_list(content: &str) -> Vec<ParsedChapter> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let found = TIMECODE_RE.find(line)?;
+            let start = parse_clock(found.as_str())?;
+            let rest = format!("{} {}", &line[..found.start()], &line[found.end()..]);
+            let title = rest
+                .trim_matches(|c: char| c.is_whitespace() || "-–—:|()[]".contains(c))
+                .to_string();
+            Some(ParsedChapter {
+                start,
+                end: None,
+                title: if title.is_empty() {
+                    "Chapter".to_string()
+                } else {
+                    title
+                },
+            })
+        })
+        .collect()
+}
+
+fn parse_webvtt_chapters(content: &str) -> Vec<ParsedChapter> {
+    let mut chapters = Vec::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let Some((start, end)) = line.split_once("-->") else {
+            continue;
+        };
+        let (Some(start), Some(end)) = (
+            parse_clock(start),
+            end.split_whitespace().next().and_then(parse_clock),
+        ) else {
+            continue;
+        };
+        let title = lines
+            .by_ref()
+            .take_while(|l| !l.trim().is_empty())
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(" ");
+        chapters.push(ParsedChapter {
+            start,
+            end: Some(end),
+            title,
+        });
+    }
+    chapters
+}
+
+fn parse_ffmetadata_chapters(content: &str) -> Vec<ParsedChapter> {
+    struct Raw {
+        timebase: (f64, f64),
+        start: Option<f64>,
+        end: Option<f64>,
+        title: String,
+    }
+    let mut raw: Vec<Raw> = Vec::new();
+    let mut in_chapter = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_chapter = line.eq_ignore_ascii_case("[CHAPTER]");
+            if in_chapter {
+                // ffmpeg assumes nanoseconds without TIMEBASE.
+                raw.push(Raw {
+                    timebase: (1.0, 1e9),
+                    start: None,
+                    end: None,
+                    title: String::new(),
+                });
+            }
+            continue;
+        }
+        let Some(chapter) = raw.last_mut().filter(|_| in_chapter) else {
+            continue;
+        };
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key.to_lowercase().as_str() {
+            "timebase" => {
+                if let Some((num, den)) = value.split_once('/') {
+                    if let (Ok(num), Ok(den)) = (num.parse::<f64>(), den.parse::<f64>()) {
+                        if den > 0.0 {
+                            chapter.timebase = (num, den);
+                        }
+                    }
+                }
+            }
+            "start" => chapter.start = value.parse().ok(),
+            "end" => chapter.end = value.parse().ok(),
+            "title" => chapter.title = unescape_ffmetadata(value),
+            _ => (),
+        }
+    }
+    raw.into_iter()
+        .filter_map(|c| {
+            Some(ParsedChapter {
+                start: c.start? * c.timebase.0 / c.timebase.1,
+                end: c.end.map(|end| end * c.timebase.0 / c.timebase.1),
+                title: c.title,
+            })
+        })
+        .collect()
+}
+
+fn unescape_ffmetadata(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => out.extend(chars.next()),
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+pub(crate) fn format_youtube_timecode(seconds: f64) -> String {
+    let total = seconds.floor().max(0.0) as u64;
+    let h = total / 3600;
+    let m = (total % 3600) / 60;
+    let s = total % 60;
+    if h > 0 {
+        format!("{h:02}:{m:02}:{s:02}")
+    } else {
+        format!("{m:02}:{s:02}")
+    }
+}
+
+/// `HH:MM:SS.mmm` with the given milliseconds separator.
+pub(crate) fn format_clock(seconds: f64, ms_separator: char) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    let ms = total_ms % 1000;
+    let total = total_ms / 1000;
+    format!(
+        "{:02}:{:02}:{:02}{ms_separator}{ms:03}",
+        total / 3600,
+        (total % 3600) / 60,
+        total % 60
+    )
+}
+
+fn escape_ffmetadata(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, '=' | ';' | '#' | '\\' | '\n') {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[test]
+fn test_fix_youtube_chapters() {
+    let chapter = |start: f64, end: f64, title: &str| Chapter {
+        start,
+        end,
+        title: title.to_string(),
+    };
+    let chapters = vec![
+        chapter(5.0, 30.0, "A"),
+        chapter(30.0, 35.0, "B"),
+        chapter(35.0, 60.0, "a"),
+    ];
+    let issues = validate_youtube_chapters(&chapters);
+    assert!(issues.contains(&YoutubeChapterIssue::FirstNotAtZero(5.0)));
+    assert!(issues.contains(&YoutubeChapterIssue::DuplicateName("a".to_string())));
+    // Short chapter is merged, only two chapters remain.
+    assert!(fix_youtube_chapters(chapters).is_err());
+
+    // Intro would be shorter than allowed: the first chapter starts at zero.
+    let chapters = vec![
+        chapter(4.0, 30.0, "A"),
+        chapter(30.0, 60.0, "B"),
+        chapter(60.0, 65.0, "C"),
+        chapter(65.0, 90.0, "D"),
+    ];
+    assert_eq!(
+        fix_youtube_chapters(chapters).unwrap(),
+        vec![
+            chapter(0.0, 30.0, "A"),
+            chapter(30.0, 65.0, "B"),
+            chapter(65.0, 90.0, "D")
+        ]
+    );
+
+    let chapters = vec![
+        chapter(20.0, 40.0, "A"),
+        chapter(40.0, 60.0, "B"),
+        chapter(60.0, 80.0, "C"),
+    ];
+    let fixed = fix_youtube_chapters(chapters).unwrap();
+    assert_eq!(fixed[0], chapter(0.0, 20.0, "Intro"));
+    assert_eq!(fixed.len(), 4);
+    assert!(validate_youtube_chapters(&fixed).is_empty());
+}
+
+#[test]
+fn test_parse_chapters() {
+    assert_eq!(
+        parse_timecode_list("0:00 Intro\n1:05:30 - Outro\nno time here\nVerse (02:10.5)"),
+        vec![
+            ParsedChapter {
+                start: 0.0,
+                end: None,
+                title: "Intro".to_string()
+            },
+            ParsedChapter {
+                start: 3930.0,
+                end: None,
+                title: "Outro".to_string()
+            },
+            ParsedChapter {
+                start: 130.5,
+                end: None,
+                title: "Verse".to_string()
+            },
+        ]
+    );
+    let chapters = vec![
+        Chapter {
+            start: 0.0,
+            end: 65.5,
+            title: "Intro".to_string(),
+        },
+        Chapter {
+            start: 65.5,
+            end: 70.0,
+            title: "A=B; \"C\"".to_string(),
+        },
+    ];
+    for format in [ChapterFormat::WebVtt, ChapterFormat::FfMetadata] {
+        let content = render_chapters(&chapters, format);
+        let parsed = if format == ChapterFormat::WebVtt {
+            parse_webvtt_chapters(&content)
+        } else {
+            parse_ffmetadata_chapters(&content)
+        };
+        assert_eq!(complete_chapter_ends(parsed, None), chapters);
+    }
+}
+
+#[test]
+fn test_render_chapters() {
+    let chapters = vec![
+        Chapter {
+            start: 0.0,
+            end: 65.5,
+            title: "Intro".to_string(),
+        },
+        Chapter {
+            start: 65.5,
+            end: 3700.0,
+            title: "A=B; \"C\"".to_string(),
+        },
+    ];
+    assert_eq!(
+        render_chapters(&chapters, ChapterFormat::YouTube),
+        "00:00 - Intro\n01:05 - A=B; \"C\""
+    );
+    assert_eq!(
+        render_chapters(&chapters, ChapterFormat::WebVtt),
+        "WEBVTT\n\n1\n00:00:00.000 --> 00:01:05.500\nIntro\n\n\
+         2\n00:01:05.500 --> 01:01:40.000\nA=B; \"C\"\n"
+    );
+    assert!(render_chapters(&chapters, ChapterFormat::FfMetadata)
+        .contains("START=65500\nEND=3700000\ntitle=A\\=B\\; \"C\"\n"));
+    assert!(render_chapters(&chapters, ChapterFormat::Csv)
+        .ends_with("00:01:05.500,01:01:40.000,\"A=B; \"\"C\"\"\"\n"));
+}
@@ -4,6 +4,8 @@ use fraction::Fraction;
 use rea_rs::Position;
 use serde::{Deserialize, Serialize};
 
+use crate::ffmpeg_common::xfade::XFadeTransition;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum FilterParamValue {
     File(PathBuf),
@@ -332,67 +334,3 @@ impl Filter {
         }
     }
 }
-
-#[allow(non_camel_case_types)]
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, strum::Display)]
-pub enum XFadeTransition {
-    custom,
-    fade,
-    wipeleft,
-    wiperight,
-    wipeup,
-    wipedown,
-    slideleft,
-    slideright,
-    slideup,
-    slidedown,
-    circlecrop,
-    rectcrop,
-    distance,
-    fadeblack,
-    fadewhite,
-    radial,
-    smoothleft,
-    smoothright,
-    smoothup,
-    smoothdown,
-    circleopen,
-    circleclose,
-    vertopen,
-    vertclose,
-    horzopen,
-    horzclose,
-    dissolve,
-    pixelize,
-    diagtl,
-    diagtr,
-    diagbl,
-    diagbr,
-    hlslice,
-    hrslice,
-    vuslice,
-    vdslice,
-    hblur,
-    fadegrays,
-    wipetl,
-    wipetr,
-    wipebl,
-    wipebr,
-    squeezeh,
-    squeezev,
-    zoomin,
-    fadefast,
-    fadeslow,
-    hlwind,
-    hrwind,
-    vuwind,
-    vdwind,
-    coverleft,
-    coverright,
-    coverup,
-    coverdown,
-    revealleft,
-    revealright,
-    revealup,
-    revealdown,
-}
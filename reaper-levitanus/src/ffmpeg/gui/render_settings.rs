@@ -11,6 +11,7 @@ use crate::ffmpeg::{
     parser::ParsingProgress,
     RenderSettings,
 };
+use crate::ffmpeg_common::video_presets::{built_in_framerates, built_in_resolutions};
 
 impl Front {
     pub(crate) fn widget_render_settings(&mut self, ctx: &Context, ui: &mut Ui) {
@@ -180,12 +181,16 @@ impl Front {
             ComboBox::from_id_salt("default resolutions")
                 .selected_text("built-in resolutions")
                 .show_ui(ui, |ui| {
-                    for (name, res) in built_in_resolutions() {
+                    for (name, (width, height)) in built_in_resolutions() {
+                        let res = Resolution {
+                            width: width as usize,
+                            height: height as usize,
+                        };
                         if ui
                             .selectable_label(res == self.state.render_settings.resolution, name)
                             .clicked()
                         {
-                            self.state.render_settings.resolution = res.clone()
+                            self.state.render_settings.resolution = res
                         }
                     }
                 });
@@ -242,7 +247,7 @@ impl Front {
                             .selectable_label(fps == self.state.render_settings.fps, name)
                             .clicked()
                         {
-                            self.state.render_settings.fps = fps.clone()
+                            self.state.render_settings.fps = fps
                         }
                     }
                 });
@@ -893,392 +898,3 @@ impl Front {
         updated
     }
 }
-
-fn built_in_resolutions() -> Vec<(&'static str, Resolution)> {
-    vec![
-        (
-            "ntsc",
-            Resolution {
-                width: 720,
-                height: 480,
-            },
-        ),
-        (
-            "pal",
-            Resolution {
-                width: 720,
-                height: 576,
-            },
-        ),
-        (
-            "qntsc",
-            Resolution {
-                width: 352,
-                height: 240,
-            },
-        ),
-        (
-            "qpal",
-            Resolution {
-                width: 352,
-                height: 288,
-            },
-        ),
-        (
-            "sntsc",
-            Resolution {
-                width: 640,
-                height: 480,
-            },
-        ),
-        (
-            "spal",
-            Resolution {
-                width: 768,
-                height: 576,
-            },
-        ),
-        (
-            "film",
-            Resolution {
-                width: 352,
-                height: 240,
-            },
-        ),
-        (
-            "ntsc-film",
-            Resolution {
-                width: 352,
-                height: 240,
-            },
-        ),
-        (
-            "sqcif",
-            Resolution {
-                width: 128,
-                height: 96,
-            },
-        ),
-        (
-            "qcif",
-            Resolution {
-                width: 176,
-                height: 144,
-            },
-        ),
-        (
-            "cif",
-            Resolution {
-                width: 352,
-                height: 288,
-            },
-        ),
-        (
-            "4cif",
-            Resolution {
-                width: 704,
-                height: 576,
-            },
-        ),
-        (
-            "16cif",
-            Resolution {
-                width: 1408,
-                height: 1152,
-            },
-        ),
-        (
-            "qqvga",
-            Resolution {
-                width: 160,
-                height: 120,
-            },
-        ),
-        (
-            "qvga",
-            Resolution {
-                width: 320,
-                height: 240,
-            },
-        ),
-        (
-            "vga",
-            Resolution {
-                width: 640,
-                height: 480,
-            },
-        ),
-        (
-            "svga",
-            Resolution {
-                width: 800,
-                height: 600,
-            },
-        ),
-        (
-            "xga",
-            Resolution {
-                width: 1024,
-                height: 768,
-            },
-        ),
-        (
-            "uxga",
-            Resolution {
-                width: 1600,
-                height: 1200,
-            },
-        ),
-        (
-            "qxga",
-            Resolution {
-                width: 2048,
-                height: 1536,
-            },
-        ),
-        (
-            "sxga",
-            Resolution {
-                width: 1280,
-                height: 1024,
-            },
-        ),
-        (
-            "qsxga",
-            Resolution {
-                width: 2560,
-                height: 2048,
-            },
-        ),
-        (
-            "hsxga",
-            Resolution {
-                width: 5120,
-                height: 4096,
-            },
-        ),
-        (
-            "wvga",
-            Resolution {
-                width: 852,
-                height: 480,
-            },
-        ),
-        (
-            "wxga",
-            Resolution {
-                width: 1366,
-                height: 768,
-            },
-        ),
-        (
-            "wsxga",
-            Resolution {
-                width: 1600,
-                height: 1024,
-            },
-        ),
-        (
-            "wuxga",
-            Resolution {
-                width: 1920,
-                height: 1200,
-            },
-        ),
-        (
-            "woxga",
-            Resolution {
-                width: 2560,
-                height: 1600,
-            },
-        ),
-        (
-            "wqsxga",
-            Resolution {
-                width: 3200,
-                height: 2048,
-            },
-        ),
-        (
-            "wquxga",
-            Resolution {
-                width: 3840,
-                height: 2400,
-            },
-        ),
-        (
-            "whsxga",
-            Resolution {
-                width: 6400,
-                height: 4096,
-            },
-        ),
-        (
-            "whuxga",
-            Resolution {
-                width: 7680,
-                height: 4800,
-            },
-        ),
-        (
-            "cga",
-            Resolution {
-                width: 320,
-                height: 200,
-            },
-        ),
-        (
-            "ega",
-            Resolution {
-                width: 640,
-                height: 350,
-            },
-        ),
-        (
-            "hd480",
-            Resolution {
-                width: 852,
-                height: 480,
-            },
-        ),
-        (
-            "hd720",
-            Resolution {
-                width: 1280,
-                height: 720,
-            },
-        ),
-        (
-            "hd1080",
-            Resolution {
-                width: 1920,
-                height: 1080,
-            },
-        ),
-        (
-            "2k",
-            Resolution {
-                width: 2048,
-                height: 1080,
-            },
-        ),
-        (
-            "2kflat",
-            Resolution {
-                width: 1998,
-                height: 1080,
-            },
-        ),
-        (
-            "2kscope",
-            Resolution {
-                width: 2048,
-                height: 858,
-            },
-        ),
-        (
-            "4k",
-            Resolution {
-                width: 4096,
-                height: 2160,
-            },
-        ),
-        (
-            "4kflat",
-            Resolution {
-                width: 3996,
-                height: 2160,
-            },
-        ),
-        (
-            "4kscope",
-            Resolution {
-                width: 4096,
-                height: 1716,
-            },
-        ),
-        (
-            "nhd",
-            Resolution {
-                width: 640,
-                height: 360,
-            },
-        ),
-        (
-            "hqvga",
-            Resolution {
-                width: 240,
-                height: 160,
-            },
-        ),
-        (
-            "wqvga",
-            Resolution {
-                width: 400,
-                height: 240,
-            },
-        ),
-        (
-            "fwqvga",
-            Resolution {
-                width: 432,
-                height: 240,
-            },
-        ),
-        (
-            "hvga",
-            Resolution {
-                width: 480,
-                height: 320,
-            },
-        ),
-        (
-            "qhd",
-            Resolution {
-                width: 960,
-                height: 540,
-            },
-        ),
-        (
-            "2kdci",
-            Resolution {
-                width: 2048,
-                height: 1080,
-            },
-        ),
-        (
-            "4kdci",
-            Resolution {
-                width: 4096,
-                height: 2160,
-            },
-        ),
-        (
-            "uhd2160",
-            Resolution {
-                width: 3840,
-                height: 2160,
-            },
-        ),
-        (
-            "uhd4320",
-            Resolution {
-                width: 7680,
-                height: 4320,
-            },
-        ),
-    ]
-}
-
-fn built_in_framerates() -> Vec<(&'static str, Fraction)> {
-    vec![
-        ("ntsc", Fraction::new(30000_u64, 1001_u64)),
-        ("pal", Fraction::new(25_u64, 1_u64)),
-        ("qntsc", Fraction::new(30000_u64, 1001_u64)),
-        ("qpal", Fraction::new(25_u64, 1_u64)),
-        ("sntsc", Fraction::new(30000_u64, 1001_u64)),
-        ("spal", Fraction::new(25_u64, 1_u64)),
-        ("film", Fraction::new(24_u64, 1_u64)),
-        ("ntsc - film", Fraction::new(24000_u64, 1001_u64)),
-    ]
-}
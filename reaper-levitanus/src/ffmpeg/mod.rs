@@ -10,8 +10,8 @@ mod base_types;
 mod filters;
 mod gui;
 mod nodes;
-mod options;
-mod parser;
+use crate::ffmpeg_common::options;
+use crate::ffmpeg_common::parser;
 mod stream_ids;
 
 // pub fn render_video() -> Result<(), Box<dyn Error>> {
@@ -0,0 +1,7 @@
+//! ffmpeg options, parser of ffmpeg capabilities, filter enums and video
+//! presets, used by both GUIs.
+
+pub mod options;
+pub mod parser;
+pub mod video_presets;
+pub mod xfade;
@@ -295,6 +295,18 @@ impl Default for Encoder {
     }
 }
 
+impl Encoder {
+    /// Codec of the encoded stream: `(codec h264)` at the end of description,
+    /// or encoder name itself (e.g. `aac`).
+    pub fn codec(&self) -> String {
+        self.description
+            .rsplit_once("(codec ")
+            .and_then(|(_, codec)| codec.split_once(')'))
+            .map(|(codec, _)| codec.trim().to_string())
+            .unwrap_or_else(|| self.name.clone())
+    }
+}
+
 impl Default for PixelFormat {
     fn default() -> Self {
         let json = r#"{
@@ -409,7 +421,7 @@ impl FfmpegColor {
     pub(crate) fn ffmpeg_representation(&self) -> String {
         format!("{:#08x}@{:#04x}", self.color, self.alpha)
     }
-    pub(crate) fn built_in_colors() -> impl Iterator<Item = (&'static str, u32)> {
+    pub fn built_in_colors() -> impl Iterator<Item = (&'static str, u32)> {
         let names = vec![
             "AliceBlue",
             "AntiqueWhite",
@@ -698,6 +710,11 @@ impl FfmpegColor {
     }
 }
 
+#[test]
+fn test_encoder_codec() {
+    assert_eq!(Encoder::default().codec(), "h264");
+}
+
 #[test]
 fn test_ffmpeg_color() {
     let color = FfmpegColor::new(
@@ -4,7 +4,7 @@ use std::{
     ffi::OsStr,
     fs::OpenOptions,
     io::Write,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::Command,
     sync::mpsc::{SendError, Sender},
 };
@@ -14,6 +14,8 @@ use log::info;
 use path_absolutize::Absolutize;
 use regex::Regex;
 
+use serde::Serialize;
+
 use super::options::{
     Encoder, EncoderType, Muxer, Opt, OptionParameter, ParsedFilter, PixelFormat,
 };
@@ -44,28 +46,102 @@ pub fn parse_all(
 ) -> Result<(), Box<dyn Error>> {
     let mut sender: Option<Sender<ParsingProgress>> = sender.into();
     let mut progress = 0.0;
-    if let Err(e) = parse_muxers(muxers_path(&out_dir), &mut progress, &mut sender) {
-        send_progress(ParsingProgress::Result(Err(e.to_string())), &mut sender)?;
-        return Err(e);
-    };
-    progress = 0.25;
-    if let Err(e) = parse_encoders(encoders_path(&out_dir), &mut progress, &mut sender) {
-        send_progress(ParsingProgress::Result(Err(e.to_string())), &mut sender)?;
-        return Err(e);
-    };
-    progress = 0.5;
-    if let Err(e) = parse_filters(filters_path(&out_dir), &mut progress, &mut sender) {
-        send_progress(ParsingProgress::Result(Err(e.to_string())), &mut sender)?;
-        return Err(e);
-    };
-    progress = 0.9;
-    if let Err(e) = parse_pix_fmts(pix_fmts_path(&out_dir), &mut progress, &mut sender) {
+    if let Err(e) = parse_all_to_files(&out_dir, Path::new("ffmpeg"), &mut progress, &mut sender) {
         send_progress(ParsingProgress::Result(Err(e.to_string())), &mut sender)?;
         return Err(e);
-    };
+    }
     send_progress(ParsingProgress::Result(Ok(())), &mut sender)?;
     Ok(())
 }
+
+fn parse_all_to_files(
+    out_dir: &PathBuf,
+    ffmpeg: &Path,
+    progress: &mut f32,
+    sender: &mut Option<Sender<ParsingProgress>>,
+) -> Result<(), Box<dyn Error>> {
+    write_json(
+        muxers_path(out_dir),
+        &parse_muxers(ffmpeg, progress, sender)?,
+    )?;
+    *progress = 0.25;
+    write_json(
+        encoders_path(out_dir),
+        &parse_encoders(ffmpeg, progress, sender)?,
+    )?;
+    *progress = 0.5;
+    write_json(
+        filters_path(out_dir),
+        &parse_filters(ffmpeg, progress, sender)?,
+    )?;
+    *progress = 0.9;
+    write_json(
+        pix_fmts_path(out_dir),
+        &parse_pix_fmts(ffmpeg, progress, sender)?,
+    )?;
+    Ok(())
+}
+
+fn write_json(out_file: PathBuf, value: &impl Serialize) -> Result<(), Box<dyn Error>> {
+    let string: String = serde_json::to_string_pretty(value)?;
+    info!(
+        "\ndamping parsed data to the file: {}\n",
+        out_file.absolutize()?.display()
+    );
+    let mut f = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(out_file)?;
+    f.write_all(string.as_bytes())?;
+    Ok(())
+}
+
+/// The first line of `ffmpeg -version`, e.g. `ffmpeg version 6.1.1`.
+pub(crate) fn parse_version(ffmpeg: &Path) -> Result<String, Box<dyn Error>> {
+    let string = output_with_args(ffmpeg, ["-version"])?;
+    let line = string
+        .lines()
+        .next()
+        .ok_or("ffmpeg -version printed nothing")?;
+    let version = match line.find(" Copyright") {
+        Some(idx) => &line[..idx],
+        None => line,
+    };
+    Ok(version.trim().to_string())
+}
+
+/// Flags of `configuration:` line of `ffmpeg -version`, e.g. `--enable-libx264`.
+pub(crate) fn parse_build_configuration(ffmpeg: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let string = output_with_args(ffmpeg, ["-version"])?;
+    Ok(string
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("configuration:"))
+        .map(|flags| flags.split_whitespace().map(|f| f.to_string()).collect())
+        .unwrap_or_default())
+}
+
+/// Names of all encoders, without parsing their options.
+pub(crate) fn parse_encoder_names(ffmpeg: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let string = output_with_args(ffmpeg, ["-encoders"])?;
+    let enc_re = Regex::new(r"^[VAS][\w\.]{5}\s(?<name>[\w-]+)\s")?;
+    Ok(string
+        .lines()
+        .filter_map(|line| enc_re.captures(line.trim()))
+        .map(|cap| cap["name"].to_string())
+        .collect())
+}
+
+/// Names of all filters, without parsing their options.
+pub(crate) fn parse_filter_names(ffmpeg: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let string = output_with_args(ffmpeg, ["-filters"])?;
+    let filter_re = Regex::new(r"^[\w\.]{3}\s(?<name>\w+)\s+\S+->\S+\s")?;
+    Ok(string
+        .lines()
+        .filter_map(|line| filter_re.captures(line.trim()))
+        .map(|cap| cap["name"].to_string())
+        .collect())
+}
 pub fn check_parsed_paths(out_dir: PathBuf) -> bool {
     muxers_path(&out_dir).exists()
         && encoders_path(&out_dir).exists()
@@ -106,12 +182,12 @@ fn send_progress(
     }
 }
 
-fn parse_muxers(
-    out_file: PathBuf,
+pub(crate) fn parse_muxers(
+    ffmpeg: &Path,
     progress: &mut f32,
     sender: &mut Option<Sender<ParsingProgress>>,
-) -> Result<(), Box<dyn Error>> {
-    let string = output_with_args(["-muxers"])?;
+) -> Result<Vec<Muxer>, Box<dyn Error>> {
+    let string = output_with_args(ffmpeg, ["-muxers"])?;
     let lines = string.lines();
     let mux_re = Regex::new(r"\s.*E\s+(?<name>\w+)\s+(?<description>\w.*)")?;
     let ext_re = Regex::new(r"Common extensions:\s(.*)\.")?;
@@ -131,7 +207,7 @@ fn parse_muxers(
         info!("Parsing muxer '{name}'");
         inc_progress(progress, sender)?;
 
-        let info_string = output_with_args(["-h", &format!("muxer={name}")])?;
+        let info_string = output_with_args(ffmpeg, ["-h", &format!("muxer={name}")])?;
         let mut extensions = None;
         let mut video_codec = None;
         let mut audio_codec = None;
@@ -189,24 +265,17 @@ fn parse_muxers(
         };
         muxers.push(muxer);
     }
-    let muxers_string: String = serde_json::to_string_pretty(&muxers)?;
-    info!(
-        "\ndamping muxers to the file: {}\n",
-        out_file.absolutize()?.display()
-    );
-    let mut f = OpenOptions::new().write(true).create(true).open(out_file)?;
-    f.write_all(muxers_string.as_bytes())?;
-    Ok(())
+    Ok(muxers)
 }
 
-fn parse_encoders(
-    out_file: PathBuf,
+pub(crate) fn parse_encoders(
+    ffmpeg: &Path,
     progress: &mut f32,
     sender: &mut Option<Sender<ParsingProgress>>,
-) -> Result<(), Box<dyn Error>> {
-    let string = output_with_args(["-encoders"])?;
+) -> Result<Vec<Encoder>, Box<dyn Error>> {
+    let string = output_with_args(ffmpeg, ["-encoders"])?;
     let lines = string.lines();
-    let enc_re = Regex::new(r"^(?<flags>[\w\.]{6})\s(?<name>\w+)\s+(?<description>\w.*)")?;
+    let enc_re = Regex::new(r"^(?<flags>[\w\.]{6})\s(?<name>[\w-]+)\s+(?<description>\w.*)")?;
     let pix_f_re = Regex::new(r"Supported pixel formats: (.*)")?;
     let info_end_re = Regex::new(r".*AVOptions:$")?;
 
@@ -222,7 +291,7 @@ fn parse_encoders(
         info!("Parsing encoder '{name}'");
         inc_progress(progress, sender)?;
 
-        let info_string = output_with_args(["-h", &format!("encoder={name}")])?;
+        let info_string = output_with_args(ffmpeg, ["-h", &format!("encoder={name}")])?;
         let mut info = Vec::new();
         let flatgs_string = cap["flags"].to_string();
         let mut flags = flatgs_string.chars();
@@ -293,22 +362,15 @@ fn parse_encoders(
         };
         encoders.push(encoder);
     }
-    let encoders_string: String = serde_json::to_string_pretty(&encoders)?;
-    info!(
-        "\ndamping encoders to the file: {}\n",
-        out_file.absolutize()?.display()
-    );
-    let mut f = OpenOptions::new().write(true).create(true).open(out_file)?;
-    f.write_all(encoders_string.as_bytes())?;
-    Ok(())
+    Ok(encoders)
 }
 
 fn parse_filters(
-    out_file: PathBuf,
+    ffmpeg: &Path,
     progress: &mut f32,
     sender: &mut Option<Sender<ParsingProgress>>,
-) -> Result<(), Box<dyn Error>> {
-    let string = output_with_args(["-filters"])?;
+) -> Result<Vec<ParsedFilter>, Box<dyn Error>> {
+    let string = output_with_args(ffmpeg, ["-filters"])?;
     let lines = string.lines();
     let filter_re = Regex::new(
         r"^(?<flags>[\w\.]{3})\s(?<name>\w+)\s+(?<inputs>V+)->(?<outputs>V+)\s+(?<description>\w.*)",
@@ -340,7 +402,7 @@ fn parse_filters(
         info!("Parsing filter '{name}'");
         inc_progress(progress, sender)?;
 
-        let info_string = output_with_args(["-h", &format!("filter={name}")])?;
+        let info_string = output_with_args(ffmpeg, ["-h", &format!("filter={name}")])?;
         let mut info = Vec::new();
         let flatgs_string = cap["flags"].to_string();
         let mut flags = flatgs_string.chars();
@@ -401,22 +463,15 @@ fn parse_filters(
         };
         filters.push(filter);
     }
-    let filters_string: String = serde_json::to_string_pretty(&filters)?;
-    info!(
-        "\ndamping filters_string to the file: {}\n",
-        out_file.absolutize()?.display()
-    );
-    let mut f = OpenOptions::new().write(true).create(true).open(out_file)?;
-    f.write_all(filters_string.as_bytes())?;
-    Ok(())
+    Ok(filters)
 }
 
-fn parse_pix_fmts(
-    out_file: PathBuf,
+pub(crate) fn parse_pix_fmts(
+    ffmpeg: &Path,
     progress: &mut f32,
     sender: &mut Option<Sender<ParsingProgress>>,
-) -> Result<(), Box<dyn Error>> {
-    let string = output_with_args(["-pix_fmts"])?;
+) -> Result<Vec<PixelFormat>, Box<dyn Error>> {
+    let string = output_with_args(ffmpeg, ["-pix_fmts"])?;
     let lines = string.lines();
     let pix_fmt_re = Regex::new(
         r"^(?<flags>[\w\.]{5})\s(?<name>\w+)\s+(?<nb_components>\d)\s+(?<bits_per_pixel>\d+)\s+(?<bit_depth>[\d-]+)",
@@ -472,14 +527,7 @@ fn parse_pix_fmts(
         };
         pix_fmts.push(pix_format);
     }
-    let filters_string: String = serde_json::to_string_pretty(&pix_fmts)?;
-    info!(
-        "\ndamping filters_string to the file: {}\n",
-        out_file.absolutize()?.display()
-    );
-    let mut f = OpenOptions::new().write(true).create(true).open(out_file)?;
-    f.write_all(filters_string.as_bytes())?;
-    Ok(())
+    Ok(pix_fmts)
 }
 
 fn parse_option(line: &str, mut options: &mut Vec<Opt>) -> Result<ParseFlow, Box<dyn Error>> {
@@ -521,12 +569,13 @@ fn parse_option(line: &str, mut options: &mut Vec<Opt>) -> Result<ParseFlow, Box
 }
 
 fn parse_enum(line: &str, options: &mut Vec<Opt>) -> Result<ParseFlow, Box<dyn Error>> {
+    // The next option right after enum items.
+    if OPT_RE.captures(line).is_some() {
+        return parse_option(line, options);
+    }
     let Some(cap) = OPT_ENUM_RE_NAME.captures(line) else {
         return Ok(ParseFlow::Opt);
     };
-    if OPT_RE.captures(line).is_some() {
-        return Ok(ParseFlow::Opt);
-    }
     let _description = match OPT_ENUM_RE_DESC.find(line) {
         Some(d) => d.as_str().to_string(),
         None => "".to_string(),
@@ -582,9 +631,10 @@ enum ParseFlow {
 }
 
 fn output_with_args(
+    ffmpeg: &Path,
     args: impl IntoIterator<Item = impl AsRef<OsStr>>,
 ) -> Result<String, Box<dyn Error>> {
-    let mut ffmpeg = Command::new("ffmpeg");
+    let mut ffmpeg = Command::new(ffmpeg);
     ffmpeg.arg("-hide_banner");
     ffmpeg.args(args);
     let output = ffmpeg.output()?;
@@ -599,6 +649,33 @@ pub enum ParsingProgress {
     Unparsed,
 }
 
+#[test]
+fn test_parse_enum_option() {
+    let mut options = Vec::new();
+    let mut flow = ParseFlow::Opt;
+    for line in [
+        "-coder             <int>        E..V....... (from -1 to 1) (default default)",
+        "default         -1           E..V.......",
+        "cavlc           0            E..V.......",
+        "-crf               <float>      E..V....... Select the quality (from -1 to 51)",
+    ] {
+        flow = match flow {
+            ParseFlow::Enum => parse_enum(line, &mut options).unwrap(),
+            _ => parse_option(line, &mut options).unwrap(),
+        };
+    }
+    assert_eq!(options.len(), 2);
+    assert_eq!(
+        options[0].parameter,
+        OptionParameter::Enum {
+            items: vec!["default".to_string(), "cavlc".to_string()],
+            selected_idx: None
+        }
+    );
+    assert_eq!(options[1].name, "crf");
+    assert_eq!(options[1].parameter, OptionParameter::Float(None));
+}
+
 #[test]
 fn test_parsing() -> Result<(), Box<dyn Error>> {
     std::env::set_var("RUST_LOG", "debug");
@@ -0,0 +1,94 @@
+use fraction::Fraction;
+
+/// Named frame sizes of ffmpeg `video_size` option.
+pub fn built_in_resolutions() -> Vec<(&'static str, (u32, u32))> {
+    vec![
+        ("ntsc", (720, 480)),
+        ("pal", (720, 576)),
+        ("qntsc", (352, 240)),
+        ("qpal", (352, 288)),
+        ("sntsc", (640, 480)),
+        ("spal", (768, 576)),
+        ("film", (352, 240)),
+        ("ntsc-film", (352, 240)),
+        ("sqcif", (128, 96)),
+        ("qcif", (176, 144)),
+        ("cif", (352, 288)),
+        ("4cif", (704, 576)),
+        ("16cif", (1408, 1152)),
+        ("qqvga", (160, 120)),
+        ("qvga", (320, 240)),
+        ("vga", (640, 480)),
+        ("svga", (800, 600)),
+        ("xga", (1024, 768)),
+        ("uxga", (1600, 1200)),
+        ("qxga", (2048, 1536)),
+        ("sxga", (1280, 1024)),
+        ("qsxga", (2560, 2048)),
+        ("hsxga", (5120, 4096)),
+        ("wvga", (852, 480)),
+        ("wxga", (1366, 768)),
+        ("wsxga", (1600, 1024)),
+        ("wuxga", (1920, 1200)),
+        ("woxga", (2560, 1600)),
+        ("wqsxga", (3200, 2048)),
+        ("wquxga", (3840, 2400)),
+        ("whsxga", (6400, 4096)),
+        ("whuxga", (7680, 4800)),
+        ("cga", (320, 200)),
+        ("ega", (640, 350)),
+        ("hd480", (852, 480)),
+        ("hd720", (1280, 720)),
+        ("hd1080", (1920, 1080)),
+        ("2k", (2048, 1080)),
+        ("2kflat", (1998, 1080)),
+        ("2kscope", (2048, 858)),
+        ("4k", (4096, 2160)),
+        ("4kflat", (3996, 2160)),
+        ("4kscope", (4096, 1716)),
+        ("nhd", (640, 360)),
+        ("hqvga", (240, 160)),
+        ("wqvga", (400, 240)),
+        ("fwqvga", (432, 240)),
+        ("hvga", (480, 320)),
+        ("qhd", (960, 540)),
+        ("2kdci", (2048, 1080)),
+        ("4kdci", (4096, 2160)),
+        ("uhd2160", (3840, 2160)),
+        ("uhd4320", (7680, 4320)),
+    ]
+}
+
+/// Named frame rates of ffmpeg `framerate` option.
+pub fn built_in_framerates() -> Vec<(&'static str, Fraction)> {
+    vec![
+        ("ntsc", Fraction::new(30000_u64, 1001_u64)),
+        ("pal", Fraction::new(25_u64, 1_u64)),
+        ("qntsc", Fraction::new(30000_u64, 1001_u64)),
+        ("qpal", Fraction::new(25_u64, 1_u64)),
+        ("sntsc", Fraction::new(30000_u64, 1001_u64)),
+        ("spal", Fraction::new(25_u64, 1_u64)),
+        ("film", Fraction::new(24_u64, 1_u64)),
+        ("ntsc-film", Fraction::new(24000_u64, 1001_u64)),
+    ]
+}
+
+/// ffmpeg rational of the frame rate, e.g. `30000/1001` or `25`.
+pub fn fps_rational(fps: &Fraction) -> String {
+    match (fps.numer(), fps.denom()) {
+        (Some(numer), Some(&1)) => numer.to_string(),
+        (Some(numer), Some(denom)) => format!("{numer}/{denom}"),
+        _ => fps.to_string(),
+    }
+}
+
+#[test]
+fn test_fps_rational() {
+    let rationals = built_in_framerates()
+        .iter()
+        .map(|(_, fps)| fps_rational(fps))
+        .collect::<Vec<_>>();
+    assert_eq!(rationals[0], "30000/1001");
+    assert_eq!(rationals[1], "25");
+    assert_eq!(rationals[7], "24000/1001");
+}
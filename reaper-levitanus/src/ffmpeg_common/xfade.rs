@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+/// Transitions of ffmpeg `xfade` filter.
+#[allow(non_camel_case_types)]
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumString,
+    strum::EnumIter,
+)]
+pub enum XFadeTransition {
+    custom,
+    fade,
+    wipeleft,
+    wiperight,
+    wipeup,
+    wipedown,
+    slideleft,
+    slideright,
+    slideup,
+    slidedown,
+    circlecrop,
+    rectcrop,
+    distance,
+    fadeblack,
+    fadewhite,
+    radial,
+    smoothleft,
+    smoothright,
+    smoothup,
+    smoothdown,
+    circleopen,
+    circleclose,
+    vertopen,
+    vertclose,
+    horzopen,
+    horzclose,
+    dissolve,
+    pixelize,
+    diagtl,
+    diagtr,
+    diagbl,
+    diagbr,
+    hlslice,
+    hrslice,
+    vuslice,
+    vdslice,
+    hblur,
+    fadegrays,
+    wipetl,
+    wipetr,
+    wipebl,
+    wipebr,
+    squeezeh,
+    squeezev,
+    zoomin,
+    fadefast,
+    fadeslow,
+    hlwind,
+    hrwind,
+    vuwind,
+    vdwind,
+    coverleft,
+    coverright,
+    coverup,
+    coverdown,
+    revealleft,
+    revealright,
+    revealup,
+    revealdown,
+}
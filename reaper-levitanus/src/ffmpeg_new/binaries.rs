@@ -0,0 +1,272 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use itertools::Itertools;
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    levitanus_data_dir,
+    parser::{parse_build_configuration, parse_encoder_names, parse_filter_names, parse_version},
+    render::write_log_line,
+};
+
+const BINARIES_FILE_NAME: &str = "ffmpeg_binaries.json";
+
+/// Filters, used by composed video render, overlays and loudness normalization.
+pub(crate) const REQUIRED_FILTERS: &[&str] = &[
+    "fps",
+    "scale",
+    "pad",
+    "setsar",
+    "format",
+    "setpts",
+    "color",
+    "concat",
+    "xfade",
+    "null",
+    "overlay",
+    "colorchannelmixer",
+    "drawtext",
+    "sendcmd",
+    "loudnorm",
+];
+
+static CURRENT_BINARIES: Mutex<Option<FfmpegBinaries>> = Mutex::new(None);
+
+/// ffmpeg and ffprobe executables, shared by all projects.
+///
+/// Either a full path, or a name to be found in `PATH`. REAPER, started from
+/// desktop launcher, often has a different `PATH`, than a terminal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FfmpegBinaries {
+    pub ffmpeg: PathBuf,
+    pub ffprobe: PathBuf,
+}
+
+impl Default for FfmpegBinaries {
+    fn default() -> Self {
+        Self {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            ffprobe: PathBuf::from("ffprobe"),
+        }
+    }
+}
+
+impl FfmpegBinaries {
+    fn path() -> anyhow::Result<PathBuf> {
+        Ok(levitanus_data_dir()?.join(BINARIES_FILE_NAME))
+    }
+
+    fn load() -> Self {
+        let path = match Self::path() {
+            Ok(path) => path,
+            Err(e) => {
+                error!("{e}");
+                return Self::default();
+            }
+        };
+        if !path.exists() {
+            return Self::default();
+        }
+        let binaries = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(serde_json::from_str(&json)?));
+        match binaries {
+            Ok(binaries) => binaries,
+            Err(e) => {
+                error!("can not read ffmpeg binaries from {}: {e}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    /// Reads global settings. Path of settings needs REAPER API, so it is
+    /// called once from the main thread, when plugin starts.
+    pub fn load_settings() {
+        let binaries = Self::load();
+        if let Ok(mut current) = CURRENT_BINARIES.lock() {
+            *current = Some(binaries);
+        }
+    }
+
+    /// Binaries from global settings, or defaults, if settings were not
+    /// loaded. Never touches REAPER API, so safe in any thread.
+    pub fn current() -> Self {
+        CURRENT_BINARIES
+            .lock()
+            .ok()
+            .and_then(|current| current.clone())
+            .unwrap_or_default()
+    }
+
+    /// Saves settings and uses them for all new processes.
+    pub fn save(self) -> anyhow::Result<()> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(&self)?)?;
+        if let Ok(mut current) = CURRENT_BINARIES.lock() {
+            *current = Some(self);
+        }
+        Ok(())
+    }
+}
+
+/// ffmpeg process, from global settings.
+pub fn ffmpeg_command() -> Command {
+    Command::new(FfmpegBinaries::current().ffmpeg)
+}
+
+/// ffprobe process, from global settings.
+pub fn ffprobe_command() -> Command {
+    Command::new(FfmpegBinaries::current().ffprobe)
+}
+
+/// Command line of the process, as written to logs and shown to user.
+pub(super) fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy())
+        .join(" ")
+}
+
+/// Runs process to the end and returns its stderr, or None, if canceled.
+///
+/// Command line and the whole stderr are written to the job log.
+pub(super) fn run_cancelable(
+    mut command: Command,
+    cancel_flag: &AtomicBool,
+    log: &mut Option<File>,
+) -> anyhow::Result<Option<(ExitStatus, String)>> {
+    write_log_line(log, &format!("# {}", command_line(&command)));
+    let mut child = match command.stdout(Stdio::null()).stderr(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(error) => {
+            write_log_line(log, &format!("# can not spawn: {error}"));
+            return Err(error.into());
+        }
+    };
+    let stderr_handle = child.stderr.take().map(|mut stderr| {
+        thread::spawn(move || {
+            let mut output = String::new();
+            let _ = stderr.read_to_string(&mut output);
+            output
+        })
+    });
+    let status = loop {
+        if cancel_flag.load(Ordering::Relaxed) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        match child.try_wait()? {
+            Some(status) => break status,
+            None => thread::sleep(Duration::from_millis(100)),
+        }
+    };
+    let output = stderr_handle
+        .map(|handle| handle.join().unwrap_or_default())
+        .unwrap_or_default();
+    write_log_line(log, output.trim_end());
+    write_log_line(log, &format!("# exited with status {status}"));
+    Ok(Some((status, output)))
+}
+
+/// Versions and components of configured binaries.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct BinariesReport {
+    pub(super) binaries: FfmpegBinaries,
+    pub(super) ffmpeg_version: Result<String, String>,
+    pub(super) ffprobe_version: Result<String, String>,
+    /// `--enable-...` flags, ffmpeg was built with.
+    pub(super) build_configuration: Vec<String>,
+    pub(super) encoders: Vec<String>,
+    pub(super) filters: Vec<String>,
+}
+
+impl BinariesReport {
+    /// Runs `-version`, `-encoders` and `-filters`, which is fast enough
+    /// to be done on every start.
+    pub(super) fn probe(binaries: FfmpegBinaries) -> Self {
+        let run = |path: &Path| {
+            parse_version(path).map_err(|e| format!("can not run {}: {e}", path.display()))
+        };
+        let ffmpeg_version = run(&binaries.ffmpeg);
+        let ffprobe_version = run(&binaries.ffprobe);
+        let (build_configuration, encoders, filters) = match ffmpeg_version {
+            Ok(_) => (
+                parse_build_configuration(&binaries.ffmpeg).unwrap_or_default(),
+                parse_encoder_names(&binaries.ffmpeg).unwrap_or_default(),
+                parse_filter_names(&binaries.ffmpeg).unwrap_or_default(),
+            ),
+            Err(_) => Default::default(),
+        };
+        debug!(
+            "ffmpeg_new: {:?}, {:?}, {} encoders, {} filters",
+            ffmpeg_version,
+            ffprobe_version,
+            encoders.len(),
+            filters.len()
+        );
+        Self {
+            binaries,
+            ffmpeg_version,
+            ffprobe_version,
+            build_configuration,
+            encoders,
+            filters,
+        }
+    }
+
+    /// Required filters and encoders, this ffmpeg does not have.
+    pub(super) fn missing_components<'a>(
+        &self,
+        required_encoders: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<String> {
+        if self.ffmpeg_version.is_err() {
+            return Vec::new();
+        }
+        let missing_filters = REQUIRED_FILTERS
+            .iter()
+            .filter(|filter| !self.filters.iter().any(|f| f == *filter))
+            .map(|filter| format!("filter {filter}"));
+        let missing_encoders = required_encoders
+            .into_iter()
+            .filter(|encoder| !self.encoders.iter().any(|e| e == encoder))
+            .map(|encoder| format!("encoder {encoder}"));
+        missing_filters.chain(missing_encoders).collect()
+    }
+}
+
+#[test]
+fn test_missing_components() {
+    let report = BinariesReport {
+        binaries: FfmpegBinaries::default(),
+        ffmpeg_version: Ok("ffmpeg version 7.0".to_string()),
+        ffprobe_version: Ok("ffprobe version 7.0".to_string()),
+        build_configuration: Vec::new(),
+        encoders: vec!["libx264".to_string(), "aac".to_string()],
+        filters: REQUIRED_FILTERS
+            .iter()
+            .filter(|f| **f != "xfade")
+            .map(|f| f.to_string())
+            .collect(),
+    };
+    assert_eq!(
+        report.missing_components(["libx264", "prores_ks", "aac"]),
+        vec!["filter xfade".to_string(), "encoder prores_ks".to_string()]
+    );
+}
@@ -0,0 +1,233 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    binaries::{BinariesReport, FfmpegBinaries},
+    options::{Encoder, EncoderType, Muxer, PixelFormat},
+    parser::{parse_encoders, parse_filter_names, parse_muxers, parse_pix_fmts},
+};
+
+pub(super) const CAPABILITIES_FILE_NAME: &str = "ffmpeg_capabilities.json";
+/// Cache keeps capabilities of a few binaries, e.g. system and portable ffmpeg.
+const MAX_CACHED_BINARIES: usize = 4;
+
+/// What the installed ffmpeg can do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct FfmpegCapabilities {
+    pub(super) ffmpeg: PathBuf,
+    /// The first line of `ffmpeg -version`.
+    pub(super) version: String,
+    pub(super) muxers: Vec<Muxer>,
+    pub(super) encoders: Vec<Encoder>,
+    pub(super) pix_fmts: Vec<PixelFormat>,
+    pub(super) filters: Vec<String>,
+}
+
+impl FfmpegCapabilities {
+    fn parse(ffmpeg: &Path, version: String) -> anyhow::Result<Self> {
+        let (mut progress, mut sender) = (0.0, None);
+        let to_anyhow = |e: Box<dyn std::error::Error>| anyhow!("{e}");
+        info!("ffmpeg_new: parsing capabilities of {}", ffmpeg.display());
+        Ok(Self {
+            ffmpeg: ffmpeg.to_path_buf(),
+            version,
+            muxers: parse_muxers(ffmpeg, &mut progress, &mut sender).map_err(to_anyhow)?,
+            encoders: parse_encoders(ffmpeg, &mut progress, &mut sender).map_err(to_anyhow)?,
+            pix_fmts: parse_pix_fmts(ffmpeg, &mut progress, &mut sender).map_err(to_anyhow)?,
+            filters: parse_filter_names(ffmpeg).map_err(to_anyhow)?,
+        })
+    }
+
+    pub(super) fn encoder(&self, name: &str) -> Option<&Encoder> {
+        self.encoders.iter().find(|encoder| encoder.name == name)
+    }
+
+    pub(super) fn encoders_of_type(
+        &self,
+        encoder_type: EncoderType,
+    ) -> impl Iterator<Item = &Encoder> {
+        self.encoders
+            .iter()
+            .filter(move |encoder| encoder.encoder_type == encoder_type)
+    }
+
+    /// Pixel formats, or those supported by the encoder, if it is known.
+    pub(super) fn output_pixel_formats(&self, encoder: Option<&Encoder>) -> Vec<String> {
+        match encoder.and_then(|e| e.supported_pixel_formats.as_ref()) {
+            Some(formats) => formats.clone(),
+            None => self
+                .pix_fmts
+                .iter()
+                .filter(|format| format.output_support)
+                .map(|format| format.name.clone())
+                .collect(),
+        }
+    }
+
+    /// Muxer, chosen by ffmpeg for the output file extension.
+    pub(super) fn muxer_for_extension(&self, extension: &str) -> Option<&Muxer> {
+        self.muxers.iter().find(|muxer| {
+            muxer
+                .extensions
+                .iter()
+                .flatten()
+                .any(|ext| ext.trim() == extension)
+        })
+    }
+
+    /// Extensions of containers, which hold both video and audio.
+    pub(super) fn video_container_extensions(&self) -> Vec<String> {
+        self.muxers
+            .iter()
+            .filter(|muxer| muxer.video_codec.is_some() && muxer.audio_codec.is_some())
+            .flat_map(|muxer| muxer.extensions.iter().flatten())
+            .map(|ext| ext.trim().to_string())
+            .filter(|ext| !ext.is_empty())
+            .sorted()
+            .dedup()
+            .collect()
+    }
+}
+
+fn load_cache(cache_path: &Path) -> Vec<FfmpegCapabilities> {
+    if !cache_path.exists() {
+        return Vec::new();
+    }
+    let cache = std::fs::read_to_string(cache_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(serde_json::from_str(&json)?));
+    match cache {
+        Ok(cache) => cache,
+        Err(e) => {
+            error!(
+                "can not read ffmpeg capabilities from {}: {e}",
+                cache_path.display()
+            );
+            Vec::new()
+        }
+    }
+}
+
+fn save_cache(cache_path: &Path, capabilities: &FfmpegCapabilities) -> anyhow::Result<()> {
+    let mut cache = load_cache(cache_path);
+    cache.retain(|cached| cached.ffmpeg != capabilities.ffmpeg);
+    cache.insert(0, capabilities.clone());
+    cache.truncate(MAX_CACHED_BINARIES);
+    if let Some(dir) = cache_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(cache_path, serde_json::to_string(&cache)?)?;
+    Ok(())
+}
+
+/// Cached capabilities, if they belong to the same binary and version.
+fn cached_capabilities(
+    cache: Vec<FfmpegCapabilities>,
+    ffmpeg: &Path,
+    version: &str,
+) -> Option<FfmpegCapabilities> {
+    cache
+        .into_iter()
+        .find(|cached| cached.ffmpeg == ffmpeg && cached.version == version)
+}
+
+/// Result of background capability loading.
+#[derive(Debug)]
+pub(super) enum CapabilityEvent {
+    /// Versions and components of binaries, sent first.
+    Binaries(BinariesReport),
+    Capabilities(anyhow::Result<FfmpegCapabilities>),
+}
+
+/// Probes binaries and loads ffmpeg capabilities in background thread.
+///
+/// Parsing runs `ffmpeg -h` for every muxer and encoder and takes a while,
+/// so it happens only when ffmpeg binary or its version changes, or when
+/// rescan is forced.
+#[derive(Debug)]
+pub(super) struct CapabilityService {
+    receiver: Option<Receiver<CapabilityEvent>>,
+}
+
+impl CapabilityService {
+    pub(super) fn start(binaries: FfmpegBinaries, cache_path: PathBuf, force_rescan: bool) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let ffmpeg = binaries.ffmpeg.clone();
+            let report = BinariesReport::probe(binaries);
+            let version = report.ffmpeg_version.clone();
+            let _ = tx.send(CapabilityEvent::Binaries(report));
+            let result = version.map_err(|e| anyhow!(e)).and_then(|version| {
+                if !force_rescan {
+                    if let Some(cached) =
+                        cached_capabilities(load_cache(&cache_path), &ffmpeg, &version)
+                    {
+                        debug!("ffmpeg_new: capabilities of {version} are taken from cache");
+                        return Ok(cached);
+                    }
+                }
+                let capabilities = FfmpegCapabilities::parse(&ffmpeg, version)?;
+                if let Err(e) = save_cache(&cache_path, &capabilities) {
+                    error!("can not save ffmpeg capabilities: {e}");
+                }
+                Ok(capabilities)
+            });
+            let _ = tx.send(CapabilityEvent::Capabilities(result));
+        });
+        Self { receiver: Some(rx) }
+    }
+
+    pub(super) fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    /// The next event, once it is ready.
+    pub(super) fn poll(&mut self) -> Option<CapabilityEvent> {
+        let event = match self.receiver.as_ref()?.try_recv() {
+            Ok(event) => event,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => CapabilityEvent::Capabilities(Err(anyhow!(
+                "ffmpeg capabilities thread stopped unexpectedly"
+            ))),
+        };
+        if matches!(event, CapabilityEvent::Capabilities(_)) {
+            self.receiver = None;
+        }
+        Some(event)
+    }
+}
+
+#[test]
+fn test_capabilities_cache_key() {
+    let capabilities = |ffmpeg: &str, version: &str| FfmpegCapabilities {
+        ffmpeg: PathBuf::from(ffmpeg),
+        version: version.to_string(),
+        muxers: Vec::new(),
+        encoders: Vec::new(),
+        pix_fmts: Vec::new(),
+        filters: Vec::new(),
+    };
+    let cache = vec![
+        capabilities("ffmpeg", "ffmpeg version 6.1"),
+        capabilities("/opt/ffmpeg/ffmpeg", "ffmpeg version 7.0"),
+    ];
+    let found = cached_capabilities(
+        cache.clone(),
+        Path::new("/opt/ffmpeg/ffmpeg"),
+        "ffmpeg version 7.0",
+    );
+    assert_eq!(found, Some(cache[1].clone()));
+    // Upgraded ffmpeg is parsed again.
+    assert_eq!(
+        cached_capabilities(cache, Path::new("ffmpeg"), "ffmpeg version 7.1"),
+        None
+    );
+}
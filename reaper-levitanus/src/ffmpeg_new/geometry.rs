@@ -0,0 +1,293 @@
+use std::path::Path;
+
+use rea_rs::gui;
+use serde::{Deserialize, Serialize};
+
+use super::binaries::ffprobe_command;
+use crate::ffmpeg_common::video_presets::{
+    built_in_framerates, built_in_resolutions, fps_rational,
+};
+
+const DEFAULT_PAD_COLOR: &str = "black";
+
+/// Gap and letterbox color, `black` if not set.
+pub(super) fn pad_color_or_default(pad_color: &str) -> &str {
+    match pad_color.trim() {
+        "" => DEFAULT_PAD_COLOR,
+        color => color,
+    }
+}
+
+/// How source frame is fitted into the output frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub(super) enum FitMode {
+    /// Whole frame is visible, the rest is padded: letterbox or pillarbox.
+    #[default]
+    Letterbox,
+    /// Frame fills the output, overflowing edges are cut.
+    Crop,
+}
+
+impl FitMode {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Letterbox => "letterbox / pillarbox",
+            Self::Crop => "crop to fill",
+        }
+    }
+}
+
+/// Output frame of the render. Every video is converted to it.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct VideoGeometry {
+    pub(super) width: u32,
+    pub(super) height: u32,
+    /// ffmpeg rational, e.g. `30000/1001`.
+    pub(super) fps: String,
+    pub(super) fit: FitMode,
+    pub(super) pixel_format: String,
+    /// ffmpeg color of gaps and letterbox. Empty means black.
+    pub(super) pad_color: String,
+}
+
+impl Default for VideoGeometry {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            fps: "25".to_string(),
+            fit: FitMode::default(),
+            pixel_format: "yuv420p".to_string(),
+            pad_color: String::new(),
+        }
+    }
+}
+
+impl VideoGeometry {
+    /// Filter chain, converting any video stream to the geometry.
+    pub(super) fn normalize_filter(&self) -> String {
+        let Self {
+            width,
+            height,
+            fps,
+            fit,
+            pixel_format,
+            pad_color,
+        } = self;
+        let pad_color = pad_color_or_default(pad_color);
+        let fit = match fit {
+            FitMode::Letterbox => format!(
+                "scale=w={width}:h={height}:force_original_aspect_ratio=decrease:force_divisible_by=2,\
+                 pad=width={width}:height={height}:x=(ow-iw)/2:y=(oh-ih)/2:color={pad_color}"
+            ),
+            FitMode::Crop => format!(
+                "scale=w={width}:h={height}:force_original_aspect_ratio=increase,\
+                 crop=w={width}:h={height}"
+            ),
+        };
+        format!("fps={fps},{fit},setsar=1,format={pixel_format}")
+    }
+}
+
+/// Frame size and rate of the first video stream.
+fn probe_video_geometry(path: &Path) -> Option<(u32, u32, String)> {
+    let output = ffprobe_command()
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "stream=width,height,r_frame_rate",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let out = String::from_utf8_lossy(&output.stdout);
+    let mut fields = out.trim().split(',');
+    let width = fields.next()?.trim().parse().ok()?;
+    let height = fields.next()?.trim().parse().ok()?;
+    let fps = fields.next()?.trim().to_string();
+    if fps.is_empty() || fps.starts_with('0') {
+        return None;
+    }
+    Some((width, height, fps))
+}
+
+/// Output geometry settings of the project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub(super) struct GeometrySettings {
+    /// Frame size and rate follow the first video source of the target.
+    pub(super) match_source: bool,
+    pub(super) width: u32,
+    pub(super) height: u32,
+    /// ffmpeg rational, e.g. `30000/1001`.
+    pub(super) fps: String,
+    pub(super) fit: FitMode,
+    pub(super) pixel_format: String,
+}
+
+impl Default for GeometrySettings {
+    fn default() -> Self {
+        let geometry = VideoGeometry::default();
+        Self {
+            match_source: true,
+            width: geometry.width,
+            height: geometry.height,
+            fps: geometry.fps,
+            fit: geometry.fit,
+            pixel_format: geometry.pixel_format,
+        }
+    }
+}
+
+impl GeometrySettings {
+    fn fixed(&self, pad_color: &str) -> VideoGeometry {
+        let default = VideoGeometry::default();
+        let or_default = |value: &str, default: String| match value.trim() {
+            "" => default,
+            value => value.to_string(),
+        };
+        VideoGeometry {
+            width: self.width.max(2),
+            height: self.height.max(2),
+            fps: or_default(&self.fps, default.fps),
+            fit: self.fit,
+            pixel_format: or_default(&self.pixel_format, default.pixel_format),
+            pad_color: pad_color.to_string(),
+        }
+    }
+
+    /// Geometry of the render with the given first video source.
+    ///
+    /// None means the single source is kept as is. Timelines always get
+    /// geometry: they mix several sources, gaps and transitions.
+    pub(super) fn for_source(
+        &self,
+        source: Option<&Path>,
+        timeline: bool,
+        pad_color: &str,
+    ) -> Option<VideoGeometry> {
+        let fixed = self.fixed(pad_color);
+        if !self.match_source {
+            return Some(fixed);
+        }
+        if !timeline {
+            return None;
+        }
+        match source.and_then(probe_video_geometry) {
+            Some((width, height, fps)) => Some(VideoGeometry {
+                width,
+                height,
+                fps,
+                ..fixed
+            }),
+            None => Some(fixed),
+        }
+    }
+}
+
+pub(super) fn ui_geometry_settings(settings: &mut GeometrySettings, ui: &mut gui::egui::Ui) {
+    ui.checkbox(&mut settings.match_source, "Match first source")
+        .on_hover_text(
+            "Single items are rendered as is, timelines get size and rate of the first item",
+        );
+    ui.add_enabled_ui(!settings.match_source, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Resolution");
+            ui.add(gui::egui::DragValue::new(&mut settings.width).range(2..=16384));
+            ui.label("x");
+            ui.add(gui::egui::DragValue::new(&mut settings.height).range(2..=16384));
+            gui::egui::ComboBox::from_id_salt("geometry_resolutions")
+                .selected_text("built-in resolutions")
+                .show_ui(ui, |ui| {
+                    for (name, (width, height)) in built_in_resolutions() {
+                        let selected = (settings.width, settings.height) == (width, height);
+                        if ui
+                            .selectable_label(selected, format!("{name} ({width}x{height})"))
+                            .clicked()
+                        {
+                            settings.width = width;
+                            settings.height = height;
+                        }
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.label("Frame rate");
+            ui.add(
+                gui::egui::TextEdit::singleline(&mut settings.fps)
+                    .hint_text("30000/1001")
+                    .desired_width(80.0),
+            );
+            gui::egui::ComboBox::from_id_salt("geometry_framerates")
+                .selected_text("built-in framerates")
+                .show_ui(ui, |ui| {
+                    for (name, fps) in built_in_framerates() {
+                        let fps = fps_rational(&fps);
+                        if ui
+                            .selectable_label(settings.fps == fps, format!("{name} ({fps})"))
+                            .clicked()
+                        {
+                            settings.fps = fps;
+                        }
+                    }
+                });
+        });
+    });
+    ui.horizontal(|ui| {
+        gui::egui::ComboBox::from_label("Fit")
+            .selected_text(settings.fit.name())
+            .show_ui(ui, |ui| {
+                for fit in [FitMode::Letterbox, FitMode::Crop] {
+                    ui.selectable_value(&mut settings.fit, fit, fit.name());
+                }
+            });
+        ui.label("Pixel format");
+        ui.add(
+            gui::egui::TextEdit::singleline(&mut settings.pixel_format)
+                .hint_text("yuv420p")
+                .desired_width(80.0),
+        );
+    });
+}
+
+#[test]
+fn test_normalize_filter() {
+    let geometry = VideoGeometry {
+        width: 1280,
+        height: 720,
+        fps: "30000/1001".to_string(),
+        fit: FitMode::Crop,
+        pixel_format: "yuv422p10le".to_string(),
+        pad_color: String::new(),
+    };
+    assert_eq!(
+        geometry.normalize_filter(),
+        "fps=30000/1001,scale=w=1280:h=720:force_original_aspect_ratio=increase,\
+         crop=w=1280:h=720,setsar=1,format=yuv422p10le"
+    );
+    let settings = GeometrySettings {
+        match_source: false,
+        fps: " ".to_string(),
+        ..GeometrySettings::default()
+    };
+    assert_eq!(
+        settings.for_source(None, false, ""),
+        Some(VideoGeometry::default())
+    );
+    assert_eq!(
+        GeometrySettings::default().for_source(None, false, ""),
+        None
+    );
+    assert_eq!(
+        GeometrySettings::default().for_source(None, true, ""),
+        Some(VideoGeometry::default())
+    );
+}
@@ -0,0 +1,171 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use rea_rs::gui;
+
+use super::render::RenderJobSnapshot;
+
+/// Log file of a job, written next to the output, or into `log_dir`.
+pub(super) fn log_path_for_output(output: &Path, log_dir: &str) -> PathBuf {
+    let mut file_name = output.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".log");
+    let log_dir = log_dir.trim();
+    if log_dir.is_empty() {
+        output.with_file_name(file_name)
+    } else {
+        Path::new(log_dir).join(file_name)
+    }
+}
+
+/// ffmpeg log of one render job, read from disk.
+#[derive(Debug)]
+pub(super) struct LogViewerState {
+    job_id: u64,
+    job_name: String,
+    command: Option<String>,
+    log_path: PathBuf,
+    text: String,
+    error: Option<String>,
+    search: String,
+    /// Byte ranges of lines, matching `search`. Updated only when the
+    /// text or search changes: ffmpeg logs can be large.
+    lines: Vec<Range<usize>>,
+}
+
+impl LogViewerState {
+    pub(super) fn open(job: &RenderJobSnapshot) -> Self {
+        let mut viewer = Self {
+            job_id: job.id,
+            job_name: job.name.clone(),
+            command: job.command.clone(),
+            log_path: job.log_path.clone(),
+            text: String::new(),
+            error: None,
+            search: String::new(),
+            lines: Vec::new(),
+        };
+        viewer.reload();
+        viewer
+    }
+
+    fn reload(&mut self) {
+        match std::fs::read_to_string(&self.log_path) {
+            Ok(text) => {
+                self.text = text;
+                self.error = None;
+            }
+            Err(error) => {
+                self.text.clear();
+                self.error = Some(format!("can not read {}: {error}", self.log_path.display()));
+            }
+        }
+        self.refilter();
+    }
+
+    fn refilter(&mut self) {
+        self.lines = matching_lines(&self.text, &self.search);
+    }
+
+    fn reveal(&mut self) {
+        let dir = self.log_path.parent().unwrap_or(Path::new("."));
+        if let Err(error) = open::that(dir) {
+            self.error = Some(format!("can not open {}: {error}", dir.display()));
+        }
+    }
+}
+
+/// Byte ranges of lines, which contain `search`, ignoring case.
+fn matching_lines(text: &str, search: &str) -> Vec<Range<usize>> {
+    let search = search.to_lowercase();
+    let mut start = 0;
+    text.split_inclusive('\n')
+        .filter_map(|line| {
+            let range = start..start + line.trim_end_matches(['\r', '\n']).len();
+            start += line.len();
+            let line = &text[range.clone()];
+            (search.is_empty() || line.to_lowercase().contains(&search)).then_some(range)
+        })
+        .collect()
+}
+
+/// Draws log of the job, returns false, when viewer is closed.
+pub(super) fn ui_log_viewer(viewer: &mut LogViewerState, ui: &mut gui::egui::Ui) -> bool {
+    let mut keep_open = true;
+    ui.horizontal(|ui| {
+        ui.label(gui::egui::RichText::new(format!("Log: {}", viewer.job_name)).strong());
+        if ui
+            .add_enabled(
+                viewer.command.is_some(),
+                gui::egui::Button::new("Copy command"),
+            )
+            .clicked()
+        {
+            if let Some(command) = &viewer.command {
+                ui.ctx().copy_text(command.clone());
+            }
+        }
+        if ui.button("Reveal log").clicked() {
+            viewer.reveal();
+        }
+        if ui.button("Reload").clicked() {
+            viewer.reload();
+        }
+        if ui.button("Close").clicked() {
+            keep_open = false;
+        }
+    });
+    ui.label(viewer.log_path.display().to_string());
+    if let Some(error) = &viewer.error {
+        ui.colored_label(gui::egui::Color32::RED, error);
+    }
+    ui.horizontal(|ui| {
+        ui.label("Search");
+        if ui.text_edit_singleline(&mut viewer.search).changed() {
+            viewer.refilter();
+        }
+    });
+
+    if !viewer.search.is_empty() {
+        ui.label(format!("{} matching lines", viewer.lines.len()));
+    }
+    let row_height = ui.text_style_height(&gui::egui::TextStyle::Monospace);
+    gui::egui::ScrollArea::both()
+        .id_salt(("render_job_log", viewer.job_id))
+        .max_height(240.0)
+        .show_rows(ui, row_height, viewer.lines.len(), |ui, rows| {
+            for range in &viewer.lines[rows] {
+                ui.monospace(&viewer.text[range.clone()]);
+            }
+        });
+    keep_open
+}
+
+#[test]
+fn test_log_path_for_output() {
+    let output = Path::new("/music/song.mkv");
+    assert_eq!(
+        log_path_for_output(output, ""),
+        PathBuf::from("/music/song.mkv.log")
+    );
+    assert_eq!(
+        log_path_for_output(output, "/tmp/logs"),
+        PathBuf::from("/tmp/logs/song.mkv.log")
+    );
+}
+
+#[test]
+fn test_matching_lines() {
+    let text = "ffmpeg version 7.0\r\nInput #0\n\nError while decoding\nerror: end";
+    let lines = |search: &str| {
+        matching_lines(text, search)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(lines("").len(), 5);
+    assert_eq!(lines("ERROR"), vec!["Error while decoding", "error: end"]);
+    assert_eq!(lines("version"), vec!["ffmpeg version 7.0"]);
+    assert!(lines("missing").is_empty());
+}
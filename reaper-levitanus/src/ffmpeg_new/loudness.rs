@@ -0,0 +1,217 @@
+use std::{collections::HashMap, fmt, fs::File, path::Path, sync::atomic::AtomicBool};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use super::binaries::{ffmpeg_command, ffprobe_command, run_cancelable};
+
+/// EBU R128 target of `loudnorm` filter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct LoudnessTarget {
+    /// Integrated loudness, LUFS.
+    pub(super) integrated: f64,
+    /// Maximum true peak, dBTP.
+    pub(super) true_peak: f64,
+    /// Loudness range, LU.
+    pub(super) range: f64,
+}
+
+impl LoudnessTarget {
+    /// Well-known targets, offered by preset editor.
+    pub(super) fn well_known() -> [(&'static str, Self); 2] {
+        [
+            (
+                "streaming",
+                Self {
+                    integrated: -14.0,
+                    true_peak: -1.0,
+                    range: 11.0,
+                },
+            ),
+            (
+                "broadcast",
+                Self {
+                    integrated: -23.0,
+                    true_peak: -1.0,
+                    range: 11.0,
+                },
+            ),
+        ]
+    }
+
+    fn filter(&self) -> String {
+        format!(
+            "loudnorm=I={}:TP={}:LRA={}",
+            self.integrated, self.true_peak, self.range
+        )
+    }
+
+    /// Filter of the second pass, applying measured values.
+    ///
+    /// `loudnorm` works at 192 kHz, so output is resampled back to the
+    /// source rate.
+    pub(super) fn apply_filter(&self, measured: &LoudnessMeasurement) -> String {
+        let mut filter = format!(
+            "{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}\
+             :linear=true:print_format=summary",
+            self.filter(),
+            measured.integrated,
+            measured.true_peak,
+            measured.range,
+            measured.threshold,
+            measured.offset
+        );
+        if let Some(sample_rate) = measured.sample_rate {
+            filter.push_str(&format!(",aresample={sample_rate}"));
+        }
+        filter
+    }
+}
+
+impl Default for LoudnessTarget {
+    fn default() -> Self {
+        Self::well_known()[0].1.clone()
+    }
+}
+
+/// Values, measured by the first `loudnorm` pass.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct LoudnessMeasurement {
+    /// Integrated loudness, LUFS.
+    pub(super) integrated: f64,
+    /// True peak, dBTP.
+    pub(super) true_peak: f64,
+    /// Loudness range, LU.
+    pub(super) range: f64,
+    pub(super) threshold: f64,
+    /// Gain offset, suggested by `loudnorm`.
+    pub(super) offset: f64,
+    pub(super) sample_rate: Option<u32>,
+}
+
+impl LoudnessMeasurement {
+    /// Parses json, printed by `loudnorm` at the end of ffmpeg output.
+    fn from_loudnorm_output(output: &str) -> anyhow::Result<Self> {
+        let start = output
+            .rfind('{')
+            .ok_or_else(|| anyhow!("loudnorm printed no measurements"))?;
+        let end = output[start..]
+            .find('}')
+            .map(|end| start + end + 1)
+            .ok_or_else(|| anyhow!("loudnorm measurements are incomplete"))?;
+        let values: HashMap<String, String> = serde_json::from_str(&output[start..end])?;
+        let value = |key: &str| -> anyhow::Result<f64> {
+            let value = values
+                .get(key)
+                .ok_or_else(|| anyhow!("loudnorm did not measure {key}"))?;
+            value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| anyhow!("{key} is {value}: audio is probably silent"))
+        };
+        Ok(Self {
+            integrated: value("input_i")?,
+            true_peak: value("input_tp")?,
+            range: value("input_lra")?,
+            threshold: value("input_thresh")?,
+            offset: value("target_offset")?,
+            sample_rate: None,
+        })
+    }
+}
+
+impl fmt::Display for LoudnessMeasurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} LUFS, {:.1} dBTP, LRA {:.1} LU",
+            self.integrated, self.true_peak, self.range
+        )
+    }
+}
+
+/// The first pass: measures loudness of the first audio stream.
+///
+/// Returns None, if canceled.
+pub(super) fn measure_loudness(
+    path: &Path,
+    target: &LoudnessTarget,
+    cancel_flag: &AtomicBool,
+    log: &mut Option<File>,
+) -> anyhow::Result<Option<LoudnessMeasurement>> {
+    let mut command = ffmpeg_command();
+    command
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(path)
+        .args([
+            "-map".to_string(),
+            "0:a:0".to_string(),
+            "-af".to_string(),
+            format!("{}:print_format=json", target.filter()),
+            "-f".to_string(),
+            "null".to_string(),
+            "-".to_string(),
+        ]);
+    let Some((status, output)) = run_cancelable(command, cancel_flag, log)
+        .map_err(|e| anyhow!("can not run ffmpeg to measure loudness: {e}"))?
+    else {
+        return Ok(None);
+    };
+    if !status.success() {
+        return Err(anyhow!(
+            "loudness measurement failed with {status}: {}",
+            output.lines().last().unwrap_or_default()
+        ));
+    }
+    let mut measurement = LoudnessMeasurement::from_loudnorm_output(&output)?;
+    measurement.sample_rate = probe_sample_rate(path);
+    Ok(Some(measurement))
+}
+
+fn probe_sample_rate(path: &Path) -> Option<u32> {
+    let output = ffprobe_command()
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "a:0",
+            "-show_entries",
+            "stream=sample_rate",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path)
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .trim_end_matches(',')
+        .parse()
+        .ok()
+}
+
+#[test]
+fn test_loudnorm_measurement() {
+    let output = "[Parsed_loudnorm_0 @ 0x5581] \n{\n\t\"input_i\" : \"-27.61\",\n\
+                  \t\"input_tp\" : \"-4.47\",\n\t\"input_lra\" : \"18.06\",\n\
+                  \t\"input_thresh\" : \"-39.20\",\n\t\"output_i\" : \"-14.01\",\n\
+                  \t\"output_tp\" : \"-1.00\",\n\t\"output_lra\" : \"7.50\",\n\
+                  \t\"output_thresh\" : \"-25.41\",\n\t\"normalization_type\" : \"dynamic\",\n\
+                  \t\"target_offset\" : \"0.01\"\n}\n";
+    let mut measured = LoudnessMeasurement::from_loudnorm_output(output).unwrap();
+    measured.sample_rate = Some(48000);
+    assert_eq!(measured.integrated, -27.61);
+    assert_eq!(measured.offset, 0.01);
+    assert_eq!(
+        LoudnessTarget::default().apply_filter(&measured),
+        "loudnorm=I=-14:TP=-1:LRA=11:measured_I=-27.61:measured_TP=-4.47:\
+         measured_LRA=18.06:measured_thresh=-39.2:offset=0.01:linear=true:\
+         print_format=summary,aresample=48000"
+    );
+    assert!(LoudnessMeasurement::from_loudnorm_output(
+        "{\"input_i\" : \"-inf\", \"input_tp\" : \"-inf\"}"
+    )
+    .is_err());
+}
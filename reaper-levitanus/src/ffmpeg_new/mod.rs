@@ -1,7 +1,7 @@
 use std::{
     cell::Cell,
     cell::RefCell,
-    collections::VecDeque,
+    collections::{BTreeMap, VecDeque},
     error::Error,
     fmt,
     path::{Path, PathBuf},
@@ -13,23 +13,57 @@ use std::{
 };
 
 use anyhow::anyhow;
+use itertools::Itertools;
 use log::debug;
 use rea_rs::{
     gui::{self, DockableEguiWindow},
-    CommandId, ControlSurface, ExtState, MessageBoxType, MessageBoxValue, Mutable, Project,
-    Reaper, Track,
+    CommandId, ControlSurface, ExtState, MessageBoxType, MessageBoxValue, Mutable, Project, Reaper,
+    Track,
 };
 use serde::{Deserialize, Serialize};
 
+pub mod binaries;
+mod capabilities;
+mod geometry;
+mod log_viewer;
+mod loudness;
+mod overlays;
+mod presets;
 mod render;
 mod render_targets;
+mod smart_cut;
+mod timeline;
+mod validation;
+mod variants;
+mod verification;
 
-use render::{RenderEngine, RenderJobDefinition, RenderJobStatus, RenderStateSnapshot};
+pub use crate::ffmpeg_common::{options, parser};
+
+use binaries::{BinariesReport, FfmpegBinaries};
+use capabilities::{
+    CapabilityEvent, CapabilityService, FfmpegCapabilities, CAPABILITIES_FILE_NAME,
+};
+use geometry::{ui_geometry_settings, GeometrySettings};
+use log_viewer::{log_path_for_output, ui_log_viewer, LogViewerState};
+use options::EncoderType;
+use overlays::{ui_overlay_settings, OverlaySettings, TargetLabels};
+use presets::{
+    load_encoder_presets, save_encoder_presets, ui_preset_editor, EncoderPreset, PresetEditorState,
+};
+
+use render::{
+    eta_string, JobVideoInput, RenderEngine, RenderJobCommand, RenderJobDefinition,
+    RenderJobStatus, RenderStateSnapshot, SubtitleInput, PAUSE_SUPPORTED,
+};
 use render_targets::{
-    build_render_targets, AvailbleForRender, RenderTarget, DEFAULT_RENDER_TARGETS_BUF_SIZE,
+    build_render_targets, collect_target_labels, AvailbleForRender, RenderTarget,
+    DEFAULT_RENDER_TARGETS_BUF_SIZE,
 };
 
 use crate::ffmpeg_new::render_targets::FALLBACK_RENDER_TARGETS_BUF_SIZE;
+use crate::subtitles::{subtitle_path_for_target, SubtitleFormat};
+use validation::validate_jobs;
+use variants::{duplicate_outputs, ui_output_variants, OutputVariant};
 
 pub static BACKEND_ID_STRING: &str = "LevitanusFfmpegGuiNew";
 const AUTO_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
@@ -42,29 +76,65 @@ enum GuiToSurfaceMessage {
     RequestRenderTargets,
     RequestRender,
     CancelRender,
+    RenderJob(u64, RenderJobCommand),
+    RetryFailedJobs,
+    SavePresets(Vec<EncoderPreset>),
+    RescanCapabilities,
+    SaveBinaries(FfmpegBinaries),
 }
 
 const GUI_STATE_EXT_SECTION: &str = "Levitanus";
 const GUI_STATE_EXT_KEY: &str = "FFMPEG_NEW_GUI_STATE";
+const DEFAULT_MUXER: &str = "mkv";
+
+/// Directory for global plugin data, shared by all projects.
+fn levitanus_data_dir() -> anyhow::Result<PathBuf> {
+    let resource_path = Reaper::get()
+        .get_resource_path()
+        .map_err(|e| anyhow!("can not get resource path: {e}"))?;
+    Ok(PathBuf::from(resource_path)
+        .join("Data")
+        .join("reaper-levitanus"))
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 #[serde(default)]
 struct GuiPersistedState {
-    selected_muxer: usize,
-    selected_video_codec: usize,
-    selected_audio_codec: usize,
+    /// Extension of the output container.
+    muxer: String,
+    /// Name of the global encoder preset.
+    video_preset: String,
+    audio_preset: String,
     parallel_render: bool,
     render_workers: usize,
     use_rendered_video: bool,
+    mux_subtitles: bool,
+    /// ffmpeg color of gaps between video items and of letterbox. Empty means
+    /// black.
+    pad_color: String,
+    /// Directory of ffmpeg logs. Empty means next to the output.
+    log_dir: String,
+    /// Re-encode only partial GOPs at the edges of a copied window.
+    smart_cut: bool,
+    /// Additional outputs of every render target.
+    variants: Vec<OutputVariant>,
+    /// Output frame, rate and pixel format.
+    geometry: GeometrySettings,
+    /// Overlays by render target name, used instead of the preset overlay.
+    target_overlays: BTreeMap<String, OverlaySettings>,
 }
 
 impl GuiPersistedState {
     fn sanitize_for_elements(&mut self, elements: &GuiElementsState) {
-        self.selected_muxer = clamp_selected_idx(self.selected_muxer, elements.muxers.len());
-        self.selected_video_codec =
-            clamp_selected_idx(self.selected_video_codec, elements.video_codecs.len());
-        self.selected_audio_codec =
-            clamp_selected_idx(self.selected_audio_codec, elements.audio_codecs.len());
+        if !elements.muxers.contains(&self.muxer) {
+            self.muxer = if elements.muxers.iter().any(|m| m == DEFAULT_MUXER) {
+                DEFAULT_MUXER.to_string()
+            } else {
+                elements.muxers.first().cloned().unwrap_or_default()
+            };
+        }
+        self.video_preset = sanitize_preset_name(&self.video_preset, elements, EncoderType::Video);
+        self.audio_preset = sanitize_preset_name(&self.audio_preset, elements, EncoderType::Audio);
         self.render_workers = self.render_workers.clamp(1, 64);
     }
 }
@@ -76,8 +146,14 @@ struct GuiElementsState {
     render_targets_revision: u64,
     render_state: RenderStateSnapshot,
     muxers: Vec<String>,
-    video_codecs: Vec<String>,
-    audio_codecs: Vec<String>,
+    presets: Vec<EncoderPreset>,
+    /// None until parsed, or if ffmpeg can not be run.
+    capabilities: Option<Arc<FfmpegCapabilities>>,
+    capabilities_scanning: bool,
+    capabilities_error: Option<String>,
+    binaries: FfmpegBinaries,
+    /// None until binaries are probed.
+    binaries_report: Option<BinariesReport>,
 }
 
 impl Default for GuiElementsState {
@@ -88,27 +164,61 @@ impl Default for GuiElementsState {
             render_targets_revision: 0,
             render_state: RenderStateSnapshot::default(),
             muxers: vec!["mkv".to_string(), "mp4".to_string(), "mov".to_string()],
-            video_codecs: vec![
-                "copy".to_string(),
-                "libx264".to_string(),
-                "libx265".to_string(),
-                "prores_ks".to_string(),
-            ],
-            audio_codecs: vec![
-                "copy".to_string(),
-                "aac".to_string(),
-                "pcm_s16le".to_string(),
-                "flac".to_string(),
-            ],
+            presets: Vec::new(),
+            capabilities: None,
+            capabilities_scanning: false,
+            capabilities_error: None,
+            binaries: FfmpegBinaries::default(),
+            binaries_report: None,
         }
     }
 }
 
-fn clamp_selected_idx(idx: usize, len: usize) -> usize {
-    if len == 0 {
-        0
-    } else {
-        idx.min(len - 1)
+impl GuiElementsState {
+    fn presets_of_type(&self, encoder_type: EncoderType) -> impl Iterator<Item = &EncoderPreset> {
+        self.presets
+            .iter()
+            .filter(move |preset| preset.encoder_type == encoder_type)
+    }
+
+    fn preset(&self, name: &str, encoder_type: EncoderType) -> Option<&EncoderPreset> {
+        self.presets_of_type(encoder_type)
+            .find(|preset| preset.name == name)
+    }
+}
+
+/// Keeps preset name if it still exists, otherwise picks the first preset
+/// of the type.
+fn sanitize_preset_name(
+    name: &str,
+    elements: &GuiElementsState,
+    encoder_type: EncoderType,
+) -> String {
+    if elements.preset(name, encoder_type.clone()).is_some() {
+        return name.to_string();
+    }
+    elements
+        .presets_of_type(encoder_type)
+        .next()
+        .map(|preset| preset.name.clone())
+        .unwrap_or_default()
+}
+
+/// Subtitle codec, which can be muxed into the container.
+///
+/// Containers without known text subtitle codec get the default subtitle
+/// codec of their muxer.
+fn subtitle_codec_for_muxer(
+    muxer: &str,
+    capabilities: Option<&FfmpegCapabilities>,
+) -> Option<String> {
+    match muxer {
+        "mkv" => Some("srt".to_string()),
+        "mp4" | "mov" => Some("mov_text".to_string()),
+        "webm" => Some("webvtt".to_string()),
+        _ => capabilities
+            .and_then(|capabilities| capabilities.muxer_for_extension(muxer))
+            .and_then(|muxer| muxer.subtitle_codec.clone()),
     }
 }
 
@@ -122,8 +232,8 @@ fn render_target_name(path: &PathBuf) -> String {
 fn render_status(status: &AvailbleForRender) -> (&'static str, gui::egui::Color32) {
     match status {
         AvailbleForRender::Ok => ("Ok", gui::egui::Color32::GREEN),
-        AvailbleForRender::NoVideo => ("NoVideo", gui::egui::Color32::YELLOW),
-        AvailbleForRender::OutOfBounds(_) => ("OutOfBounds", gui::egui::Color32::RED),
+        AvailbleForRender::Gaps => ("Gaps", gui::egui::Color32::YELLOW),
+        AvailbleForRender::NoVideo => ("NoVideo", gui::egui::Color32::RED),
     }
 }
 
@@ -160,6 +270,7 @@ struct FfmpegGuiSurface {
     gui_elements_state: Arc<Mutex<GuiElementsState>>,
     gui_persisted_state: Arc<Mutex<GuiPersistedState>>,
     render_engine: RenderEngine,
+    capability_service: CapabilityService,
     render_targets_buf_size: Cell<usize>,
     last_auto_refresh_at: Mutex<Instant>,
 }
@@ -176,7 +287,12 @@ impl FfmpegGuiSurface {
         let is_window_alive = Arc::new(AtomicBool::new(true));
         let message_bus = Arc::new(MessageBus::default());
         let pending_ops = Arc::new(Mutex::new(VecDeque::new()));
-        let gui_elements_state = Arc::new(Mutex::new(GuiElementsState::default()));
+        let gui_elements_state = Arc::new(Mutex::new(GuiElementsState {
+            presets: load_encoder_presets(),
+            capabilities_scanning: true,
+            binaries: FfmpegBinaries::current(),
+            ..GuiElementsState::default()
+        }));
         let initial_persisted_state = {
             let mut state = Self::load_gui_state_from_project();
             let elements = gui_elements_state
@@ -202,6 +318,10 @@ impl FfmpegGuiSurface {
                 gui_persisted_state: Arc::clone(&gui_persisted_state),
                 last_gui_state_snapshot: None,
                 last_render_targets_revision: None,
+                preset_editor: PresetEditorState::default(),
+                binaries_draft: None,
+                log_viewer: None,
+                overlay_target: None,
             },
             |_ctx, _queue, state| {
                 let gui_state_snapshot = state
@@ -245,6 +365,15 @@ impl FfmpegGuiSurface {
                         .auto_shrink([false, false])
                         .show(ui, |ui| {
                             ui.heading("FFmpeg render");
+                            ui_capabilities_status(
+                                state,
+                                &gui_elements_snapshot,
+                                &gui_persisted_snapshot,
+                                ui,
+                            );
+                            gui::egui::CollapsingHeader::new("ffmpeg binaries").show(ui, |ui| {
+                                ui_binaries_settings(state, &gui_elements_snapshot, ui);
+                            });
                             ui.separator();
 
                             ui_render_settings(
@@ -252,8 +381,36 @@ impl FfmpegGuiSurface {
                                 &mut gui_persisted_snapshot,
                                 ui,
                             );
+                            gui::egui::CollapsingHeader::new("Output geometry").show(ui, |ui| {
+                                ui_geometry_settings(&mut gui_persisted_snapshot.geometry, ui);
+                            });
+                            gui::egui::CollapsingHeader::new("Output variants").show(ui, |ui| {
+                                ui_output_variants(
+                                    &mut gui_persisted_snapshot.variants,
+                                    &gui_elements_snapshot,
+                                    ui,
+                                );
+                            });
+                            gui::egui::CollapsingHeader::new("Encoder presets").show(ui, |ui| {
+                                if let Some(presets) = ui_preset_editor(
+                                    &mut state.preset_editor,
+                                    &gui_elements_snapshot.presets,
+                                    gui_elements_snapshot.capabilities.as_deref(),
+                                    ui,
+                                ) {
+                                    state
+                                        .message_bus
+                                        .send_to_surface(GuiToSurfaceMessage::SavePresets(presets));
+                                }
+                            });
                             ui.separator();
                             ui_render_queue_controls(state, &gui_elements_snapshot, ui);
+                            if let Some(viewer) = &mut state.log_viewer {
+                                ui.separator();
+                                if !ui_log_viewer(viewer, ui) {
+                                    state.log_viewer = None;
+                                }
+                            }
 
                             ui.separator();
                             if ui.button("Refresh render targets").clicked() {
@@ -261,7 +418,17 @@ impl FfmpegGuiSurface {
                                     .message_bus
                                     .send_to_surface(GuiToSurfaceMessage::RequestRenderTargets);
                             }
-                            ui_render_targets_list(&gui_elements_snapshot, ui);
+                            ui_render_targets_list(
+                                &gui_elements_snapshot,
+                                &gui_persisted_snapshot,
+                                &mut state.overlay_target,
+                                ui,
+                            );
+                            ui_target_overlay(
+                                &mut state.overlay_target,
+                                &mut gui_persisted_snapshot,
+                                ui,
+                            );
 
                             if let Some(error) = &gui_elements_snapshot.error {
                                 ui.colored_label(gui::egui::Color32::RED, error);
@@ -291,6 +458,7 @@ impl FfmpegGuiSurface {
             gui_elements_state,
             gui_persisted_state,
             render_engine: RenderEngine::default(),
+            capability_service: Self::start_capability_service(false)?,
             render_targets_buf_size: Cell::new(DEFAULT_RENDER_TARGETS_BUF_SIZE),
             last_auto_refresh_at: Mutex::new(Instant::now()),
         };
@@ -298,6 +466,79 @@ impl FfmpegGuiSurface {
         Ok(surface)
     }
 
+    fn start_capability_service(force_rescan: bool) -> anyhow::Result<CapabilityService> {
+        let cache_path = levitanus_data_dir()?.join(CAPABILITIES_FILE_NAME);
+        Ok(CapabilityService::start(
+            FfmpegBinaries::current(),
+            cache_path,
+            force_rescan,
+        ))
+    }
+
+    /// Probes binaries again, capabilities are parsed again only if forced
+    /// or if ffmpeg binary changed.
+    fn rescan_capabilities(&mut self, force_rescan: bool) {
+        if force_rescan && self.capability_service.is_running() {
+            return;
+        }
+        match Self::start_capability_service(force_rescan) {
+            Ok(service) => {
+                self.capability_service = service;
+                if let Ok(mut gui_state) = self.gui_elements_state.lock() {
+                    gui_state.capabilities_scanning = true;
+                    gui_state.capabilities_error = None;
+                    gui_state.binaries = FfmpegBinaries::current();
+                    gui_state.binaries_report = None;
+                    gui_state.render_targets_revision =
+                        gui_state.render_targets_revision.saturating_add(1);
+                }
+            }
+            Err(error) => {
+                if let Ok(mut gui_state) = self.gui_elements_state.lock() {
+                    gui_state.capabilities_error = Some(error.to_string());
+                }
+            }
+        }
+    }
+
+    fn sync_capabilities_to_gui(&mut self) {
+        let Some(event) = self.capability_service.poll() else {
+            return;
+        };
+        let Ok(mut gui_state) = self.gui_elements_state.lock() else {
+            return;
+        };
+        let result = match event {
+            CapabilityEvent::Binaries(report) => {
+                gui_state.binaries_report = Some(report);
+                gui_state.render_targets_revision =
+                    gui_state.render_targets_revision.saturating_add(1);
+                return;
+            }
+            CapabilityEvent::Capabilities(result) => result,
+        };
+        gui_state.capabilities_scanning = false;
+        match result {
+            Ok(capabilities) => {
+                debug!(
+                    "ffmpeg_new: capabilities of {} are loaded",
+                    capabilities.version
+                );
+                let muxers = capabilities.video_container_extensions();
+                if !muxers.is_empty() {
+                    gui_state.muxers = muxers;
+                }
+                gui_state.capabilities = Some(Arc::new(capabilities));
+                gui_state.capabilities_error = None;
+            }
+            Err(error) => {
+                debug!("ffmpeg_new: can not load capabilities: {error}");
+                gui_state.capabilities_error = Some(error.to_string());
+            }
+        }
+        gui_state.render_targets_revision = gui_state.render_targets_revision.saturating_add(1);
+    }
+
     fn refresh_render_targets(&self) {
         let mut pr = Reaper::get().current_project();
         let cached_size = self.render_targets_buf_size.get().max(2);
@@ -400,10 +641,48 @@ impl FfmpegGuiSurface {
                 GuiToSurfaceMessage::CancelRender => {
                     self.render_engine.request_cancel();
                 }
+                GuiToSurfaceMessage::RenderJob(id, command) => {
+                    let result = self.render_engine.job_command(id, command);
+                    self.show_render_error(result);
+                }
+                GuiToSurfaceMessage::RetryFailedJobs => {
+                    let result = self.render_engine.retry_failed();
+                    self.show_render_error(result);
+                }
+                GuiToSurfaceMessage::RescanCapabilities => {
+                    self.rescan_capabilities(true);
+                }
+                GuiToSurfaceMessage::SaveBinaries(binaries) => match binaries.save() {
+                    Ok(()) => self.rescan_capabilities(false),
+                    Err(error) => {
+                        if let Ok(mut gui_state) = self.gui_elements_state.lock() {
+                            gui_state.error =
+                                Some(format!("can not save ffmpeg binaries: {error}"));
+                        }
+                    }
+                },
+                GuiToSurfaceMessage::SavePresets(presets) => {
+                    let result = save_encoder_presets(&presets);
+                    if let Ok(mut gui_state) = self.gui_elements_state.lock() {
+                        gui_state.error = result
+                            .err()
+                            .map(|error| format!("can not save encoder presets: {error}"));
+                        gui_state.presets = presets;
+                        gui_state.render_targets_revision =
+                            gui_state.render_targets_revision.saturating_add(1);
+                    }
+                }
             }
         }
     }
 
+    fn show_render_error(&self, result: anyhow::Result<()>) {
+        if let Ok(mut gui_state) = self.gui_elements_state.lock() {
+            gui_state.error = result.err().map(|error| error.to_string());
+            gui_state.render_targets_revision = gui_state.render_targets_revision.saturating_add(1);
+        }
+    }
+
     fn start_render(&mut self) -> anyhow::Result<()> {
         let elements = self
             .gui_elements_state
@@ -463,97 +742,194 @@ impl FfmpegGuiSurface {
 
         let invalid_targets = render_targets
             .iter()
-            .filter(|target| !matches!(target.availble_for_render, AvailbleForRender::Ok))
+            .filter(|target| matches!(target.availble_for_render, AvailbleForRender::NoVideo))
             .count();
-        if invalid_targets > 0 {
+        if invalid_targets > 0 && !persisted.use_rendered_video {
             return Err(anyhow!(
-                "{invalid_targets} render targets are not ready for video render (NoVideo/OutOfBounds)"
+                "{invalid_targets} render targets are not ready for video render (NoVideo)"
             ));
         }
 
-        let muxer = elements
-            .muxers
-            .get(persisted.selected_muxer)
-            .cloned()
-            .ok_or_else(|| anyhow!("selected muxer is invalid"))?;
-        let selected_video_codec = elements
-            .video_codecs
-            .get(persisted.selected_video_codec)
-            .cloned()
-            .ok_or_else(|| anyhow!("selected video codec is invalid"))?;
-        let audio_codec = elements
-            .audio_codecs
-            .get(persisted.selected_audio_codec)
+        if persisted.muxer.is_empty() {
+            return Err(anyhow!("selected muxer is invalid"));
+        }
+        let main_video_preset = if persisted.use_rendered_video {
+            EncoderPreset::copy(EncoderType::Video)
+        } else {
+            elements
+                .preset(&persisted.video_preset, EncoderType::Video)
+                .cloned()
+                .ok_or_else(|| anyhow!("selected video preset is invalid"))?
+        };
+        let main_audio_preset = elements
+            .preset(&persisted.audio_preset, EncoderType::Audio)
             .cloned()
-            .ok_or_else(|| anyhow!("selected audio codec is invalid"))?;
+            .ok_or_else(|| anyhow!("selected audio preset is invalid"))?;
+        // Main settings are the first output, variants add more.
+        let mut outputs = vec![(
+            OutputVariant {
+                muxer: persisted.muxer.clone(),
+                ..OutputVariant::default()
+            },
+            Some(main_video_preset),
+            main_audio_preset,
+        )];
+        for variant in persisted.variants.iter() {
+            let (video_preset, audio_preset) = variant.resolve(&elements)?;
+            outputs.push((variant.clone(), video_preset, audio_preset));
+        }
 
+        let pr = Reaper::get().current_project();
         let mut jobs = Vec::new();
         for target in render_targets {
-            if !matches!(target.availble_for_render, AvailbleForRender::Ok) {
-                continue;
-            }
-
-            let (input_video_path, output_path, use_source_window, video_codec) =
-                if persisted.use_rendered_video {
-                    let rendered_video_path = target.path.with_extension(&muxer);
-                    if !rendered_video_path.exists() {
-                        return Err(anyhow!(
-                            "use rendered video enabled, but source video does not exist: {}",
-                            rendered_video_path.display()
-                        ));
+            let (video, output_base) = if persisted.use_rendered_video {
+                let rendered_video_path = target.path.with_extension(&persisted.muxer);
+                if !rendered_video_path.exists() {
+                    return Err(anyhow!(
+                        "use rendered video enabled, but source video does not exist: {}",
+                        rendered_video_path.display()
+                    ));
+                }
+                let output_base =
+                    with_suffix_before_extension(&rendered_video_path, " new_audio", None);
+                (JobVideoInput::File(rendered_video_path), output_base)
+            } else {
+                if matches!(target.availble_for_render, AvailbleForRender::NoVideo) {
+                    continue;
+                }
+                if let Some(missing) = target
+                    .timeline
+                    .sources()
+                    .into_iter()
+                    .find(|source| !source.exists())
+                {
+                    return Err(anyhow!(
+                        "video source does not exist: {}",
+                        missing.display()
+                    ));
+                }
+                let video = match target.timeline.single_clip() {
+                    Some((path, offset)) => JobVideoInput::Window {
+                        path: path.to_path_buf(),
+                        offset,
+                    },
+                    None => JobVideoInput::Timeline {
+                        timeline: target.timeline.clone(),
+                    },
+                };
+                (video, target.path.clone())
+            };
+            let geometry = match &video {
+                JobVideoInput::File(path) => {
+                    persisted
+                        .geometry
+                        .for_source(Some(path), false, &persisted.pad_color)
+                }
+                _ => persisted.geometry.for_source(
+                    target.timeline.sources().first().copied(),
+                    matches!(video, JobVideoInput::Timeline { .. }),
+                    &persisted.pad_color,
+                ),
+            };
+
+            for (variant, video_preset, audio_preset) in outputs.iter() {
+                let with_video = video_preset.is_some();
+                let (video, video_preset) = match video_preset {
+                    Some(preset) => {
+                        if preset.is_copy() && matches!(video, JobVideoInput::Timeline { .. }) {
+                            return Err(anyhow!(
+                                "{} has several video items, fades or gaps: \
+                                 select video preset other than copy",
+                                render_target_name(&target.path)
+                            ));
+                        }
+                        (video.clone(), preset.clone())
                     }
-                    let output_path = with_suffix_before_extension(
-                        &rendered_video_path,
-                        " new_audio",
-                        Some(&muxer),
-                    );
-                    (
-                        rendered_video_path,
-                        output_path,
-                        false,
-                        "copy".to_string(),
-                    )
-                } else {
-                    let input_video_path = target
-                        .video_source
-                        .as_ref()
-                        .ok_or_else(|| anyhow!("render target has no linked video source"))?
-                        .clone();
-                    if !input_video_path.exists() {
-                        return Err(anyhow!(
-                            "video source does not exist: {}",
-                            input_video_path.display()
-                        ));
+                    None => (
+                        JobVideoInput::AudioOnly,
+                        EncoderPreset::copy(EncoderType::Video),
+                    ),
+                };
+                let output_path = variant.output_path(&output_base);
+
+                let muxer = &variant.muxer;
+                let subtitles = if persisted.mux_subtitles && with_video {
+                    let path = subtitle_path_for_target(&target.path, SubtitleFormat::Srt);
+                    match subtitle_codec_for_muxer(muxer, elements.capabilities.as_deref()) {
+                        Some(codec) if path.exists() => Some(SubtitleInput { path, codec }),
+                        Some(_) => {
+                            return Err(anyhow!(
+                                "mux subtitles is checked, but subtitles of {} do not exist: \
+                                 export them or uncheck mux subtitles\n{}",
+                                render_target_name(&target.path),
+                                path.display()
+                            ))
+                        }
+                        None => return Err(anyhow!("muxer {muxer} does not support subtitles")),
                     }
-                    (
-                        input_video_path,
-                        target.path.with_extension(&muxer),
-                        true,
-                        selected_video_codec.clone(),
-                    )
+                } else {
+                    None
                 };
 
-            jobs.push(RenderJobDefinition {
-                render_target: target,
-                output_path,
-                input_video_path,
-                use_source_window,
-                video_codec,
-                audio_codec: audio_codec.clone(),
-            });
+                // Copied video keeps its frame.
+                let job_geometry = geometry.clone().filter(|_| !video_preset.is_copy());
+                let overlay = persisted
+                    .target_overlays
+                    .get(&render_target_name(&target.path))
+                    .or(video_preset.overlay.as_ref())
+                    .filter(|overlay| with_video && !overlay.is_empty())
+                    .cloned();
+                // Beats are walked only for the bar and beat counter.
+                let labels = match &overlay {
+                    Some(overlay) => collect_target_labels(&pr, &target, overlay.counts_beats()),
+                    None => TargetLabels::default(),
+                };
+                jobs.push(RenderJobDefinition {
+                    render_target: target.clone(),
+                    log_path: log_path_for_output(&output_path, &persisted.log_dir),
+                    output_path,
+                    video,
+                    video_preset,
+                    audio_preset: audio_preset.clone(),
+                    subtitles,
+                    smart_cut: persisted.smart_cut,
+                    geometry: job_geometry,
+                    scale_height: variant.height.filter(|_| with_video),
+                    overlay,
+                    labels,
+                });
+            }
+        }
+
+        let duplicates = duplicate_outputs(jobs.iter().map(|job| job.output_path.as_path()));
+        if !duplicates.is_empty() {
+            return Err(anyhow!(
+                "several outputs are written into the same file, \
+                 change suffix or muxer of output variants:\n{}",
+                duplicates.iter().map(|path| path.display()).join("\n")
+            ));
         }
 
         if jobs.is_empty() {
             return Err(anyhow!("no valid render jobs were generated"));
         }
 
+        let invalid_jobs = validate_jobs(&jobs, elements.capabilities.as_deref());
+        if !invalid_jobs.is_empty() {
+            return Err(anyhow!(
+                "render settings do not fit {} targets:\n{}",
+                invalid_jobs.len(),
+                invalid_jobs.iter().join("\n")
+            ));
+        }
+
         let jobs = self.confirm_overwrite_and_filter_jobs(jobs)?;
         if jobs.is_empty() {
             return Err(anyhow!("all render jobs were skipped by overwrite dialog"));
         }
 
         self.render_engine
-            .start(jobs, persisted.parallel_render, persisted.render_workers)?;
+            .enqueue(jobs, persisted.parallel_render, persisted.render_workers)?;
 
         if let Ok(mut gui_state) = self.gui_elements_state.lock() {
             gui_state.error = None;
@@ -661,6 +1037,7 @@ impl ControlSurface for FfmpegGuiSurface {
 
         self.process_gui_messages();
         self.process_surface_operations();
+        self.sync_capabilities_to_gui();
         self.sync_render_state_to_gui();
 
         if !self.is_window_alive.load(Ordering::Relaxed) {
@@ -689,42 +1066,35 @@ fn ui_render_settings(
     ui: &mut gui::egui::Ui,
 ) {
     ui.horizontal(|ui| {
-        let selected_muxer = elements
-            .muxers
-            .get(persisted.selected_muxer)
-            .map(String::as_str)
-            .unwrap_or("-");
         gui::egui::ComboBox::from_label("Muxer")
-            .selected_text(selected_muxer)
+            .selected_text(persisted.muxer.as_str())
             .show_ui(ui, |ui| {
-                for (idx, muxer) in elements.muxers.iter().enumerate() {
-                    ui.selectable_value(&mut persisted.selected_muxer, idx, muxer);
+                for muxer in elements.muxers.iter() {
+                    ui.selectable_value(&mut persisted.muxer, muxer.clone(), muxer);
                 }
             });
 
-        let selected_video_codec = elements
-            .video_codecs
-            .get(persisted.selected_video_codec)
-            .map(String::as_str)
-            .unwrap_or("-");
-        gui::egui::ComboBox::from_label("Video codec")
-            .selected_text(selected_video_codec)
+        gui::egui::ComboBox::from_label("Video preset")
+            .selected_text(persisted.video_preset.as_str())
             .show_ui(ui, |ui| {
-                for (idx, codec) in elements.video_codecs.iter().enumerate() {
-                    ui.selectable_value(&mut persisted.selected_video_codec, idx, codec);
+                for preset in elements.presets_of_type(EncoderType::Video) {
+                    ui.selectable_value(
+                        &mut persisted.video_preset,
+                        preset.name.clone(),
+                        &preset.name,
+                    );
                 }
             });
 
-        let selected_audio_codec = elements
-            .audio_codecs
-            .get(persisted.selected_audio_codec)
-            .map(String::as_str)
-            .unwrap_or("-");
-        gui::egui::ComboBox::from_label("Audio codec")
-            .selected_text(selected_audio_codec)
+        gui::egui::ComboBox::from_label("Audio preset")
+            .selected_text(persisted.audio_preset.as_str())
             .show_ui(ui, |ui| {
-                for (idx, codec) in elements.audio_codecs.iter().enumerate() {
-                    ui.selectable_value(&mut persisted.selected_audio_codec, idx, codec);
+                for preset in elements.presets_of_type(EncoderType::Audio) {
+                    ui.selectable_value(
+                        &mut persisted.audio_preset,
+                        preset.name.clone(),
+                        &preset.name,
+                    );
                 }
             });
     });
@@ -734,64 +1104,235 @@ fn ui_render_settings(
         ui.label("Render workers");
         ui.add(gui::egui::DragValue::new(&mut persisted.render_workers).range(1..=64));
         ui.checkbox(&mut persisted.use_rendered_video, "Use rendered video");
+        ui.checkbox(&mut persisted.mux_subtitles, "Mux subtitles");
+        ui.checkbox(&mut persisted.smart_cut, "Smart cut")
+            .on_hover_text(
+                "With copied video, re-encode only frames before the first \
+             and after the last keyframe of the item",
+            );
+        ui.label("Pad color")
+            .on_hover_text("Color of gaps between video items and of letterbox");
+        ui.add(
+            gui::egui::TextEdit::singleline(&mut persisted.pad_color)
+                .hint_text("black")
+                .desired_width(80.0),
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("Log directory");
+        ui.add(gui::egui::TextEdit::singleline(&mut persisted.log_dir).hint_text("next to output"));
     });
 }
 
-fn ui_render_queue_controls(
+fn ui_capabilities_status(
     state: &FfmpegWindowState,
     elements: &GuiElementsState,
+    persisted: &GuiPersistedState,
     ui: &mut gui::egui::Ui,
 ) {
+    if let Some(report) = &elements.binaries_report {
+        for version in [&report.ffmpeg_version, &report.ffprobe_version] {
+            match version {
+                Ok(version) => {
+                    ui.label(version)
+                        .on_hover_text(report.build_configuration.join("\n"));
+                }
+                Err(error) => {
+                    ui.colored_label(gui::egui::Color32::RED, error);
+                }
+            }
+        }
+        let required_encoders = [
+            elements.preset(&persisted.video_preset, EncoderType::Video),
+            elements.preset(&persisted.audio_preset, EncoderType::Audio),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|preset| !preset.is_copy())
+        .map(|preset| preset.codec.as_str());
+        let missing = report.missing_components(required_encoders);
+        if !missing.is_empty() {
+            ui.colored_label(
+                gui::egui::Color32::YELLOW,
+                format!("ffmpeg has no {}", missing.join(", ")),
+            );
+        }
+    }
     ui.horizontal(|ui| {
-        let can_start = !elements.render_state.active;
+        if elements.capabilities_scanning {
+            ui.spinner();
+            ui.label("Reading ffmpeg capabilities...");
+        } else if let Some(error) = &elements.capabilities_error {
+            ui.colored_label(gui::egui::Color32::RED, error);
+        } else if let Some(capabilities) = &elements.capabilities {
+            ui.label(format!(
+                "{}: {} muxers, {} encoders, {} filters",
+                capabilities.version,
+                capabilities.muxers.len(),
+                capabilities.encoders.len(),
+                capabilities.filters.len()
+            ));
+        }
         if ui
             .add_enabled(
-                can_start,
-                gui::egui::Button::new(gui::egui::RichText::new("Render").strong()),
+                !elements.capabilities_scanning,
+                gui::egui::Button::new("Rescan ffmpeg"),
             )
             .clicked()
         {
             state
                 .message_bus
-                .send_to_surface(GuiToSurfaceMessage::RequestRender);
+                .send_to_surface(GuiToSurfaceMessage::RescanCapabilities);
         }
+    });
+}
 
+fn ui_binaries_settings(
+    state: &mut FfmpegWindowState,
+    elements: &GuiElementsState,
+    ui: &mut gui::egui::Ui,
+) {
+    let draft = state
+        .binaries_draft
+        .get_or_insert_with(|| elements.binaries.clone());
+    let mut ffmpeg = draft.ffmpeg.to_string_lossy().to_string();
+    let mut ffprobe = draft.ffprobe.to_string_lossy().to_string();
+    gui::egui::Grid::new("ffmpeg_binaries")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("ffmpeg");
+            if ui.text_edit_singleline(&mut ffmpeg).changed() {
+                draft.ffmpeg = PathBuf::from(&ffmpeg);
+            }
+            ui.end_row();
+            ui.label("ffprobe");
+            if ui.text_edit_singleline(&mut ffprobe).changed() {
+                draft.ffprobe = PathBuf::from(&ffprobe);
+            }
+            ui.end_row();
+        });
+    ui.label("Full path, or name to be found in PATH.");
+    ui.horizontal(|ui| {
+        let changed = *draft != elements.binaries;
         if ui
-            .add_enabled(
-                elements.render_state.active,
-                gui::egui::Button::new("Cancel"),
-            )
+            .add_enabled(changed, gui::egui::Button::new("Apply"))
             .clicked()
         {
             state
                 .message_bus
-                .send_to_surface(GuiToSurfaceMessage::CancelRender);
+                .send_to_surface(GuiToSurfaceMessage::SaveBinaries(draft.clone()));
+        }
+        if ui.button("Revert").clicked() {
+            *draft = elements.binaries.clone();
+        }
+    });
+}
+
+fn ui_render_queue_controls(
+    state: &mut FfmpegWindowState,
+    elements: &GuiElementsState,
+    ui: &mut gui::egui::Ui,
+) {
+    let render_state = &elements.render_state;
+    let send = |msg| state.message_bus.send_to_surface(msg);
+    ui.horizontal(|ui| {
+        let render_label = if render_state.active {
+            "Add to queue"
+        } else {
+            "Render"
+        };
+        if ui
+            .button(gui::egui::RichText::new(render_label).strong())
+            .clicked()
+        {
+            send(GuiToSurfaceMessage::RequestRender);
+        }
+
+        if ui
+            .add_enabled(render_state.active, gui::egui::Button::new("Cancel all"))
+            .clicked()
+        {
+            send(GuiToSurfaceMessage::CancelRender);
+        }
+
+        let has_failed = render_state.jobs.iter().any(|job| {
+            matches!(
+                job.status,
+                RenderJobStatus::Failed | RenderJobStatus::Canceled
+            )
+        });
+        if ui
+            .add_enabled(has_failed, gui::egui::Button::new("Retry failed"))
+            .clicked()
+        {
+            send(GuiToSurfaceMessage::RetryFailedJobs);
         }
 
         ui.label(format!(
             "{}/{}",
-            elements.render_state.finished_jobs,
-            elements.render_state.total_jobs
+            render_state.finished_jobs, render_state.total_jobs
         ));
+        if let Some(eta) = render_state.eta {
+            ui.label(format!("ETA {}", eta_string(eta)));
+        }
     });
 
-    if !elements.render_state.jobs.is_empty() {
+    let mut open_log = None;
+    if !render_state.jobs.is_empty() {
         gui::egui::ScrollArea::vertical()
             .max_height(180.0)
             .show(ui, |ui| {
-                for job in &elements.render_state.jobs {
+                for job in &render_state.jobs {
                     ui.group(|ui| {
                         ui.label(gui::egui::RichText::new(&job.name).strong());
                         ui.label(&job.output);
                         let (status, color) = match job.status {
                             RenderJobStatus::Queued => ("queued", gui::egui::Color32::LIGHT_BLUE),
                             RenderJobStatus::Running => ("rendering", gui::egui::Color32::YELLOW),
+                            RenderJobStatus::Paused => ("paused", gui::egui::Color32::ORANGE),
                             RenderJobStatus::Done => ("done", gui::egui::Color32::GREEN),
                             RenderJobStatus::Failed => ("failed", gui::egui::Color32::RED),
                             RenderJobStatus::Canceled => ("canceled", gui::egui::Color32::GRAY),
                         };
-                        ui.colored_label(color, status);
-                        ui.add(gui::egui::ProgressBar::new(job.progress.clamp(0.0, 1.0)));
+                        ui.horizontal(|ui| {
+                            ui.colored_label(color, status);
+                            let commands: &[(&str, RenderJobCommand)] = match job.status {
+                                RenderJobStatus::Queued => &[
+                                    ("Up", RenderJobCommand::MoveUp),
+                                    ("Down", RenderJobCommand::MoveDown),
+                                    ("Cancel", RenderJobCommand::Cancel),
+                                ],
+                                RenderJobStatus::Running if PAUSE_SUPPORTED => &[
+                                    ("Pause", RenderJobCommand::Pause),
+                                    ("Cancel", RenderJobCommand::Cancel),
+                                ],
+                                RenderJobStatus::Running => &[("Cancel", RenderJobCommand::Cancel)],
+                                RenderJobStatus::Paused => &[
+                                    ("Resume", RenderJobCommand::Resume),
+                                    ("Cancel", RenderJobCommand::Cancel),
+                                ],
+                                RenderJobStatus::Failed | RenderJobStatus::Canceled => {
+                                    &[("Retry", RenderJobCommand::Retry)]
+                                }
+                                RenderJobStatus::Done => &[],
+                            };
+                            for (label, command) in commands {
+                                if ui.small_button(*label).clicked() {
+                                    send(GuiToSurfaceMessage::RenderJob(job.id, *command));
+                                }
+                            }
+                            if ui.small_button("Log").clicked() {
+                                open_log = Some(job);
+                            }
+                        });
+                        ui.add(
+                            gui::egui::ProgressBar::new(job.progress.clamp(0.0, 1.0))
+                                .show_percentage(),
+                        );
+                        let stats = job.stats_line();
+                        if !stats.is_empty() {
+                            ui.small(stats);
+                        }
                         if let Some(error) = &job.error {
                             ui.colored_label(gui::egui::Color32::RED, error);
                         }
@@ -799,9 +1340,17 @@ fn ui_render_queue_controls(
                 }
             });
     }
+    if let Some(job) = open_log {
+        state.log_viewer = Some(LogViewerState::open(job));
+    }
 }
 
-fn ui_render_targets_list(state: &GuiElementsState, ui: &mut gui::egui::Ui) {
+fn ui_render_targets_list(
+    state: &GuiElementsState,
+    persisted: &GuiPersistedState,
+    overlay_target: &mut Option<String>,
+    ui: &mut gui::egui::Ui,
+) {
     ui.heading("Render targets");
 
     let table_revision = state
@@ -821,12 +1370,13 @@ fn ui_render_targets_list(state: &GuiElementsState, ui: &mut gui::egui::Ui) {
         .max_height(280.0)
         .show(ui, |ui| {
             gui::egui::Grid::new(("render_targets_table", table_revision.as_str()))
-                .num_columns(3)
+                .num_columns(4)
                 .striped(true)
                 .show(ui, |ui| {
                     ui.strong("Name");
                     ui.strong("Path");
                     ui.strong("Available for render");
+                    ui.strong("Overlay");
                     ui.end_row();
 
                     if let Some(targets) = &state.render_targets {
@@ -842,18 +1392,62 @@ fn ui_render_targets_list(state: &GuiElementsState, ui: &mut gui::egui::Ui) {
                             )
                             .on_hover_text(path_text.as_ref());
                             ui.label(gui::egui::RichText::new(status_text).color(status_color));
+                            let name = render_target_name(&target.path);
+                            let overlay_text = if persisted.target_overlays.contains_key(&name) {
+                                "custom"
+                            } else {
+                                "preset"
+                            };
+                            let selected = overlay_target.as_ref() == Some(&name);
+                            if ui.selectable_label(selected, overlay_text).clicked() {
+                                *overlay_target = Some(name);
+                            }
                             ui.end_row();
                         }
                     } else {
                         ui.label("No targets");
                         ui.label("-");
                         ui.label("-");
+                        ui.label("-");
                         ui.end_row();
                     }
                 });
         });
 }
 
+/// Edits overlay of the render target, chosen in the targets list.
+fn ui_target_overlay(
+    overlay_target: &mut Option<String>,
+    persisted: &mut GuiPersistedState,
+    ui: &mut gui::egui::Ui,
+) {
+    let Some(name) = overlay_target.clone() else {
+        return;
+    };
+    ui.separator();
+    ui.strong(format!("Overlay of {name}"));
+    let mut custom = persisted.target_overlays.contains_key(&name);
+    if ui
+        .checkbox(&mut custom, "Override preset overlay")
+        .on_hover_text("Empty overlay disables the preset overlay for this target")
+        .changed()
+    {
+        if custom {
+            persisted
+                .target_overlays
+                .insert(name.clone(), OverlaySettings::default());
+        } else {
+            persisted.target_overlays.remove(&name);
+        }
+    }
+    if let Some(overlay) = persisted.target_overlays.get_mut(&name) {
+        ui_overlay_settings("target_overlay", overlay, ui);
+    }
+    if ui.button("Close overlay").clicked() {
+        *overlay_target = None;
+    }
+}
+
 fn with_suffix_before_extension(path: &Path, suffix: &str, ext: Option<&str>) -> PathBuf {
     let parent = path.parent().unwrap_or_else(|| Path::new(""));
     let stem = path
@@ -863,7 +1457,11 @@ fn with_suffix_before_extension(path: &Path, suffix: &str, ext: Option<&str>) ->
         .to_string();
     let extension = ext
         .map(|value| value.to_string())
-        .or_else(|| path.extension().and_then(|e| e.to_str()).map(|s| s.to_string()))
+        .or_else(|| {
+            path.extension()
+                .and_then(|e| e.to_str())
+                .map(|s| s.to_string())
+        })
         .unwrap_or_default();
 
     let filename = if extension.is_empty() {
@@ -882,6 +1480,11 @@ struct FfmpegWindowState {
     gui_persisted_state: Arc<Mutex<GuiPersistedState>>,
     last_gui_state_snapshot: Option<GuiPersistedState>,
     last_render_targets_revision: Option<u64>,
+    preset_editor: PresetEditorState,
+    binaries_draft: Option<FfmpegBinaries>,
+    log_viewer: Option<LogViewerState>,
+    /// Render target, which overlay is edited.
+    overlay_target: Option<String>,
 }
 
 impl Drop for FfmpegWindowState {
@@ -0,0 +1,454 @@
+use std::path::Path;
+
+use rea_rs::gui;
+use serde::{Deserialize, Serialize};
+
+/// Instance name of `drawtext`, which shows bar and beat.
+const BARS_DRAWTEXT: &str = "drawtext@levitanus_bars";
+
+/// Names and musical grid of a render target, shown by overlays.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(super) struct TargetLabels {
+    pub(super) project: String,
+    /// Region of the render bound, or file name of the target.
+    pub(super) region: String,
+    /// Project time of the render bound start, seconds.
+    pub(super) start: f64,
+    /// Markers inside the bound: time from the bound start and name.
+    pub(super) markers: Vec<(f64, String)>,
+    /// Beats inside the bound: time from the bound start and `bar.beat`.
+    pub(super) beats: Vec<(f64, String)>,
+}
+
+/// Corner or edge of the frame, where overlay is placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub(super) enum OverlayPosition {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    Center,
+    BottomLeft,
+    BottomCenter,
+    #[default]
+    BottomRight,
+}
+
+impl OverlayPosition {
+    const ALL: [Self; 7] = [
+        Self::TopLeft,
+        Self::TopCenter,
+        Self::TopRight,
+        Self::Center,
+        Self::BottomLeft,
+        Self::BottomCenter,
+        Self::BottomRight,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::TopLeft => "top left",
+            Self::TopCenter => "top",
+            Self::TopRight => "top right",
+            Self::Center => "center",
+            Self::BottomLeft => "bottom left",
+            Self::BottomCenter => "bottom",
+            Self::BottomRight => "bottom right",
+        }
+    }
+
+    /// `x` and `y` options. Frame size is `frame_w`x`frame_h`, size of the
+    /// overlay is `w`x`h`, margin is 3% of the frame height.
+    fn xy(&self, frame_w: &str, frame_h: &str, w: &str, h: &str) -> String {
+        let margin = format!("{frame_h}*0.03");
+        let left = margin.clone();
+        let center_x = format!("({frame_w}-{w})/2");
+        let right = format!("{frame_w}-{w}-{margin}");
+        let top = margin.clone();
+        let center_y = format!("({frame_h}-{h})/2");
+        let bottom = format!("{frame_h}-{h}-{margin}");
+        let (x, y) = match self {
+            Self::TopLeft => (left, top),
+            Self::TopCenter => (center_x, top),
+            Self::TopRight => (right, top),
+            Self::Center => (center_x, center_y),
+            Self::BottomLeft => (left, bottom),
+            Self::BottomCenter => (center_x, bottom),
+            Self::BottomRight => (right, bottom),
+        };
+        format!("x={x}:y={y}")
+    }
+}
+
+/// Text, shown for the whole render or changing with the markers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) enum CaptionText {
+    Project,
+    Region,
+    /// Name of the last passed marker.
+    Markers,
+    Custom(String),
+}
+
+impl CaptionText {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Project => "project name",
+            Self::Region => "region name",
+            Self::Markers => "marker names",
+            Self::Custom(_) => "custom text",
+        }
+    }
+}
+
+/// Running counter for review copies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) enum OverlayCounter {
+    /// Project time, `hh:mm:ss.mmm`.
+    Timecode,
+    /// Bar and beat of the project tempo map.
+    BarsBeats,
+}
+
+impl OverlayCounter {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Timecode => "timecode",
+            Self::BarsBeats => "bars and beats",
+        }
+    }
+}
+
+/// Burn-in layer: logo, caption and counter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub(super) struct OverlaySettings {
+    /// PNG file. Empty means no logo.
+    pub(super) logo_path: String,
+    pub(super) logo_position: OverlayPosition,
+    /// Percent.
+    pub(super) logo_opacity: u8,
+    pub(super) caption: Option<CaptionText>,
+    pub(super) caption_position: OverlayPosition,
+    pub(super) counter: Option<OverlayCounter>,
+    pub(super) counter_position: OverlayPosition,
+    /// Percent of the frame height.
+    pub(super) font_size: u8,
+    /// Empty means default font of fontconfig.
+    pub(super) font_file: String,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self {
+            logo_path: String::new(),
+            logo_position: OverlayPosition::TopRight,
+            logo_opacity: 70,
+            caption: None,
+            caption_position: OverlayPosition::BottomLeft,
+            counter: None,
+            counter_position: OverlayPosition::BottomRight,
+            font_size: 4,
+            font_file: String::new(),
+        }
+    }
+}
+
+/// `sendcmd` script, switching the bar and beat counter. None, if the text
+/// never changes.
+///
+/// Script is passed as a file: a command per beat quickly exceeds the
+/// command line length limit.
+pub(super) fn beat_commands(labels: &TargetLabels) -> Option<String> {
+    if labels.beats.len() < 2 {
+        return None;
+    }
+    Some(
+        labels
+            .beats
+            .iter()
+            .skip(1)
+            .map(|(time, beat)| format!("{time:.6} {BARS_DRAWTEXT} reinit text={beat};\n"))
+            .collect(),
+    )
+}
+
+/// Escapes value of a filter option inside of filter graph.
+fn escape_filter_value(value: &str) -> String {
+    let escape = |value: &str, special: &[char]| {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            if special.contains(&c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    };
+    let option = escape(value, &['\\', '\'', ':']);
+    escape(&option, &['\\', '\'', '[', ']', ',', ';'])
+}
+
+impl OverlaySettings {
+    pub(super) fn logo(&self) -> Option<&Path> {
+        let path = self.logo_path.trim();
+        (!path.is_empty()).then_some(Path::new(path))
+    }
+
+    pub(super) fn has_text(&self) -> bool {
+        self.caption.is_some() || self.counter.is_some()
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.logo().is_none() && !self.has_text()
+    }
+
+    /// Bar and beat counter needs beats of the target.
+    pub(super) fn counts_beats(&self) -> bool {
+        self.counter == Some(OverlayCounter::BarsBeats)
+    }
+
+    fn drawtext(&self, text: &str, position: OverlayPosition, expansion: bool) -> String {
+        let mut filter = format!(
+            "text={}:expansion={}:fontcolor=white:fontsize=h*{}/100:\
+             box=1:boxcolor=black@0.5:boxborderw=8:{}",
+            escape_filter_value(text),
+            if expansion { "normal" } else { "none" },
+            self.font_size.max(1),
+            position.xy("w", "h", "text_w", "text_h")
+        );
+        if !self.font_file.trim().is_empty() {
+            filter.push_str(&format!(
+                ":fontfile={}",
+                escape_filter_value(self.font_file.trim())
+            ));
+        }
+        filter
+    }
+
+    fn caption_filters(&self, caption: &CaptionText, labels: &TargetLabels) -> Vec<String> {
+        let position = self.caption_position;
+        let text = match caption {
+            CaptionText::Project => &labels.project,
+            CaptionText::Region => &labels.region,
+            CaptionText::Custom(text) => text,
+            CaptionText::Markers => {
+                return labels
+                    .markers
+                    .iter()
+                    .enumerate()
+                    // Unnamed marker only ends the previous caption.
+                    .filter(|(_, (_, name))| !name.trim().is_empty())
+                    .map(|(idx, (start, name))| {
+                        let enable = match labels.markers.get(idx + 1) {
+                            Some((end, _)) => format!("between(t,{start:.6},{end:.6})"),
+                            None => format!("gte(t,{start:.6})"),
+                        };
+                        format!(
+                            "drawtext={}:enable={}",
+                            self.drawtext(name, position, false),
+                            escape_filter_value(&enable)
+                        )
+                    })
+                    .collect();
+            }
+        };
+        // drawtext fails on empty text.
+        if text.trim().is_empty() {
+            return Vec::new();
+        }
+        vec![format!("drawtext={}", self.drawtext(text, position, false))]
+    }
+
+    fn counter_filters(
+        &self,
+        counter: OverlayCounter,
+        labels: &TargetLabels,
+        commands: &Path,
+    ) -> Vec<String> {
+        let position = self.counter_position;
+        match counter {
+            OverlayCounter::Timecode => {
+                let text = format!("%{{pts:hms:{:.6}}}", labels.start);
+                vec![format!("drawtext={}", self.drawtext(&text, position, true))]
+            }
+            OverlayCounter::BarsBeats => {
+                let Some((_, first)) = labels.beats.first() else {
+                    return Vec::new();
+                };
+                let mut filters = Vec::new();
+                if labels.beats.len() > 1 {
+                    filters.push(format!(
+                        "sendcmd=f={}",
+                        escape_filter_value(&commands.to_string_lossy())
+                    ));
+                }
+                filters.push(format!(
+                    "{BARS_DRAWTEXT}={}",
+                    self.drawtext(first, position, false)
+                ));
+                filters
+            }
+        }
+    }
+
+    /// Filter graph part, drawing the overlay over `[input]` into `[output]`.
+    ///
+    /// Logo, if any, is read from the input `logo_input`. Bar and beat
+    /// changes are read from `commands`, written by [beat_commands].
+    pub(super) fn filter(
+        &self,
+        labels: &TargetLabels,
+        commands: &Path,
+        input: &str,
+        output: &str,
+        logo_input: usize,
+    ) -> String {
+        let mut filters = Vec::new();
+        if let Some(caption) = &self.caption {
+            filters.extend(self.caption_filters(caption, labels));
+        }
+        if let Some(counter) = self.counter {
+            filters.extend(self.counter_filters(counter, labels, commands));
+        }
+        if filters.is_empty() {
+            filters.push("null".to_string());
+        }
+        let text_chain = filters.join(",");
+        if self.logo().is_none() {
+            return format!("[{input}]{text_chain}[{output}]");
+        }
+        format!(
+            "[{logo_input}:v]format=rgba,colorchannelmixer=aa={:.2}[ovlogo];\
+             [{input}]{text_chain}[ovtext];\
+             [ovtext][ovlogo]overlay={}[{output}]",
+            f64::from(self.logo_opacity.min(100)) / 100.0,
+            self.logo_position.xy("W", "H", "w", "h")
+        )
+    }
+}
+
+fn ui_position(id: &str, position: &mut OverlayPosition, ui: &mut gui::egui::Ui) {
+    gui::egui::ComboBox::from_id_salt(id)
+        .selected_text(position.name())
+        .show_ui(ui, |ui| {
+            for value in OverlayPosition::ALL {
+                ui.selectable_value(position, value, value.name());
+            }
+        });
+}
+
+/// Edits overlay in place. `id` separates widgets of several editors.
+pub(super) fn ui_overlay_settings(id: &str, overlay: &mut OverlaySettings, ui: &mut gui::egui::Ui) {
+    ui.push_id(id, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Logo");
+            ui.add(
+                gui::egui::TextEdit::singleline(&mut overlay.logo_path)
+                    .hint_text("PNG file")
+                    .desired_width(200.0),
+            );
+            ui_position("logo_position", &mut overlay.logo_position, ui);
+            ui.label("Opacity");
+            ui.add(
+                gui::egui::DragValue::new(&mut overlay.logo_opacity)
+                    .range(0..=100)
+                    .suffix("%"),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Caption");
+            gui::egui::ComboBox::from_id_salt("caption_text")
+                .selected_text(overlay.caption.as_ref().map_or("none", CaptionText::kind))
+                .show_ui(ui, |ui| {
+                    let custom = match &overlay.caption {
+                        Some(CaptionText::Custom(text)) => text.clone(),
+                        _ => String::new(),
+                    };
+                    for caption in [
+                        None,
+                        Some(CaptionText::Project),
+                        Some(CaptionText::Region),
+                        Some(CaptionText::Markers),
+                        Some(CaptionText::Custom(custom)),
+                    ] {
+                        let text = caption.as_ref().map_or("none", CaptionText::kind);
+                        ui.selectable_value(&mut overlay.caption, caption, text);
+                    }
+                });
+            if let Some(CaptionText::Custom(text)) = &mut overlay.caption {
+                ui.add(gui::egui::TextEdit::singleline(text).desired_width(160.0));
+            }
+            ui_position("caption_position", &mut overlay.caption_position, ui);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Counter");
+            gui::egui::ComboBox::from_id_salt("counter")
+                .selected_text(overlay.counter.map_or("none", |counter| counter.name()))
+                .show_ui(ui, |ui| {
+                    for counter in [
+                        None,
+                        Some(OverlayCounter::Timecode),
+                        Some(OverlayCounter::BarsBeats),
+                    ] {
+                        let text = counter.map_or("none", |counter| counter.name());
+                        ui.selectable_value(&mut overlay.counter, counter, text);
+                    }
+                });
+            ui_position("counter_position", &mut overlay.counter_position, ui);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Font size");
+            ui.add(
+                gui::egui::DragValue::new(&mut overlay.font_size)
+                    .range(1..=50)
+                    .suffix("% of height"),
+            );
+            ui.label("Font file");
+            ui.add(
+                gui::egui::TextEdit::singleline(&mut overlay.font_file)
+                    .hint_text("fontconfig default")
+                    .desired_width(200.0),
+            );
+        });
+    });
+}
+
+#[test]
+fn test_overlay_filter() {
+    let labels = TargetLabels {
+        project: "Album: demo".to_string(),
+        region: "song".to_string(),
+        start: 62.5,
+        markers: vec![(0.0, "Verse".to_string()), (12.0, "Chorus".to_string())],
+        beats: vec![(0.0, "1.1".to_string()), (0.5, "1.2".to_string())],
+    };
+    assert_eq!(escape_filter_value("it's 1:2"), "it\\\\\\'s 1\\\\:2");
+
+    let overlay = OverlaySettings {
+        caption: Some(CaptionText::Markers),
+        counter: Some(OverlayCounter::BarsBeats),
+        ..OverlaySettings::default()
+    };
+    let commands = Path::new("/tmp/levitanus.beats");
+    let filter = overlay.filter(&labels, commands, "vout", "voverlay", 3);
+    assert!(filter.starts_with("[vout]drawtext=text=Verse:"));
+    assert!(filter.contains("enable=between(t\\,0.000000\\,12.000000)"));
+    assert!(filter.contains("text=Chorus:"));
+    assert!(filter.contains("enable=gte(t\\,12.000000)"));
+    assert!(filter.contains("sendcmd=f=/tmp/levitanus.beats,drawtext@levitanus_bars=text=1.1:"));
+    assert_eq!(
+        beat_commands(&labels).as_deref(),
+        Some("0.500000 drawtext@levitanus_bars reinit text=1.2;\n")
+    );
+    assert!(filter.ends_with("[voverlay]"));
+
+    let overlay = OverlaySettings {
+        logo_path: "/logo.png".to_string(),
+        counter: Some(OverlayCounter::Timecode),
+        ..OverlaySettings::default()
+    };
+    let filter = overlay.filter(&labels, commands, "0:v:0", "voverlay", 2);
+    assert!(filter.starts_with("[2:v]format=rgba,colorchannelmixer=aa=0.70[ovlogo];"));
+    assert!(filter.contains("[0:v:0]drawtext=text=%{pts\\\\:hms\\\\:62.500000}"));
+    assert!(filter.ends_with("[ovtext][ovlogo]overlay=x=W-w-H*0.03:y=H*0.03[voverlay]"));
+}
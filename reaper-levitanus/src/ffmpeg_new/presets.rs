@@ -0,0 +1,615 @@
+use std::path::PathBuf;
+
+use log::error;
+use rea_rs::gui;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    capabilities::FfmpegCapabilities,
+    levitanus_data_dir,
+    loudness::LoudnessTarget,
+    options::{EncoderType, Opt, OptionParameter},
+    overlays::{ui_overlay_settings, OverlaySettings},
+};
+
+const PRESETS_FILE_NAME: &str = "encoder_presets.json";
+
+/// How encoder bitrate is controlled.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) enum RateControl {
+    /// Encoder defaults.
+    Default,
+    /// Constant rate factor, `-crf`.
+    Crf(f64),
+    /// Fixed quality scale, `-q:v` or `-q:a`.
+    Quality(f64),
+    /// Average bitrate, e.g. `384k` or `8M`.
+    Bitrate(String),
+}
+
+impl RateControl {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Crf(_) => "crf",
+            Self::Quality(_) => "quality",
+            Self::Bitrate(_) => "bitrate",
+        }
+    }
+}
+
+/// Named encoder settings, shared by all projects.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct EncoderPreset {
+    pub(super) name: String,
+    pub(super) encoder_type: EncoderType,
+    /// Encoder name, or `copy`.
+    pub(super) codec: String,
+    /// Only assigned options are passed to ffmpeg.
+    pub(super) options: Vec<Opt>,
+    pub(super) pixel_format: Option<String>,
+    pub(super) rate_control: RateControl,
+    /// Two-pass `loudnorm` of audio, not possible with copy.
+    #[serde(default)]
+    pub(super) loudness: Option<LoudnessTarget>,
+    /// Burn-in layer of video, not possible with copy.
+    #[serde(default)]
+    pub(super) overlay: Option<OverlaySettings>,
+}
+
+impl EncoderPreset {
+    fn new(name: &str, encoder_type: EncoderType, codec: &str, rate_control: RateControl) -> Self {
+        Self {
+            name: name.to_string(),
+            encoder_type,
+            codec: codec.to_string(),
+            options: Vec::new(),
+            pixel_format: None,
+            rate_control,
+            loudness: None,
+            overlay: None,
+        }
+    }
+
+    /// Stream copy, used when stream is taken as is.
+    pub(super) fn copy(encoder_type: EncoderType) -> Self {
+        Self::new("copy", encoder_type, "copy", RateControl::Default)
+    }
+
+    pub(super) fn is_copy(&self) -> bool {
+        self.codec == "copy"
+    }
+
+    fn stream_specifier(&self) -> &'static str {
+        match self.encoder_type {
+            EncoderType::Video => "v",
+            EncoderType::Audio => "a",
+            EncoderType::Subtitle => "s",
+        }
+    }
+
+    /// Output arguments: codec, rate control, pixel format and options.
+    pub(super) fn args(&self) -> Vec<String> {
+        let stream = self.stream_specifier();
+        let mut args = vec![format!("-c:{stream}"), self.codec.clone()];
+        if self.is_copy() {
+            return args;
+        }
+        match &self.rate_control {
+            RateControl::Default => (),
+            RateControl::Crf(crf) => args.extend(["-crf".to_string(), crf.to_string()]),
+            RateControl::Quality(q) => args.extend([format!("-q:{stream}"), q.to_string()]),
+            RateControl::Bitrate(bitrate) => {
+                args.extend([format!("-b:{stream}"), bitrate.trim().to_string()])
+            }
+        }
+        if let Some(pixel_format) = self.pixel_format.as_ref().filter(|f| !f.is_empty()) {
+            if self.encoder_type == EncoderType::Video {
+                args.extend(["-pix_fmt".to_string(), pixel_format.clone()]);
+            }
+        }
+        for opt in self.options.iter() {
+            if let Some(value) = opt.parameter.ffmpeg_representation() {
+                args.extend([format!("-{}", opt.name), value]);
+            }
+        }
+        args
+    }
+}
+
+/// Presets, which reproduce the old hardcoded codec list.
+pub(super) fn default_encoder_presets() -> Vec<EncoderPreset> {
+    vec![
+        EncoderPreset::copy(EncoderType::Video),
+        EncoderPreset::new(
+            "libx264 crf 15",
+            EncoderType::Video,
+            "libx264",
+            RateControl::Crf(15.0),
+        ),
+        EncoderPreset::new(
+            "libx265",
+            EncoderType::Video,
+            "libx265",
+            RateControl::Default,
+        ),
+        EncoderPreset::new(
+            "prores_ks",
+            EncoderType::Video,
+            "prores_ks",
+            RateControl::Default,
+        ),
+        EncoderPreset::copy(EncoderType::Audio),
+        EncoderPreset::new(
+            "aac 384k",
+            EncoderType::Audio,
+            "aac",
+            RateControl::Bitrate("384k".to_string()),
+        ),
+        EncoderPreset::new(
+            "pcm_s16le",
+            EncoderType::Audio,
+            "pcm_s16le",
+            RateControl::Default,
+        ),
+        EncoderPreset::new("flac", EncoderType::Audio, "flac", RateControl::Default),
+    ]
+}
+
+fn presets_path() -> anyhow::Result<PathBuf> {
+    Ok(levitanus_data_dir()?.join(PRESETS_FILE_NAME))
+}
+
+/// Reads global presets, or returns defaults if there are none yet.
+pub(super) fn load_encoder_presets() -> Vec<EncoderPreset> {
+    let path = match presets_path() {
+        Ok(path) => path,
+        Err(e) => {
+            error!("{e}");
+            return default_encoder_presets();
+        }
+    };
+    if !path.exists() {
+        return default_encoder_presets();
+    }
+    let presets = std::fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(serde_json::from_str::<Vec<EncoderPreset>>(&json)?));
+    match presets {
+        Ok(presets) if !presets.is_empty() => presets,
+        Ok(_) => default_encoder_presets(),
+        Err(e) => {
+            error!("can not read encoder presets from {}: {e}", path.display());
+            default_encoder_presets()
+        }
+    }
+}
+
+pub(super) fn save_encoder_presets(presets: &[EncoderPreset]) -> anyhow::Result<()> {
+    let path = presets_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(presets)?)?;
+    Ok(())
+}
+
+/// Parses text, typed in GUI, into the option parameter of the same type.
+///
+/// Empty text clears the parameter, options of unknown type are kept as
+/// strings.
+fn parameter_from_text(template: &OptionParameter, text: &str) -> OptionParameter {
+    let empty = template.clone().with_none();
+    let text = text.trim();
+    if text.is_empty() {
+        return empty;
+    }
+    match template {
+        OptionParameter::Int(_) => text
+            .parse()
+            .map(|v| OptionParameter::Int(Some(v)))
+            .unwrap_or(empty),
+        OptionParameter::Float(_) => text
+            .parse()
+            .map(|v| OptionParameter::Float(Some(v)))
+            .unwrap_or(empty),
+        OptionParameter::Bool(_) => match text {
+            "1" | "true" => OptionParameter::Bool(Some(true)),
+            "0" | "false" => OptionParameter::Bool(Some(false)),
+            _ => empty,
+        },
+        OptionParameter::Enum { items, .. } => match items.iter().position(|i| i == text) {
+            Some(idx) => OptionParameter::Enum {
+                items: items.clone(),
+                selected_idx: Some(idx),
+            },
+            None => OptionParameter::String(Some(text.to_string())),
+        },
+        _ => empty
+            .clone()
+            .with_new_string_value(text.to_string())
+            .unwrap_or_else(|_| OptionParameter::String(Some(text.to_string()))),
+    }
+}
+
+/// Description of option with its default and possible values.
+fn option_hint(opt: &Opt) -> String {
+    let mut hint = opt.description.clone();
+    if let Some(default) = &opt.default {
+        hint.push_str(&format!("\n{default}"));
+    }
+    if let OptionParameter::Enum { items, .. } | OptionParameter::Flags { items, .. } =
+        &opt.parameter
+    {
+        hint.push_str(&format!("\nvalues: {}", items.join(", ")));
+    }
+    hint
+}
+
+/// Draft of presets, edited in GUI until saved.
+#[derive(Debug, Default)]
+pub(super) struct PresetEditorState {
+    draft: Option<Vec<EncoderPreset>>,
+    selected: usize,
+    new_option_name: String,
+    /// Text of the option value being typed, applied when focus is lost.
+    editing_option: Option<(usize, String)>,
+}
+
+/// Preset editor. Returns presets to save, when user pressed "Save".
+pub(super) fn ui_preset_editor(
+    editor: &mut PresetEditorState,
+    saved: &[EncoderPreset],
+    capabilities: Option<&FfmpegCapabilities>,
+    ui: &mut gui::egui::Ui,
+) -> Option<Vec<EncoderPreset>> {
+    let draft = editor.draft.get_or_insert_with(|| saved.to_vec());
+    let mut to_save = None;
+
+    ui.horizontal(|ui| {
+        gui::egui::ComboBox::from_label("Preset")
+            .selected_text(
+                draft
+                    .get(editor.selected)
+                    .map(|p| p.name.as_str())
+                    .unwrap_or("-"),
+            )
+            .show_ui(ui, |ui| {
+                for (idx, preset) in draft.iter().enumerate() {
+                    ui.selectable_value(&mut editor.selected, idx, &preset.name);
+                }
+            });
+        if ui.button("New").clicked() {
+            draft.push(EncoderPreset::new(
+                "new preset",
+                EncoderType::Video,
+                "libx264",
+                RateControl::Default,
+            ));
+            editor.selected = draft.len() - 1;
+        }
+        if ui.button("Duplicate").clicked() {
+            if let Some(preset) = draft.get(editor.selected).cloned() {
+                draft.push(EncoderPreset {
+                    name: format!("{} copy", preset.name),
+                    ..preset
+                });
+                editor.selected = draft.len() - 1;
+            }
+        }
+        if ui.button("Delete").clicked() && editor.selected < draft.len() {
+            draft.remove(editor.selected);
+            editor.selected = editor.selected.saturating_sub(1);
+        }
+    });
+
+    if let Some(preset) = draft.get_mut(editor.selected) {
+        let encoder = capabilities.and_then(|c| c.encoder(&preset.codec));
+        gui::egui::Grid::new("encoder_preset")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut preset.name);
+                ui.end_row();
+
+                ui.label("Type");
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut preset.encoder_type, EncoderType::Video, "Video");
+                    ui.selectable_value(&mut preset.encoder_type, EncoderType::Audio, "Audio");
+                });
+                ui.end_row();
+
+                ui.label("Codec");
+                ui.horizontal(|ui| {
+                    match capabilities {
+                        Some(capabilities) => {
+                            gui::egui::ComboBox::from_id_salt("preset_codec")
+                                .selected_text(preset.codec.as_str())
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(
+                                        &mut preset.codec,
+                                        "copy".to_string(),
+                                        "copy",
+                                    );
+                                    for encoder in
+                                        capabilities.encoders_of_type(preset.encoder_type.clone())
+                                    {
+                                        ui.selectable_value(
+                                            &mut preset.codec,
+                                            encoder.name.clone(),
+                                            &encoder.name,
+                                        )
+                                        .on_hover_text(&encoder.description);
+                                    }
+                                });
+                        }
+                        None => {
+                            ui.text_edit_singleline(&mut preset.codec);
+                        }
+                    }
+                    if capabilities.is_some() && encoder.is_none() && !preset.is_copy() {
+                        ui.colored_label(
+                            gui::egui::Color32::YELLOW,
+                            "not supported by this ffmpeg",
+                        );
+                    }
+                });
+                ui.end_row();
+
+                if preset.encoder_type == EncoderType::Video {
+                    ui.label("Pixel format");
+                    let formats = capabilities.map(|c| c.output_pixel_formats(encoder));
+                    match formats {
+                        Some(formats) => {
+                            gui::egui::ComboBox::from_id_salt("preset_pix_fmt")
+                                .selected_text(
+                                    preset.pixel_format.as_deref().unwrap_or("encoder default"),
+                                )
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(
+                                        &mut preset.pixel_format,
+                                        None,
+                                        "encoder default",
+                                    );
+                                    for format in formats.iter() {
+                                        ui.selectable_value(
+                                            &mut preset.pixel_format,
+                                            Some(format.clone()),
+                                            format,
+                                        );
+                                    }
+                                });
+                        }
+                        None => {
+                            let mut pixel_format = preset.pixel_format.clone().unwrap_or_default();
+                            if ui
+                                .add(
+                                    gui::egui::TextEdit::singleline(&mut pixel_format)
+                                        .hint_text("encoder default"),
+                                )
+                                .changed()
+                            {
+                                preset.pixel_format =
+                                    Some(pixel_format).filter(|f| !f.trim().is_empty());
+                            }
+                        }
+                    }
+                    ui.end_row();
+                }
+
+                ui.label("Rate control");
+                ui.horizontal(|ui| {
+                    gui::egui::ComboBox::from_id_salt("rate_control")
+                        .selected_text(preset.rate_control.kind())
+                        .show_ui(ui, |ui| {
+                            let rc = &mut preset.rate_control;
+                            for option in [
+                                RateControl::Default,
+                                RateControl::Crf(18.0),
+                                RateControl::Quality(2.0),
+                                RateControl::Bitrate("8M".to_string()),
+                            ] {
+                                let kind = option.kind();
+                                if ui.selectable_label(rc.kind() == kind, kind).clicked()
+                                    && rc.kind() != kind
+                                {
+                                    *rc = option;
+                                }
+                            }
+                        });
+                    match &mut preset.rate_control {
+                        RateControl::Default => (),
+                        RateControl::Crf(value) | RateControl::Quality(value) => {
+                            ui.add(gui::egui::DragValue::new(value).range(0.0..=63.0));
+                        }
+                        RateControl::Bitrate(bitrate) => {
+                            ui.add(gui::egui::TextEdit::singleline(bitrate).desired_width(80.0));
+                        }
+                    }
+                });
+                ui.end_row();
+
+                if preset.encoder_type == EncoderType::Audio && !preset.is_copy() {
+                    ui.label("Loudness");
+                    ui_loudness_target(&mut preset.loudness, ui);
+                    ui.end_row();
+                }
+                if preset.encoder_type == EncoderType::Video && !preset.is_copy() {
+                    ui.label("Overlay");
+                    ui.vertical(|ui| {
+                        let mut enabled = preset.overlay.is_some();
+                        if ui.checkbox(&mut enabled, "Burn-in overlay").changed() {
+                            preset.overlay = enabled.then(OverlaySettings::default);
+                        }
+                        if let Some(overlay) = &mut preset.overlay {
+                            ui_overlay_settings("preset_overlay", overlay, ui);
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+
+        ui.label("Options");
+        let mut remove = None;
+        gui::egui::Grid::new("encoder_preset_options")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for (idx, opt) in preset.options.iter_mut().enumerate() {
+                    ui.label(&opt.name).on_hover_text(option_hint(opt));
+                    let mut text = match &editor.editing_option {
+                        Some((editing, text)) if *editing == idx => text.clone(),
+                        _ => opt.parameter.ffmpeg_representation().unwrap_or_default(),
+                    };
+                    let response = ui.text_edit_singleline(&mut text);
+                    if response.lost_focus() {
+                        opt.parameter = parameter_from_text(&opt.parameter, &text);
+                        editor.editing_option = None;
+                    } else if response.changed() {
+                        editor.editing_option = Some((idx, text));
+                    }
+                    if ui.button("Remove").clicked() {
+                        remove = Some(idx);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(idx) = remove {
+            preset.options.remove(idx);
+        }
+        if let Some(encoder) = encoder {
+            gui::egui::ComboBox::from_id_salt("preset_add_encoder_option")
+                .selected_text(format!("Add {} option", encoder.name))
+                .show_ui(ui, |ui| {
+                    for opt in encoder.options.iter() {
+                        if preset.options.iter().any(|o| o.name == opt.name) {
+                            continue;
+                        }
+                        if ui
+                            .selectable_label(false, &opt.name)
+                            .on_hover_text(option_hint(opt))
+                            .clicked()
+                        {
+                            preset.options.push(opt.clone());
+                        }
+                    }
+                });
+        }
+        ui.horizontal(|ui| {
+            ui.add(
+                gui::egui::TextEdit::singleline(&mut editor.new_option_name)
+                    .hint_text("option name, e.g. preset"),
+            );
+            let name = editor
+                .new_option_name
+                .trim()
+                .trim_start_matches('-')
+                .to_string();
+            if ui
+                .add_enabled(!name.is_empty(), gui::egui::Button::new("Add option"))
+                .clicked()
+            {
+                preset.options.push(Opt {
+                    name,
+                    description: String::new(),
+                    parameter: OptionParameter::String(None),
+                    default: None,
+                });
+                editor.new_option_name.clear();
+            }
+        });
+    }
+
+    let mut revert = false;
+    ui.horizontal(|ui| {
+        if ui.button("Save presets").clicked() {
+            to_save = Some(draft.clone());
+        }
+        revert = ui.button("Revert").clicked();
+    });
+    if revert {
+        editor.draft = None;
+    }
+    to_save
+}
+
+fn ui_loudness_target(loudness: &mut Option<LoudnessTarget>, ui: &mut gui::egui::Ui) {
+    ui.vertical(|ui| {
+        let mut enabled = loudness.is_some();
+        if ui.checkbox(&mut enabled, "Two-pass loudnorm").changed() {
+            *loudness = enabled.then(LoudnessTarget::default);
+        }
+        let Some(target) = loudness else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            for (name, well_known) in LoudnessTarget::well_known() {
+                let label = format!("{name} ({} LUFS)", well_known.integrated);
+                if ui.selectable_label(*target == well_known, label).clicked() {
+                    *target = well_known;
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("I");
+            ui.add(
+                gui::egui::DragValue::new(&mut target.integrated)
+                    .range(-70.0..=-5.0)
+                    .speed(0.1)
+                    .suffix(" LUFS"),
+            );
+            ui.label("TP");
+            ui.add(
+                gui::egui::DragValue::new(&mut target.true_peak)
+                    .range(-9.0..=0.0)
+                    .speed(0.1)
+                    .suffix(" dBTP"),
+            );
+            ui.label("LRA");
+            ui.add(
+                gui::egui::DragValue::new(&mut target.range)
+                    .range(1.0..=50.0)
+                    .speed(0.1)
+                    .suffix(" LU"),
+            );
+        });
+    });
+}
+
+#[test]
+fn test_preset_args() {
+    let mut preset = EncoderPreset::new(
+        "x264",
+        EncoderType::Video,
+        "libx264",
+        RateControl::Crf(15.0),
+    );
+    preset.pixel_format = Some("yuv420p".to_string());
+    preset.options.push(Opt {
+        name: "preset".to_string(),
+        description: String::new(),
+        parameter: parameter_from_text(&OptionParameter::String(None), "slow"),
+        default: None,
+    });
+    preset.options.push(Opt {
+        name: "tune".to_string(),
+        description: String::new(),
+        parameter: OptionParameter::String(None),
+        default: None,
+    });
+    assert_eq!(
+        preset.args().join(" "),
+        "-c:v libx264 -crf 15 -pix_fmt yuv420p -preset slow"
+    );
+    let aac = EncoderPreset::new(
+        "aac",
+        EncoderType::Audio,
+        "aac",
+        RateControl::Bitrate("384k".to_string()),
+    );
+    assert_eq!(aac.args().join(" "), "-c:a aac -b:a 384k");
+    assert_eq!(
+        EncoderPreset::copy(EncoderType::Audio).args().join(" "),
+        "-c:a copy"
+    );
+}
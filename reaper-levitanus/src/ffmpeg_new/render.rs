@@ -1,24 +1,40 @@
 use std::{
     collections::VecDeque,
-    io::{BufRead, BufReader},
-    path::PathBuf,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
     process::{Command, Stdio},
-    sync::mpsc,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
+        Arc, Mutex, MutexGuard,
     },
     thread,
 };
 
 use anyhow::anyhow;
+use log::{debug, warn};
 
-use super::render_targets::RenderTarget;
+use super::{
+    binaries::{command_line, ffmpeg_command},
+    geometry::VideoGeometry,
+    loudness::{measure_loudness, LoudnessMeasurement},
+    overlays::{beat_commands, OverlaySettings, TargetLabels},
+    presets::EncoderPreset,
+    render_targets::RenderTarget,
+    smart_cut::smart_cut,
+    timeline::VideoTimeline,
+    verification::verify_output,
+};
+
+/// Lines of ffmpeg stderr, shown in the error message of a failed job.
+const ERROR_LOG_LINES: usize = 12;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(super) enum RenderJobStatus {
     Queued,
     Running,
+    /// ffmpeg process is stopped.
+    Paused,
     Done,
     Failed,
     Canceled,
@@ -26,11 +42,107 @@ pub(super) enum RenderJobStatus {
 
 #[derive(Debug, Clone, PartialEq)]
 pub(super) struct RenderJobSnapshot {
+    pub(super) id: u64,
     pub(super) name: String,
     pub(super) output: String,
     pub(super) progress: f32,
+    /// Target duration, seconds.
+    pub(super) duration: f64,
+    pub(super) stats: RenderProgress,
+    /// Seconds left, once ffmpeg reports its speed.
+    pub(super) eta: Option<f64>,
     pub(super) status: RenderJobStatus,
     pub(super) error: Option<String>,
+    /// ffmpeg command line, None if it can not be built.
+    pub(super) command: Option<String>,
+    pub(super) log_path: PathBuf,
+    /// Result of the first `loudnorm` pass.
+    pub(super) loudness: Option<LoudnessMeasurement>,
+}
+
+impl RenderJobSnapshot {
+    fn update_progress(&mut self, stats: RenderProgress) {
+        self.progress = if self.duration <= f64::EPSILON {
+            1.0
+        } else {
+            (stats.out_time / self.duration).clamp(0.0, 1.0) as f32
+        };
+        self.eta = stats
+            .speed
+            .filter(|speed| *speed > f64::EPSILON)
+            .map(|speed| self.remaining_time() / speed);
+        self.stats = stats;
+    }
+
+    /// Media seconds, which are not rendered yet.
+    fn remaining_time(&self) -> f64 {
+        (self.duration - self.stats.out_time).max(0.0)
+    }
+
+    /// Measured loudness, and frame, fps, speed, bitrate and size, reported
+    /// by ffmpeg.
+    pub(super) fn stats_line(&self) -> String {
+        let stats = &self.stats;
+        [
+            self.loudness
+                .as_ref()
+                .map(|loudness| format!("measured {loudness}")),
+            stats.frame.map(|frame| format!("frame {frame}")),
+            stats.fps.map(|fps| format!("{fps:.1} fps")),
+            stats.speed.map(|speed| format!("{speed:.2}x")),
+            stats.bitrate.clone(),
+            stats
+                .total_size
+                .map(|size| format!("{:.1} MiB", size as f64 / (1024.0 * 1024.0))),
+            self.eta.map(|eta| format!("ETA {}", eta_string(eta))),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+/// Values of the last block of ffmpeg `-progress` output.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(super) struct RenderProgress {
+    /// Rendered media time, seconds.
+    pub(super) out_time: f64,
+    pub(super) frame: Option<u64>,
+    pub(super) fps: Option<f64>,
+    /// As ffmpeg writes it, e.g. `1234.5kbits/s`.
+    pub(super) bitrate: Option<String>,
+    /// Output size, bytes.
+    pub(super) total_size: Option<u64>,
+    /// Media seconds, encoded per second.
+    pub(super) speed: Option<f64>,
+}
+
+impl RenderProgress {
+    /// Reads `key=value` line, returns true when the block is complete.
+    ///
+    /// Values, ffmpeg does not know yet, are `N/A` and are kept as None.
+    fn parse_line(&mut self, line: &str) -> bool {
+        let Some((key, value)) = line.split_once('=') else {
+            return false;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "frame" => self.frame = value.parse().ok(),
+            "fps" => self.fps = value.parse().ok(),
+            "bitrate" => self.bitrate = Some(value.to_string()).filter(|v| v != "N/A"),
+            "total_size" => self.total_size = value.parse().ok(),
+            "speed" => self.speed = value.trim_end_matches('x').trim().parse().ok(),
+            "out_time_us" | "out_time_ms" | "out_time" => {
+                if let Some(seconds) = parse_out_time(key.trim(), value) {
+                    self.out_time = seconds.max(0.0);
+                }
+            }
+            "progress" => return true,
+            _ => (),
+        }
+        false
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -40,6 +152,8 @@ pub(super) struct RenderStateSnapshot {
     pub(super) total_jobs: usize,
     pub(super) finished_jobs: usize,
     pub(super) jobs: Vec<RenderJobSnapshot>,
+    /// Seconds left for the whole queue.
+    pub(super) eta: Option<f64>,
 }
 
 impl Default for RenderStateSnapshot {
@@ -50,6 +164,7 @@ impl Default for RenderStateSnapshot {
             total_jobs: 0,
             finished_jobs: 0,
             jobs: Vec::new(),
+            eta: None,
         }
     }
 }
@@ -58,213 +173,549 @@ impl Default for RenderStateSnapshot {
 pub(super) struct RenderJobDefinition {
     pub(super) render_target: RenderTarget,
     pub(super) output_path: PathBuf,
-    pub(super) input_video_path: PathBuf,
-    pub(super) use_source_window: bool,
-    pub(super) video_codec: String,
-    pub(super) audio_codec: String,
+    pub(super) video: JobVideoInput,
+    pub(super) video_preset: EncoderPreset,
+    pub(super) audio_preset: EncoderPreset,
+    pub(super) subtitles: Option<SubtitleInput>,
+    /// Command line and full ffmpeg stderr are written here.
+    pub(super) log_path: PathBuf,
+    /// Copied window is cut frame-accurately, re-encoding partial GOPs.
+    pub(super) smart_cut: bool,
+    /// Output frame and rate. None keeps the frame of the single source.
+    pub(super) geometry: Option<VideoGeometry>,
+    /// Video is scaled to this height, keeping aspect ratio.
+    pub(super) scale_height: Option<u32>,
+    /// Burn-in logo and text, drawn before scaling.
+    pub(super) overlay: Option<OverlaySettings>,
+    /// Names and beats, shown by the overlay.
+    pub(super) labels: TargetLabels,
 }
 
-#[derive(Debug)]
-enum RenderWorkerEvent {
-    Started(usize),
-    Progress(usize, f32),
-    Finished(usize, Option<String>, bool),
+/// Where video stream of a render job comes from.
+#[derive(Debug, Clone)]
+pub(super) enum JobVideoInput {
+    /// Whole file is used as is, e.g. previously rendered video.
+    File(PathBuf),
+    /// Single source covering the whole target, cut from `offset`.
+    Window { path: PathBuf, offset: f64 },
+    /// Pieces of smart cut, listed for concat demuxer.
+    Concat(PathBuf),
+    /// Output has no video stream.
+    AudioOnly,
+    /// Several sources, gaps and crossfades, composed by filter graph.
+    Timeline { timeline: VideoTimeline },
 }
 
+/// Subtitle file, muxed into output as a subtitle stream.
 #[derive(Debug, Clone)]
-pub(super) struct RenderEngine {
-    state: Arc<Mutex<RenderStateSnapshot>>,
+pub(super) struct SubtitleInput {
+    pub(super) path: PathBuf,
+    pub(super) codec: String,
+}
+
+/// Command for a single job of the render queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum RenderJobCommand {
+    Cancel,
+    /// Queue failed or canceled job again.
+    Retry,
+    Pause,
+    Resume,
+    MoveUp,
+    MoveDown,
+}
+
+/// What worker reports about the running job.
+#[derive(Debug)]
+enum JobUpdate {
+    Measured(LoudnessMeasurement),
+    /// Command line of ffmpeg, which renders the job.
+    Command(String),
+    Spawned(u32),
+    Progress(RenderProgress),
+}
+
+#[derive(Debug)]
+struct QueuedJob {
+    definition: RenderJobDefinition,
+    snapshot: RenderJobSnapshot,
     cancel_flag: Arc<AtomicBool>,
+    /// ffmpeg process of the running job.
+    pid: Option<u32>,
 }
 
-impl Default for RenderEngine {
-    fn default() -> Self {
+impl QueuedJob {
+    fn new(id: u64, definition: RenderJobDefinition) -> Self {
         Self {
-            state: Arc::new(Mutex::new(RenderStateSnapshot::default())),
+            snapshot: RenderJobSnapshot {
+                id,
+                name: render_target_name(&definition.output_path),
+                output: definition.output_path.to_string_lossy().to_string(),
+                progress: 0.0,
+                duration: render_duration_secs(&definition.render_target.duration),
+                stats: RenderProgress::default(),
+                eta: None,
+                status: RenderJobStatus::Queued,
+                error: None,
+                command: build_ffmpeg_command(id, &definition, None)
+                    .ok()
+                    .map(|(_, command)| command),
+                log_path: definition.log_path.clone(),
+                loudness: None,
+            },
+            definition,
             cancel_flag: Arc::new(AtomicBool::new(false)),
+            pid: None,
+        }
+    }
+
+    fn apply(&mut self, update: JobUpdate) {
+        match update {
+            JobUpdate::Measured(loudness) => self.snapshot.loudness = Some(loudness),
+            JobUpdate::Command(command) => self.snapshot.command = Some(command),
+            JobUpdate::Spawned(pid) => self.pid = Some(pid),
+            JobUpdate::Progress(stats) => self.snapshot.update_progress(stats),
+        }
+    }
+
+    fn is_pending(&self) -> bool {
+        matches!(
+            self.snapshot.status,
+            RenderJobStatus::Queued | RenderJobStatus::Running | RenderJobStatus::Paused
+        )
+    }
+
+    fn requeue(&mut self) -> anyhow::Result<()> {
+        if !matches!(
+            self.snapshot.status,
+            RenderJobStatus::Failed | RenderJobStatus::Canceled
+        ) {
+            return Err(anyhow!("{} has not failed", self.snapshot.name));
+        }
+        self.snapshot.status = RenderJobStatus::Queued;
+        self.snapshot.progress = 0.0;
+        self.snapshot.stats = RenderProgress::default();
+        self.snapshot.eta = None;
+        self.snapshot.error = None;
+        self.snapshot.loudness = None;
+        self.cancel_flag = Arc::new(AtomicBool::new(false));
+        Ok(())
+    }
+
+    fn cancel(&mut self) {
+        match self.snapshot.status {
+            RenderJobStatus::Queued => self.snapshot.status = RenderJobStatus::Canceled,
+            RenderJobStatus::Running => self.cancel_flag.store(true, Ordering::Relaxed),
+            RenderJobStatus::Paused => {
+                self.cancel_flag.store(true, Ordering::Relaxed);
+                // Stopped ffmpeg does not write progress, so worker would
+                // never see the flag.
+                if let Some(pid) = self.pid {
+                    if let Err(error) = signal_process(pid, ProcessSignal::Continue) {
+                        warn!("can not resume ffmpeg to cancel it: {error}");
+                    }
+                }
+                self.snapshot.status = RenderJobStatus::Running;
+            }
+            RenderJobStatus::Done | RenderJobStatus::Failed | RenderJobStatus::Canceled => (),
         }
     }
 }
 
+/// Jobs of the current render, in order they are taken by workers.
+#[derive(Debug, Default)]
+struct RenderQueue {
+    jobs: Vec<QueuedJob>,
+    next_id: u64,
+    max_workers: usize,
+    running_workers: usize,
+}
+
+impl RenderQueue {
+    fn job_idx(&self, id: u64) -> anyhow::Result<usize> {
+        self.jobs
+            .iter()
+            .position(|job| job.snapshot.id == id)
+            .ok_or_else(|| anyhow!("render job {id} is not in the queue"))
+    }
+
+    fn snapshot(&self) -> RenderStateSnapshot {
+        RenderStateSnapshot {
+            active: self.running_workers > 0,
+            canceled: self
+                .jobs
+                .iter()
+                .any(|job| job.snapshot.status == RenderJobStatus::Canceled),
+            total_jobs: self.jobs.len(),
+            finished_jobs: self.jobs.iter().filter(|job| !job.is_pending()).count(),
+            jobs: self.jobs.iter().map(|job| job.snapshot.clone()).collect(),
+            eta: self.eta(),
+        }
+    }
+
+    /// Media time of pending jobs, divided by summary speed of running ones.
+    fn eta(&self) -> Option<f64> {
+        let pending = self.jobs.iter().filter(|job| job.is_pending());
+        let remaining: f64 = pending
+            .clone()
+            .map(|job| job.snapshot.remaining_time())
+            .sum();
+        let speed: f64 = pending
+            .filter(|job| job.snapshot.status == RenderJobStatus::Running)
+            .filter_map(|job| job.snapshot.stats.speed)
+            .sum();
+        (speed > f64::EPSILON).then(|| remaining / speed)
+    }
+
+    fn queued_jobs(&self) -> usize {
+        self.jobs
+            .iter()
+            .filter(|job| job.snapshot.status == RenderJobStatus::Queued)
+            .count()
+    }
+
+    /// Marks the first queued job as running and gives it to worker.
+    fn take_next(&mut self) -> Option<(u64, RenderJobDefinition, Arc<AtomicBool>)> {
+        let job = self
+            .jobs
+            .iter_mut()
+            .find(|job| job.snapshot.status == RenderJobStatus::Queued)?;
+        job.snapshot.status = RenderJobStatus::Running;
+        Some((
+            job.snapshot.id,
+            job.definition.clone(),
+            Arc::clone(&job.cancel_flag),
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(super) struct RenderEngine {
+    queue: Arc<Mutex<RenderQueue>>,
+}
+
 impl RenderEngine {
+    fn lock(&self) -> anyhow::Result<MutexGuard<'_, RenderQueue>> {
+        self.queue
+            .lock()
+            .map_err(|_| anyhow!("render queue lock poisoned"))
+    }
+
     pub(super) fn snapshot(&self) -> RenderStateSnapshot {
-        self.state
+        self.queue
             .lock()
-            .map(|state| state.clone())
+            .map(|queue| queue.snapshot())
             .unwrap_or_default()
     }
 
+    /// Cancels all queued and running jobs.
     pub(super) fn request_cancel(&self) {
-        self.cancel_flag.store(true, Ordering::Relaxed);
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.jobs.iter_mut().for_each(QueuedJob::cancel);
+        }
     }
 
-    pub(super) fn start(
+    /// Adds jobs to the queue and starts workers.
+    ///
+    /// If nothing is rendered, jobs of the previous render are cleared.
+    /// Jobs, writing to the output of a pending job, are skipped.
+    pub(super) fn enqueue(
         &self,
         jobs: Vec<RenderJobDefinition>,
         parallel: bool,
         workers: usize,
     ) -> anyhow::Result<()> {
-        let total_jobs = jobs.len();
-        if total_jobs == 0 {
+        if jobs.is_empty() {
             return Err(anyhow!("render queue is empty"));
         }
-
-        {
-            let mut state = self
-                .state
-                .lock()
-                .map_err(|_| anyhow!("render state lock poisoned"))?;
-            if state.active {
-                return Err(anyhow!("render is already running"));
+        let mut queue = self.lock()?;
+        if queue.running_workers == 0 {
+            queue.jobs.clear();
+        }
+        let mut added = 0;
+        for job in jobs {
+            let busy = queue.jobs.iter().any(|queued| {
+                queued.is_pending() && queued.definition.output_path == job.output_path
+            });
+            if busy {
+                debug!(
+                    "ffmpeg_new: {} is already in the render queue",
+                    job.output_path.display()
+                );
+                continue;
             }
-            state.active = true;
-            state.canceled = false;
-            state.total_jobs = total_jobs;
-            state.finished_jobs = 0;
-            state.jobs = jobs
-                .iter()
-                .map(|job| RenderJobSnapshot {
-                    name: render_target_name(&job.render_target.path),
-                    output: job.output_path.to_string_lossy().to_string(),
-                    progress: 0.0,
-                    status: RenderJobStatus::Queued,
-                    error: None,
-                })
-                .collect();
+            let id = queue.next_id;
+            queue.next_id += 1;
+            queue.jobs.push(QueuedJob::new(id, job));
+            added += 1;
         }
+        if added == 0 {
+            return Err(anyhow!("all render jobs are already in the queue"));
+        }
+        queue.max_workers = if parallel { workers.max(1) } else { 1 };
+        drop(queue);
+        self.spawn_workers();
+        Ok(())
+    }
 
-        self.cancel_flag.store(false, Ordering::Relaxed);
-
-        let state = Arc::clone(&self.state);
-        let cancel_flag = Arc::clone(&self.cancel_flag);
-        thread::spawn(move || {
-            let queue = Arc::new(Mutex::new(
-                jobs.into_iter().enumerate().collect::<VecDeque<_>>(),
-            ));
-            let (tx, rx) = mpsc::channel::<RenderWorkerEvent>();
+    /// Queues all failed and canceled jobs again.
+    pub(super) fn retry_failed(&self) -> anyhow::Result<()> {
+        let mut queue = self.lock()?;
+        let retried = queue
+            .jobs
+            .iter_mut()
+            .filter_map(|job| job.requeue().ok())
+            .count();
+        if retried == 0 {
+            return Err(anyhow!("there are no failed render jobs"));
+        }
+        drop(queue);
+        self.spawn_workers();
+        Ok(())
+    }
 
-            let worker_count = if parallel {
-                workers.max(1).min(total_jobs)
-            } else {
-                1
-            };
-            let mut handles = Vec::with_capacity(worker_count);
-
-            for _ in 0..worker_count {
-                let queue = Arc::clone(&queue);
-                let tx = tx.clone();
-                let cancel_flag = Arc::clone(&cancel_flag);
-                handles.push(thread::spawn(move || {
-                    worker_loop(queue, tx, cancel_flag);
-                }));
+    pub(super) fn job_command(&self, id: u64, command: RenderJobCommand) -> anyhow::Result<()> {
+        let mut queue = self.lock()?;
+        let idx = queue.job_idx(id)?;
+        let job = &mut queue.jobs[idx];
+        let status = job.snapshot.status.clone();
+        match command {
+            RenderJobCommand::Cancel => job.cancel(),
+            RenderJobCommand::Retry => {
+                job.requeue()?;
+                drop(queue);
+                self.spawn_workers();
             }
-            drop(tx);
-
-            while let Ok(event) = rx.recv() {
-                let Ok(mut shared) = state.lock() else {
-                    break;
+            RenderJobCommand::Pause => {
+                let pid = job
+                    .pid
+                    .filter(|_| status == RenderJobStatus::Running)
+                    .ok_or_else(|| anyhow!("{} is not rendering", job.snapshot.name))?;
+                signal_process(pid, ProcessSignal::Stop)?;
+                job.snapshot.status = RenderJobStatus::Paused;
+            }
+            RenderJobCommand::Resume => {
+                let pid = job
+                    .pid
+                    .filter(|_| status == RenderJobStatus::Paused)
+                    .ok_or_else(|| anyhow!("{} is not paused", job.snapshot.name))?;
+                signal_process(pid, ProcessSignal::Continue)?;
+                job.snapshot.status = RenderJobStatus::Running;
+            }
+            RenderJobCommand::MoveUp | RenderJobCommand::MoveDown => {
+                if status != RenderJobStatus::Queued {
+                    return Err(anyhow!("only queued jobs can be moved"));
+                }
+                // Finished and running jobs do not take part in the order.
+                let queued =
+                    |other: &usize| queue.jobs[*other].snapshot.status == RenderJobStatus::Queued;
+                let other = match command {
+                    RenderJobCommand::MoveUp => (0..idx).rev().find(queued),
+                    _ => (idx + 1..queue.jobs.len()).find(queued),
                 };
-                match event {
-                    RenderWorkerEvent::Started(idx) => {
-                        if let Some(job) = shared.jobs.get_mut(idx) {
-                            job.status = RenderJobStatus::Running;
-                        }
-                    }
-                    RenderWorkerEvent::Progress(idx, progress) => {
-                        if let Some(job) = shared.jobs.get_mut(idx) {
-                            job.progress = progress.clamp(0.0, 1.0);
-                        }
-                    }
-                    RenderWorkerEvent::Finished(idx, err, canceled) => {
-                        if let Some(job) = shared.jobs.get_mut(idx) {
-                            if canceled {
-                                job.status = RenderJobStatus::Canceled;
-                                shared.canceled = true;
-                            } else if let Some(error) = err {
-                                job.status = RenderJobStatus::Failed;
-                                job.error = Some(error);
-                            } else {
-                                job.status = RenderJobStatus::Done;
-                                job.progress = 1.0;
-                            }
-                        }
-                        shared.finished_jobs = shared.finished_jobs.saturating_add(1);
-                    }
+                if let Some(other) = other {
+                    queue.jobs.swap(idx, other);
                 }
             }
+        }
+        Ok(())
+    }
+
+    /// Starts as many workers, as there are queued jobs, within limit.
+    fn spawn_workers(&self) {
+        let Ok(mut queue) = self.queue.lock() else {
+            return;
+        };
+        let count = queue
+            .max_workers
+            .max(1)
+            .saturating_sub(queue.running_workers)
+            .min(queue.queued_jobs());
+        queue.running_workers += count;
+        for _ in 0..count {
+            let queue = Arc::clone(&self.queue);
+            thread::spawn(move || worker_loop(queue));
+        }
+    }
+}
 
-            for handle in handles {
-                let _ = handle.join();
+fn update_job(queue: &Mutex<RenderQueue>, id: u64, update: impl FnOnce(&mut QueuedJob)) {
+    let Ok(mut queue) = queue.lock() else {
+        return;
+    };
+    if let Some(job) = queue.jobs.iter_mut().find(|job| job.snapshot.id == id) {
+        update(job);
+    }
+}
+
+fn worker_loop(queue: Arc<Mutex<RenderQueue>>) {
+    loop {
+        let next = {
+            let Ok(mut queue) = queue.lock() else {
+                return;
+            };
+            let next = queue.take_next();
+            if next.is_none() {
+                queue.running_workers = queue.running_workers.saturating_sub(1);
             }
+            next
+        };
+        let Some((id, job, cancel_flag)) = next else {
+            return;
+        };
 
-            if let Ok(mut shared) = state.lock() {
-                shared.active = false;
-                shared.canceled = shared.canceled || cancel_flag.load(Ordering::Relaxed);
+        let (error, canceled) = execute_render_job(id, &job, &cancel_flag, |update| {
+            update_job(&queue, id, |job| job.apply(update))
+        });
+        update_job(&queue, id, |job| {
+            job.pid = None;
+            if canceled {
+                job.snapshot.status = RenderJobStatus::Canceled;
+            } else if let Some(error) = error {
+                job.snapshot.status = RenderJobStatus::Failed;
+                job.snapshot.error = Some(error);
+            } else {
+                job.snapshot.status = RenderJobStatus::Done;
+                job.snapshot.progress = 1.0;
             }
+            job.snapshot.eta = None;
         });
-
-        Ok(())
     }
 }
 
-fn worker_loop(
-    queue: Arc<Mutex<VecDeque<(usize, RenderJobDefinition)>>>,
-    tx: mpsc::Sender<RenderWorkerEvent>,
-    cancel_flag: Arc<AtomicBool>,
-) {
-    loop {
-        if cancel_flag.load(Ordering::Relaxed) {
-            break;
-        }
+/// Jobs are paused by stopping ffmpeg process, which is possible only on
+/// Linux and macOS. Pause is not offered on other platforms.
+pub(super) const PAUSE_SUPPORTED: bool = cfg!(unix);
 
-        let Some((idx, job)) = queue.lock().ok().and_then(|mut q| q.pop_front()) else {
-            break;
-        };
+#[derive(Debug, Clone, Copy)]
+enum ProcessSignal {
+    Stop,
+    Continue,
+}
 
-        let _ = tx.send(RenderWorkerEvent::Started(idx));
-        let result = execute_render_job(&job, &tx, idx, &cancel_flag);
-        let _ = tx.send(RenderWorkerEvent::Finished(idx, result.0, result.1));
+/// Stops or continues ffmpeg process.
+#[cfg(unix)]
+fn signal_process(pid: u32, signal: ProcessSignal) -> anyhow::Result<()> {
+    let signal_number = match signal {
+        ProcessSignal::Stop => libc::SIGSTOP,
+        ProcessSignal::Continue => libc::SIGCONT,
+    };
+    let pid = libc::pid_t::try_from(pid)?;
+    if unsafe { libc::kill(pid, signal_number) } != 0 {
+        return Err(anyhow!(
+            "can not send {signal:?} to ffmpeg {pid}: {}",
+            std::io::Error::last_os_error()
+        ));
     }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn signal_process(_pid: u32, _signal: ProcessSignal) -> anyhow::Result<()> {
+    Err(anyhow!(
+        "pausing ffmpeg is supported only on Linux and macOS"
+    ))
 }
 
 fn execute_render_job(
+    id: u64,
     job: &RenderJobDefinition,
-    tx: &mpsc::Sender<RenderWorkerEvent>,
-    idx: usize,
     cancel_flag: &AtomicBool,
+    mut on_update: impl FnMut(JobUpdate),
 ) -> (Option<String>, bool) {
-    let (mut command, command_str) = match build_ffmpeg_command(job) {
+    // Every ffmpeg run of the job, including preparation passes, is logged.
+    let mut log = open_job_log(&job.log_path);
+    let loudness = match &job.audio_preset.loudness {
+        Some(target) => {
+            match measure_loudness(&job.render_target.path, target, cancel_flag, &mut log) {
+                Ok(Some(measured)) => {
+                    write_log_line(&mut log, &format!("# measured loudness: {measured}"));
+                    on_update(JobUpdate::Measured(measured.clone()));
+                    Some(measured)
+                }
+                Ok(None) => return log_canceled(&mut log),
+                Err(error) => return log_failed(&mut log, error.to_string()),
+            }
+        }
+        None => None,
+    };
+    let cut = match &job.video {
+        JobVideoInput::Window { path, offset } if job.smart_cut && job.video_preset.is_copy() => {
+            let dir = job_temp_path(id, job, "smartcut");
+            let duration = render_duration_secs(&job.render_target.duration);
+            match smart_cut(path, *offset, duration, dir, cancel_flag, &mut log) {
+                Ok(Some(cut)) => Some(cut),
+                Ok(None) => return log_canceled(&mut log),
+                Err(error) => return log_failed(&mut log, format!("smart cut failed: {error}")),
+            }
+        }
+        _ => None,
+    };
+    // Pieces are removed, when `cut` is dropped at the end of the job.
+    let cut_job;
+    let job = match &cut {
+        Some(cut) => {
+            cut_job = RenderJobDefinition {
+                video: JobVideoInput::Concat(cut.list.clone()),
+                ..job.clone()
+            };
+            &cut_job
+        }
+        None => job,
+    };
+    // The file is removed, when `_commands` is dropped at the end of the job.
+    let _commands = match beat_commands(&job.labels) {
+        Some(commands) => match TempFile::write(job_temp_path(id, job, "beats"), &commands) {
+            Ok(file) => Some(file),
+            Err(error) => {
+                return log_failed(&mut log, format!("can not write beat commands: {error}"))
+            }
+        },
+        None => None,
+    };
+    let (mut command, command_str) = match build_ffmpeg_command(id, job, loudness.as_ref()) {
         Ok(value) => value,
-        Err(error) => return (Some(error.to_string()), false),
+        Err(error) => return log_failed(&mut log, error.to_string()),
     };
+    on_update(JobUpdate::Command(command_str.clone()));
 
+    if cancel_flag.load(Ordering::Relaxed) {
+        return log_canceled(&mut log);
+    }
+    write_log_line(&mut log, &format!("# {command_str}"));
     command.stdout(Stdio::piped()).stderr(Stdio::piped());
-    let Ok(mut child) = command.spawn() else {
-        return (
-            Some(format!(
-                "failed to spawn ffmpeg for '{}': {}",
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(error) => {
+            let error = format!(
+                "failed to spawn ffmpeg for '{}': {error}\n{command_str}",
                 job.output_path.display(),
-                command_str
-            )),
-            false,
-        );
+            );
+            write_log_line(&mut log, &error);
+            return (Some(error), false);
+        }
     };
 
+    on_update(JobUpdate::Spawned(child.id()));
+
+    // Full stderr goes to the log, a few last lines go to the error message.
     let stderr_reader = child.stderr.take().map(BufReader::new);
     let stderr_handle = stderr_reader.map(|reader| {
         thread::spawn(move || {
-            let mut log = String::new();
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    log.push_str(&line);
-                    log.push('\n');
+            let mut tail = VecDeque::with_capacity(ERROR_LOG_LINES);
+            for line in reader.lines().map_while(Result::ok) {
+                write_log_line(&mut log, &line);
+                if tail.len() == ERROR_LOG_LINES {
+                    tail.pop_front();
                 }
+                tail.push_back(line);
             }
-            log
+            (tail.into_iter().collect::<Vec<_>>().join("\n"), log)
         })
     });
 
     let mut canceled = false;
+    let mut stats = RenderProgress::default();
     if let Some(stdout) = child.stdout.take() {
         let reader = BufReader::new(stdout);
         for line in reader.lines() {
@@ -277,36 +728,32 @@ fn execute_render_job(
             let Ok(line) = line else {
                 continue;
             };
-            if let Some(progress) =
-                parse_ffmpeg_progress(&line, render_duration_secs(&job.render_target.duration))
-            {
-                let _ = tx.send(RenderWorkerEvent::Progress(idx, progress));
+            if stats.parse_line(&line) {
+                on_update(JobUpdate::Progress(stats.clone()));
             }
         }
     }
 
     let wait_result = child.wait();
-    let stderr_log = match stderr_handle {
+    let (log_tail, mut log) = match stderr_handle {
         Some(handle) => handle.join().unwrap_or_default(),
-        None => String::new(),
+        None => Default::default(),
     };
 
     if canceled || cancel_flag.load(Ordering::Relaxed) {
-        return (None, true);
+        return log_canceled(&mut log);
     }
 
     match wait_result {
-        Ok(status) if status.success() => (None, false),
+        Ok(status) if status.success() => match verify_output(job) {
+            Ok(()) => {
+                write_log_line(&mut log, "# done");
+                (None, false)
+            }
+            Err(error) => log_failed(&mut log, format!("output verification failed: {error}")),
+        },
         Ok(status) => {
-            let log_tail = stderr_log
-                .lines()
-                .rev()
-                .take(12)
-                .collect::<Vec<_>>()
-                .into_iter()
-                .rev()
-                .collect::<Vec<_>>()
-                .join("\n");
+            write_log_line(&mut log, &format!("# ffmpeg exited with status {status}"));
             (
                 Some(format!(
                     "ffmpeg exited with status {}{}",
@@ -320,46 +767,124 @@ fn execute_render_job(
                 false,
             )
         }
-        Err(error) => (Some(format!("failed waiting ffmpeg process: {error}")), false),
+        Err(error) => (
+            Some(format!("failed waiting ffmpeg process: {error}")),
+            false,
+        ),
     }
 }
 
-fn render_duration_secs(duration: &rea_rs::Duration) -> f64 {
-    duration
-        .num_microseconds()
-        .map(|value| (value.max(0) as f64) / 1_000_000.0)
-        .unwrap_or(0.0)
+/// Temporary file or directory of the job, named `{output file name}.{ext}`.
+///
+/// Parallel jobs may render files with the same name, so the path is unique
+/// for the job and REAPER instance.
+fn job_temp_path(id: u64, job: &RenderJobDefinition, ext: &str) -> PathBuf {
+    std::env::temp_dir().join("reaper-levitanus").join(format!(
+        "{}-{id}-{}.{ext}",
+        std::process::id(),
+        job.output_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+    ))
 }
 
-fn parse_ffmpeg_progress(line: &str, duration_secs: f64) -> Option<f32> {
-    if duration_secs <= f64::EPSILON {
-        return Some(1.0);
+/// File, removed on drop.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn write(path: PathBuf, contents: &str) -> std::io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, contents)?;
+        Ok(Self(path))
     }
+}
 
-    if let Some(us) = line.strip_prefix("out_time_us=") {
-        let micros = us.trim().parse::<f64>().ok()?;
-        return Some((micros / 1_000_000.0 / duration_secs) as f32);
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(error) = std::fs::remove_file(&self.0) {
+            warn!("can not remove {}: {error}", self.0.display());
+        }
     }
+}
 
-    if let Some(ms) = line.strip_prefix("out_time_ms=") {
-        let value = ms.trim().parse::<f64>().ok()?;
-        let seconds = if value > 10_000_000.0 {
-            value / 1_000_000.0
-        } else {
-            value / 1000.0
-        };
-        return Some((seconds / duration_secs) as f32);
+/// Creates log file of the job.
+///
+/// Render does not fail because of log, it is just not written.
+fn open_job_log(path: &Path) -> Option<File> {
+    let file = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| File::create(path));
+    match file {
+        Ok(file) => Some(file),
+        Err(error) => {
+            warn!("can not create ffmpeg log {}: {error}", path.display());
+            None
+        }
+    }
+}
+
+/// Result of the canceled job.
+fn log_canceled(log: &mut Option<File>) -> (Option<String>, bool) {
+    write_log_line(log, "# canceled");
+    (None, true)
+}
+
+/// Result of the failed job.
+fn log_failed(log: &mut Option<File>, error: String) -> (Option<String>, bool) {
+    write_log_line(log, &format!("# {error}"));
+    (Some(error), false)
+}
+
+pub(super) fn write_log_line(log: &mut Option<File>, line: &str) {
+    if let Some(file) = log {
+        if let Err(error) = writeln!(file, "{line}") {
+            warn!("can not write ffmpeg log: {error}");
+            *log = None;
+        }
     }
+}
 
-    if let Some(ts) = line.strip_prefix("out_time=") {
-        let seconds = parse_ffmpeg_timestamp(ts.trim())?;
-        return Some((seconds / duration_secs) as f32);
+pub(super) fn render_duration_secs(duration: &rea_rs::Duration) -> f64 {
+    duration
+        .num_microseconds()
+        .map(|value| (value.max(0) as f64) / 1_000_000.0)
+        .unwrap_or(0.0)
+}
+
+/// Seconds from any of `out_time_us`, `out_time_ms` or `out_time`.
+fn parse_out_time(key: &str, value: &str) -> Option<f64> {
+    match key {
+        "out_time_us" => Some(value.parse::<f64>().ok()? / 1_000_000.0),
+        // Despite the name, recent ffmpeg writes microseconds here.
+        "out_time_ms" => {
+            let value = value.parse::<f64>().ok()?;
+            Some(if value > 10_000_000.0 {
+                value / 1_000_000.0
+            } else {
+                value / 1000.0
+            })
+        }
+        "out_time" => parse_ffmpeg_timestamp(value),
+        _ => None,
     }
+}
 
-    None
+/// Time left, as `h:mm:ss`.
+pub(super) fn eta_string(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
 }
 
-fn parse_ffmpeg_timestamp(value: &str) -> Option<f64> {
+pub(super) fn parse_ffmpeg_timestamp(value: &str) -> Option<f64> {
     let (hms, fraction) = value.split_once('.').unwrap_or((value, "0"));
     let mut hms_iter = hms.split(':');
     let hours = hms_iter.next()?.parse::<f64>().ok()?;
@@ -377,7 +902,12 @@ fn timestamp_string(seconds: f64) -> String {
     format!("{:02}:{:02}:{:06.3}", hours as u64, minutes as u64, secs)
 }
 
-fn build_ffmpeg_command(job: &RenderJobDefinition) -> anyhow::Result<(Command, String)> {
+/// ffmpeg command of the job, loudness is applied once it is measured.
+fn build_ffmpeg_command(
+    id: u64,
+    job: &RenderJobDefinition,
+    loudness: Option<&LoudnessMeasurement>,
+) -> anyhow::Result<(Command, String)> {
     let mut args = vec![
         "-hide_banner".to_string(),
         "-y".to_string(),
@@ -386,46 +916,120 @@ fn build_ffmpeg_command(job: &RenderJobDefinition) -> anyhow::Result<(Command, S
         "pipe:1".to_string(),
     ];
 
-    if job.use_source_window {
-        let offset = timestamp_string(job.render_target.source_offset.as_secs_f64());
-        let duration = timestamp_string(render_duration_secs(&job.render_target.duration));
-        args.extend([
-            "-ss".to_string(),
-            offset,
-            "-t".to_string(),
-            duration,
-        ]);
+    let duration = render_duration_secs(&job.render_target.duration);
+    let mut filter_graph = None;
+    // Audio and subtitle inputs follow all video inputs.
+    let audio_input = match &job.video {
+        JobVideoInput::File(path) => {
+            args.extend(["-i".to_string(), path.to_string_lossy().to_string()]);
+            1
+        }
+        JobVideoInput::Window { path, offset } => {
+            args.extend([
+                "-ss".to_string(),
+                timestamp_string(*offset),
+                "-t".to_string(),
+                timestamp_string(duration),
+                "-i".to_string(),
+                path.to_string_lossy().to_string(),
+            ]);
+            1
+        }
+        JobVideoInput::Concat(list) => {
+            args.extend([
+                "-f".to_string(),
+                "concat".to_string(),
+                "-safe".to_string(),
+                "0".to_string(),
+                "-i".to_string(),
+                list.to_string_lossy().to_string(),
+            ]);
+            1
+        }
+        JobVideoInput::AudioOnly => 0,
+        JobVideoInput::Timeline { timeline } => {
+            let geometry = job.geometry.clone().unwrap_or_default();
+            let (inputs, graph) = timeline.build_filter_graph(&geometry, 0);
+            let count = inputs.iter().filter(|arg| *arg == "-i").count();
+            args.extend(inputs);
+            filter_graph = Some(graph);
+            count
+        }
+    };
+    let single_source = !matches!(
+        job.video,
+        JobVideoInput::AudioOnly | JobVideoInput::Timeline { .. }
+    );
+    if let Some(geometry) = job.geometry.as_ref().filter(|_| single_source) {
+        filter_graph = Some(format!("[0:v:0]{}[vout]", geometry.normalize_filter()));
     }
 
     args.extend([
-        "-i".to_string(),
-        job.input_video_path.to_string_lossy().to_string(),
         "-i".to_string(),
         job.render_target.path.to_string_lossy().to_string(),
-        "-map".to_string(),
-        "0:v:0".to_string(),
-        "-map".to_string(),
-        "1:a:0".to_string(),
-        "-c:v".to_string(),
-        job.video_codec.clone(),
     ]);
+    if let Some(subtitles) = &job.subtitles {
+        args.extend([
+            "-i".to_string(),
+            subtitles.path.to_string_lossy().to_string(),
+        ]);
+    }
+    let overlay = job
+        .overlay
+        .as_ref()
+        .filter(|_| !matches!(job.video, JobVideoInput::AudioOnly));
+    let logo_input = audio_input + 1 + usize::from(job.subtitles.is_some());
+    if let Some(logo) = overlay.and_then(OverlaySettings::logo) {
+        args.extend(["-i".to_string(), logo.to_string_lossy().to_string()]);
+    }
 
-    if job.video_codec == "libx264" || job.video_codec == "h264" {
-        args.extend(["-crf".to_string(), "15".to_string()]);
+    // Every stage takes the video by label of the previous one.
+    let (mut chains, mut label) = match filter_graph {
+        Some(graph) => (vec![graph], "vout"),
+        None => (Vec::new(), "0:v:0"),
+    };
+    if let Some(overlay) = overlay {
+        let commands = job_temp_path(id, job, "beats");
+        chains.push(overlay.filter(&job.labels, &commands, label, "voverlay", logo_input));
+        label = "voverlay";
+    }
+    if let Some(height) = job.scale_height {
+        chains.push(format!("[{label}]scale=-2:{height}[vscaled]"));
+        label = "vscaled";
+    }
+    if !matches!(job.video, JobVideoInput::AudioOnly) {
+        let video_map = if chains.is_empty() {
+            label.to_string()
+        } else {
+            args.extend(["-filter_complex".to_string(), chains.join(";")]);
+            format!("[{label}]")
+        };
+        args.extend(["-map".to_string(), video_map]);
+    }
+    args.extend(["-map".to_string(), format!("{audio_input}:a:0")]);
+    if !matches!(job.video, JobVideoInput::AudioOnly) {
+        args.extend(job.video_preset.args());
+    }
+    if let Some(subtitles) = &job.subtitles {
+        args.extend([
+            "-map".to_string(),
+            format!("{}:s:0", audio_input + 1),
+            "-c:s".to_string(),
+            subtitles.codec.clone(),
+        ]);
     }
 
-    args.extend(["-c:a".to_string(), job.audio_codec.clone()]);
-    if job.audio_codec == "aac" {
-        args.extend(["-b:a".to_string(), "384K".to_string()]);
+    args.extend(job.audio_preset.args());
+    if let (Some(target), Some(measured)) = (&job.audio_preset.loudness, loudness) {
+        args.extend(["-af".to_string(), target.apply_filter(measured)]);
     }
 
     args.push("-shortest".to_string());
     args.push(job.output_path.to_string_lossy().to_string());
 
-    let mut command = Command::new("ffmpeg");
+    let mut command = ffmpeg_command();
     command.args(&args);
-
-    let command_str = format!("ffmpeg {}", args.join(" "));
+    let command_str = command_line(&command);
     Ok((command, command_str))
 }
 
@@ -435,3 +1039,41 @@ fn render_target_name(path: &PathBuf) -> String {
         .map(|stem| stem.to_string())
         .unwrap_or_else(|| "<unnamed>".to_string())
 }
+
+#[test]
+fn test_progress_block() {
+    let mut stats = RenderProgress::default();
+    let block = "frame=250\nfps=49.87\nstream_0_0_q=23.0\nbitrate=N/A\n\
+                 total_size=1048576\nout_time_us=10000000\nout_time_ms=10000000\n\
+                 out_time=00:00:10.000000\ndup_frames=0\ndrop_frames=0\nspeed=1.99x\n\
+                 progress=continue";
+    let complete = block
+        .lines()
+        .map(|line| stats.parse_line(line))
+        .collect::<Vec<_>>();
+    assert_eq!(complete.iter().filter(|c| **c).count(), 1);
+    assert_eq!(complete.last(), Some(&true));
+    assert_eq!(stats.frame, Some(250));
+    assert_eq!(stats.bitrate, None);
+    assert_eq!(stats.total_size, Some(1048576));
+    assert_eq!(stats.speed, Some(1.99));
+    assert!((stats.out_time - 10.0).abs() < 1e-9);
+
+    let mut job = RenderJobSnapshot {
+        id: 0,
+        name: "song".to_string(),
+        output: "song.mkv".to_string(),
+        progress: 0.0,
+        duration: 30.0,
+        stats: RenderProgress::default(),
+        eta: None,
+        status: RenderJobStatus::Running,
+        error: None,
+        command: None,
+        log_path: PathBuf::from("song.mkv.log"),
+        loudness: None,
+    };
+    job.update_progress(stats);
+    assert!((job.progress - 1.0 / 3.0).abs() < 1e-6);
+    assert_eq!(job.eta.map(eta_string), Some("0:00:10".to_string()));
+}
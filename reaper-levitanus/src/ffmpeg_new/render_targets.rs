@@ -4,9 +4,15 @@ use anyhow::anyhow;
 use log::debug;
 use rea_rs::{
     project_info::{BoundsMode, RenderMode},
-    Duration, Position, Project, SoloMode, SourceOffset,
+    Duration, Position, Project, SoloMode,
 };
 
+use super::{
+    overlays::TargetLabels,
+    timeline::{VideoSegment, VideoTimeline},
+};
+use crate::{tempo_map::beat_grid, transitions::get_item_transitions};
+
 pub(super) const DEFAULT_RENDER_TARGETS_BUF_SIZE: usize = 1024;
 pub(super) const FALLBACK_RENDER_TARGETS_BUF_SIZE: usize = 1024 * 10;
 
@@ -19,17 +25,22 @@ pub(super) struct RenderTargetsBuild {
 #[derive(Debug, Clone)]
 pub(super) struct RenderTarget {
     pub(super) path: PathBuf,
-    pub(super) video_source: Option<PathBuf>,
     pub(super) availble_for_render: AvailbleForRender,
     pub(super) duration: Duration,
-    pub(super) source_offset: SourceOffset,
+    /// All video items of the target scope, composed and clipped to render bound.
+    pub(super) timeline: VideoTimeline,
+    /// Render bound in project time, seconds.
+    pub(super) bounds: (f64, f64),
+    /// Region of the render bound, or file name of the target.
+    pub(super) region: String,
 }
 
 #[derive(Debug, Clone)]
 pub(super) enum AvailbleForRender {
     Ok,
+    /// Video does not cover the whole bound, gaps are filled with pad color.
+    Gaps,
     NoVideo,
-    OutOfBounds(Position),
 }
 
 const TIMELINE_PRECISION: u32 = 1_000_000;
@@ -38,6 +49,8 @@ const TIMELINE_PRECISION: u32 = 1_000_000;
 struct RenderBound {
     start: Position,
     end: Position,
+    /// Name of the region, if the bound is a region.
+    name: Option<String>,
     rendered_tracks: Vec<(usize, String)>,
 }
 
@@ -77,16 +90,16 @@ pub(super) fn build_render_targets(
         .iter()
         .filter(|t| matches!(t.availble_for_render, AvailbleForRender::NoVideo))
         .count();
-    let out_of_bounds = targets
+    let gaps = targets
         .iter()
-        .filter(|t| matches!(t.availble_for_render, AvailbleForRender::OutOfBounds(_)))
+        .filter(|t| matches!(t.availble_for_render, AvailbleForRender::Gaps))
         .count();
     debug!(
-        "ffmpeg_new/render_targets: build done total={} ok={} no_video={} out_of_bounds={}",
+        "ffmpeg_new/render_targets: build done total={} ok={} gaps={} no_video={}",
         targets.len(),
         ok,
-        no_video,
-        out_of_bounds
+        gaps,
+        no_video
     );
     Ok(RenderTargetsBuild {
         targets,
@@ -102,12 +115,16 @@ fn build_render_target_from_plan(
     let bound_end = plan.bound.end.with_precision(TIMELINE_PRECISION);
     let duration = rea_rs::Duration::from_std((bound_end - bound_start).as_duration())
         .unwrap_or_else(|_| rea_rs::Duration::zero());
+    let origin = bound_start.as_duration().as_secs_f64();
+    let mut timeline = VideoTimeline::new((bound_end - bound_start).as_duration().as_secs_f64());
 
-    for track_idx in tracks_for_scope(pr, &plan.scope) {
+    // Tracks are pushed from bottom to top, so upper tracks cover lower ones.
+    for track_idx in tracks_for_scope(pr, &plan.scope).into_iter().rev() {
         let track = pr
             .get_track(track_idx)
             .ok_or_else(|| anyhow!("can not get track with index {track_idx}"))?;
 
+        let mut segments = Vec::new();
         for item_idx in 0..track.n_items() {
             let item = track
                 .get_item(item_idx)
@@ -132,49 +149,109 @@ fn build_render_target_from_plan(
                 continue;
             }
 
-            if item_start > bound_start || item_end < bound_end {
-                let out_of_bounds = if item_start > bound_start {
-                    bound_start
-                } else {
-                    item_end
-                };
-                debug!(
-					"ffmpeg_new/render_targets: OutOfBounds target={} item_bounds=({:?},{:?}) render_bounds=({:?},{:?})",
-					plan.render_target.display(),
-					item_start,
-					item_end,
-					bound_start,
-					bound_end
-				);
-                return Ok(RenderTarget {
-                    path: plan.render_target.clone(),
-                    video_source: Some(source.filename()),
-                    availble_for_render: AvailbleForRender::OutOfBounds(out_of_bounds),
-                    duration,
-                    source_offset: take.start_offset(),
-                });
-            }
-
-            let source_offset = take.start_offset() + (bound_start - item.position()).as_duration();
-            return Ok(RenderTarget {
-                path: plan.render_target.clone(),
-                video_source: Some(source.filename()),
-                availble_for_render: AvailbleForRender::Ok,
-                duration,
-                source_offset,
+            let transitions = get_item_transitions(&item);
+            segments.push(VideoSegment {
+                file: source.filename(),
+                timeline_start: item_start.as_duration().as_secs_f64() - origin,
+                timeline_end: item_end.as_duration().as_secs_f64() - origin,
+                source_offset: take.start_offset().as_secs_f64(),
+                fade_in: item.fade_in().length.as_secs_f64(),
+                fade_out: item.fade_out().length.as_secs_f64(),
+                fade_in_transition: transitions.fade_in,
+                fade_out_transition: transitions.fade_out,
             });
         }
+        timeline.push_track(segments);
     }
 
+    let availble_for_render = if timeline.is_empty() {
+        AvailbleForRender::NoVideo
+    } else if timeline.has_gaps() {
+        debug!(
+            "ffmpeg_new/render_targets: Gaps target={} sources={} render_bounds=({:?},{:?})",
+            plan.render_target.display(),
+            timeline.sources().len(),
+            bound_start,
+            bound_end
+        );
+        AvailbleForRender::Gaps
+    } else {
+        AvailbleForRender::Ok
+    };
+
     Ok(RenderTarget {
         path: plan.render_target.clone(),
-        video_source: None,
-        availble_for_render: AvailbleForRender::NoVideo,
+        availble_for_render,
         duration,
-        source_offset: SourceOffset::from_secs_f64(0.0),
+        timeline,
+        bounds: (origin, bound_end.as_duration().as_secs_f64()),
+        region: plan.bound.name.clone().unwrap_or_else(|| {
+            plan.render_target
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default()
+        }),
     })
 }
 
+/// Names and musical grid of the target, shown by burn-in overlays.
+///
+/// Beats are walked only `with_beats`, as it takes a call per beat.
+pub(super) fn collect_target_labels(
+    pr: &Project,
+    target: &RenderTarget,
+    with_beats: bool,
+) -> TargetLabels {
+    let (start, end) = target.bounds;
+    let mut markers: Vec<(f64, String)> = Vec::new();
+    for marker in pr.iter_markers_and_regions().filter(|m| !m.is_region) {
+        let time = marker.position.as_duration().as_secs_f64() - start;
+        if time + start >= end {
+            continue;
+        }
+        if time <= 0.0 {
+            // The marker, which is passed at the start, replaces earlier ones.
+            markers.retain(|(time, _)| *time > 0.0);
+            markers.insert(0, (0.0, marker.name.clone()));
+        } else {
+            markers.push((time, marker.name.clone()));
+        }
+    }
+    markers.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    TargetLabels {
+        project: pr.name(),
+        region: target.region.clone(),
+        start,
+        markers,
+        beats: if with_beats {
+            collect_beats(pr, start, end)
+        } else {
+            Vec::new()
+        },
+    }
+}
+
+/// Beats between `start` and `end`: time from `start` and `bar.beat`.
+///
+/// The first entry is the beat, playing at `start`.
+fn collect_beats(pr: &Project, start: f64, end: f64) -> Vec<(f64, String)> {
+    let mut beats: Vec<(f64, String)> = Vec::new();
+    for point in beat_grid(pr, start, end, Some(1)) {
+        if point.time >= end {
+            break;
+        }
+        let label = format!("{}.{}", point.measure + 1, point.step + 1);
+        if point.time <= start {
+            // The beat, which is playing at the start, replaces earlier ones.
+            beats.clear();
+            beats.push((0.0, label));
+        } else {
+            beats.push((point.time - start, label));
+        }
+    }
+    beats
+}
+
 fn build_render_target_plan(
     pr: &mut Project,
     render_targets_buf_size: usize,
@@ -337,6 +414,7 @@ fn collect_render_bounds(pr: &Project) -> anyhow::Result<Vec<RenderBound>> {
         BoundsMode::EntireProject => Ok(vec![RenderBound {
             start: Position::from(0.0),
             end: pr.length().into(),
+            name: None,
             rendered_tracks: Vec::new(),
         }]),
         BoundsMode::Custom => {
@@ -344,6 +422,7 @@ fn collect_render_bounds(pr: &Project) -> anyhow::Result<Vec<RenderBound>> {
             Ok(vec![RenderBound {
                 start,
                 end,
+                name: None,
                 rendered_tracks: Vec::new(),
             }])
         }
@@ -352,6 +431,7 @@ fn collect_render_bounds(pr: &Project) -> anyhow::Result<Vec<RenderBound>> {
             Ok(vec![RenderBound {
                 start: ts.get_start(),
                 end: ts.get_end(),
+                name: None,
                 rendered_tracks: Vec::new(),
             }])
         }
@@ -362,6 +442,7 @@ fn collect_render_bounds(pr: &Project) -> anyhow::Result<Vec<RenderBound>> {
                 .map(|item| RenderBound {
                     start: item.position(),
                     end: item.end_position(),
+                    name: None,
                     rendered_tracks: Vec::new(),
                 })
                 .collect::<Vec<_>>();
@@ -384,6 +465,7 @@ fn collect_region_bounds(pr: &Project, selected_only: bool) -> Vec<RenderBound>
         .map(|region| RenderBound {
             start: region.position,
             end: region.rgn_end,
+            name: Some(region.name.clone()),
             rendered_tracks: region
                 .iter_rendered_tracks(pr)
                 .map(|tr| (tr.index(), tr.name()))
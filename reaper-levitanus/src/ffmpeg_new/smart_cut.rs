@@ -0,0 +1,455 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::atomic::AtomicBool,
+};
+
+use anyhow::anyhow;
+use log::{debug, warn};
+
+use super::binaries::{ffmpeg_command, ffprobe_command, run_cancelable};
+
+/// Times closer than this are considered equal, less than a frame.
+const CUT_EPSILON: f64 = 0.001;
+/// Copied part is sought a bit after its keyframe, so rounding of
+/// timestamps can not move the seek to the previous keyframe.
+const KEYFRAME_SEEK_OFFSET: f64 = 0.0005;
+/// Keyframes are read only around the cut window.
+const KEYFRAME_SEARCH_MARGIN: f64 = 30.0;
+
+/// Part of the cut window, copied or re-encoded.
+#[derive(Debug, Clone, PartialEq)]
+struct CutSegment {
+    start: f64,
+    end: f64,
+    copy: bool,
+}
+
+/// Splits window into re-encoded partial GOPs at head and tail, and copied
+/// middle, which starts and ends on keyframes.
+fn plan_segments(keyframes: &[f64], start: f64, end: f64) -> Vec<CutSegment> {
+    let inner = keyframes
+        .iter()
+        .copied()
+        .filter(|keyframe| *keyframe >= start - CUT_EPSILON && *keyframe <= end + CUT_EPSILON)
+        .collect::<Vec<_>>();
+    let segment = |start: f64, end: f64, copy: bool| CutSegment { start, end, copy };
+    match (inner.first(), inner.last()) {
+        (Some(&first), Some(&last)) if last - first > CUT_EPSILON => {
+            let mut segments = Vec::new();
+            if first - start > CUT_EPSILON {
+                segments.push(segment(start, first, false));
+            }
+            segments.push(segment(first.max(start), last.min(end), true));
+            if end - last > CUT_EPSILON {
+                segments.push(segment(last, end, false));
+            }
+            segments
+        }
+        _ => vec![segment(start, end, false)],
+    }
+}
+
+/// Video stream of the source, re-encoded parts have to match it.
+#[derive(Debug, Clone, PartialEq)]
+struct SourceVideo {
+    codec: String,
+    profile: Option<String>,
+    /// `level_idc` as reported by ffprobe, e.g. 41 for h264 level 4.1.
+    level: Option<u32>,
+    pix_fmt: Option<String>,
+    bit_rate: Option<u64>,
+}
+
+impl SourceVideo {
+    /// Encoder and bitstream filter for MPEG-TS parts.
+    fn encoder(&self) -> anyhow::Result<(&'static str, &'static str)> {
+        match self.codec.as_str() {
+            "h264" => Ok(("libx264", "h264_mp4toannexb")),
+            "hevc" => Ok(("libx265", "hevc_mp4toannexb")),
+            codec => Err(anyhow!("smart cut supports h264 and hevc, not {codec}")),
+        }
+    }
+
+    /// Profile name for the encoder. Only profiles, which libx264 and
+    /// libx265 can reproduce, are supported.
+    fn encoder_profile(&self) -> anyhow::Result<&'static str> {
+        let profile = self.profile.as_deref().unwrap_or("unknown");
+        let encoder_profile = match (self.codec.as_str(), profile.to_lowercase().as_str()) {
+            ("h264", "constrained baseline" | "baseline") => "baseline",
+            ("h264", "main") => "main",
+            ("h264", "high") => "high",
+            ("h264", "high 10") => "high10",
+            ("hevc", "main") => "main",
+            ("hevc", "main 10") => "main10",
+            _ => {
+                return Err(anyhow!(
+                    "smart cut does not support {} profile {profile}",
+                    self.codec
+                ))
+            }
+        };
+        Ok(encoder_profile)
+    }
+
+    /// Head and tail are encoded with profile, level and pixel format of the
+    /// source. Parameter sets are repeated at every keyframe, as concat
+    /// demuxer keeps extradata only of the first part.
+    fn encode_args(&self) -> anyhow::Result<Vec<String>> {
+        let (encoder, _) = self.encoder()?;
+        let mut args = vec![
+            "-c:v".to_string(),
+            encoder.to_string(),
+            "-profile:v".to_string(),
+            self.encoder_profile()?.to_string(),
+        ];
+        if let Some(pix_fmt) = &self.pix_fmt {
+            args.extend(["-pix_fmt".to_string(), pix_fmt.clone()]);
+        }
+        match (encoder, self.level) {
+            ("libx264", level) => {
+                if let Some(level) = level {
+                    args.extend([
+                        "-level:v".to_string(),
+                        format!("{}.{}", level / 10, level % 10),
+                    ]);
+                }
+                args.extend(["-x264-params".to_string(), "repeat-headers=1".to_string()]);
+            }
+            (_, level) => {
+                let mut params = vec!["repeat-headers=1".to_string()];
+                if let Some(level) = level {
+                    // HEVC level_idc is 30 times the level.
+                    params.push(format!("level-idc={:.1}", f64::from(level) / 30.0));
+                }
+                args.extend(["-x265-params".to_string(), params.join(":")]);
+            }
+        }
+        match self.bit_rate {
+            Some(bit_rate) => args.extend(["-b:v".to_string(), bit_rate.to_string()]),
+            None => args.extend(["-crf".to_string(), "16".to_string()]),
+        }
+        Ok(args)
+    }
+}
+
+fn ffprobe_lines(path: &Path, args: &[&str], format: &str) -> anyhow::Result<Vec<String>> {
+    let output = ffprobe_command()
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(args)
+        .args(["-of", format])
+        .arg(path)
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "can not probe {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+fn probe_source_video(path: &Path) -> anyhow::Result<SourceVideo> {
+    let lines = ffprobe_lines(
+        path,
+        &[
+            "-show_entries",
+            "stream=codec_name,profile,level,pix_fmt,bit_rate",
+        ],
+        "default=noprint_wrappers=1",
+    )?;
+    let field = |key: &str| {
+        lines
+            .iter()
+            .filter_map(|line| line.split_once('='))
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value.trim())
+            .filter(|value| !value.is_empty() && *value != "N/A" && *value != "unknown")
+            .map(|value| value.to_string())
+    };
+    Ok(SourceVideo {
+        codec: field("codec_name")
+            .ok_or_else(|| anyhow!("{} has no video stream", path.display()))?,
+        profile: field("profile"),
+        level: field("level").and_then(|level| level.parse().ok()),
+        pix_fmt: field("pix_fmt"),
+        bit_rate: field("bit_rate").and_then(|rate| rate.parse().ok()),
+    })
+}
+
+/// Timestamp of the file start. Non-zero e.g. for MPEG-TS and MPEG-PS.
+fn probe_start_time(path: &Path) -> anyhow::Result<f64> {
+    let lines = ffprobe_lines(
+        path,
+        &["-show_entries", "format=start_time"],
+        "default=noprint_wrappers=1:nokey=1",
+    )?;
+    Ok(lines
+        .first()
+        .and_then(|line| line.parse::<f64>().ok())
+        .filter(|start_time| start_time.is_finite())
+        .unwrap_or(0.0))
+}
+
+/// Keyframe times from `packet=pts_time,flags` lines in decoding order,
+/// relative to the file start, as input `-ss` counts them.
+///
+/// Fails on open GOP: a frame after the keyframe, shown before it, refers to
+/// the previous GOP, so the copied part can not start there.
+fn parse_keyframes(lines: &[String], start_time: f64) -> anyhow::Result<Vec<f64>> {
+    let mut keyframes: Vec<f64> = Vec::new();
+    for line in lines {
+        let Some((pts_time, flags)) = line.split_once(',') else {
+            continue;
+        };
+        let Ok(pts) = pts_time.parse::<f64>() else {
+            continue;
+        };
+        let time = pts - start_time;
+        if flags.contains('K') {
+            keyframes.push(time);
+        } else if let Some(keyframe) = keyframes.last().filter(|k| time < **k - CUT_EPSILON) {
+            return Err(anyhow!(
+                "open GOP at {keyframe:.3}: smart cut needs closed GOP source"
+            ));
+        }
+    }
+    Ok(keyframes)
+}
+
+/// Keyframes around `start..end`, relative to the file start.
+fn probe_keyframes(path: &Path, start: f64, end: f64) -> anyhow::Result<Vec<f64>> {
+    let start_time = probe_start_time(path)?;
+    // Intervals are read in stream timestamps, which include start time.
+    let interval = format!(
+        "{}%{}",
+        (start_time + start - KEYFRAME_SEARCH_MARGIN).max(0.0),
+        start_time + end + KEYFRAME_SEARCH_MARGIN
+    );
+    let lines = ffprobe_lines(
+        path,
+        &[
+            "-read_intervals",
+            &interval,
+            "-show_entries",
+            "packet=pts_time,flags",
+        ],
+        "csv=p=0",
+    )?;
+    parse_keyframes(&lines, start_time)
+}
+
+/// Checks, that `start..end` of the source can be smart cut.
+pub(super) fn check_smart_cut(path: &Path, start: f64, end: f64) -> anyhow::Result<()> {
+    probe_source_video(path)?.encode_args()?;
+    probe_keyframes(path, start, end)?;
+    Ok(())
+}
+
+/// ffmpeg arguments, writing `segment` of the source into MPEG-TS `part`.
+fn part_args(
+    source: &SourceVideo,
+    segment: &CutSegment,
+    path: &Path,
+    part: &Path,
+) -> anyhow::Result<Vec<String>> {
+    let seek = if segment.copy {
+        segment.start + KEYFRAME_SEEK_OFFSET
+    } else {
+        segment.start
+    };
+    let mut args = [
+        "-hide_banner",
+        "-nostats",
+        "-y",
+        "-ss",
+        &format!("{seek:.6}"),
+        "-i",
+        &path.to_string_lossy(),
+        "-t",
+        &format!("{:.6}", segment.end - segment.start),
+        "-map",
+        "0:v:0",
+        "-an",
+    ]
+    .map(String::from)
+    .to_vec();
+    if segment.copy {
+        let (_, bitstream_filter) = source.encoder()?;
+        args.extend(["-c:v", "copy", "-bsf:v", bitstream_filter].map(String::from));
+    } else {
+        args.extend(source.encode_args()?);
+    }
+    args.extend(["-f".to_string(), "mpegts".to_string()]);
+    args.push(part.to_string_lossy().to_string());
+    Ok(args)
+}
+
+/// Parts of the smart cut and concat list, removed when dropped.
+#[derive(Debug)]
+pub(super) struct SmartCut {
+    dir: PathBuf,
+    /// File list for ffmpeg concat demuxer.
+    pub(super) list: PathBuf,
+}
+
+impl Drop for SmartCut {
+    fn drop(&mut self) {
+        if let Err(error) = std::fs::remove_dir_all(&self.dir) {
+            warn!("can not remove {}: {error}", self.dir.display());
+        }
+    }
+}
+
+/// Cuts `duration` seconds of video from `start` frame-accurately, copying
+/// everything between the first and the last keyframe of the window.
+///
+/// Returns None, if canceled.
+pub(super) fn smart_cut(
+    path: &Path,
+    start: f64,
+    duration: f64,
+    dir: PathBuf,
+    cancel_flag: &AtomicBool,
+    log: &mut Option<File>,
+) -> anyhow::Result<Option<SmartCut>> {
+    let source = probe_source_video(path)?;
+    source.encode_args()?;
+    let end = start + duration;
+    let segments = plan_segments(&probe_keyframes(path, start, end)?, start, end);
+    debug!("ffmpeg_new: smart cut of {}: {segments:?}", path.display());
+
+    std::fs::create_dir_all(&dir)?;
+    let cut = SmartCut {
+        list: dir.join("parts.ffconcat"),
+        dir,
+    };
+    let mut list = String::from("ffconcat version 1.0\n");
+    for (idx, segment) in segments.iter().enumerate() {
+        let part = cut.dir.join(format!("part{idx}.ts"));
+        let mut command = ffmpeg_command();
+        command.args(part_args(&source, segment, path, &part)?);
+        let Some((status, output)) = run_cancelable(command, cancel_flag, log)? else {
+            return Ok(None);
+        };
+        if !status.success() {
+            return Err(anyhow!(
+                "part {idx} of smart cut failed with {status}: {}",
+                output.lines().last().unwrap_or_default()
+            ));
+        }
+        let part = part.to_string_lossy().replace('\'', "'\\''");
+        list.push_str(&format!("file '{part}'\n"));
+    }
+    std::fs::write(&cut.list, list)?;
+    Ok(Some(cut))
+}
+
+#[test]
+fn test_plan_segments() {
+    let keyframes = [0.0, 2.0, 4.0, 6.0, 8.0];
+    let segment = |start: f64, end: f64, copy: bool| CutSegment { start, end, copy };
+    assert_eq!(
+        plan_segments(&keyframes, 1.5, 7.0),
+        vec![
+            segment(1.5, 2.0, false),
+            segment(2.0, 6.0, true),
+            segment(6.0, 7.0, false)
+        ]
+    );
+    // Window, aligned to keyframes, is only copied.
+    assert_eq!(
+        plan_segments(&keyframes, 2.0, 6.0),
+        vec![segment(2.0, 6.0, true)]
+    );
+    // MPEG-TS source starts at 1.4 s: keyframes are counted from the file
+    // start, so the copied part still begins on a keyframe.
+    let lines = [
+        "1.400000,K__",
+        "2.400000,___",
+        "3.400000,K__",
+        "5.400000,K_",
+    ]
+    .map(String::from);
+    let parsed = parse_keyframes(&lines, 1.4).unwrap();
+    assert_eq!(parsed, vec![0.0, 2.0, 4.0]);
+    assert_eq!(
+        plan_segments(&parsed, 1.5, 4.5),
+        vec![
+            segment(1.5, 2.0, false),
+            segment(2.0, 4.0, true),
+            segment(4.0, 4.5, false)
+        ]
+    );
+    // B-frames after the keyframe are shown before it: open GOP.
+    let lines = [
+        "0.000000,K__",
+        "0.080000,___",
+        "0.040000,___",
+        "2.080000,K__",
+        "2.000000,___",
+    ]
+    .map(String::from);
+    assert!(parse_keyframes(&lines[..3], 0.0).is_ok());
+    assert!(parse_keyframes(&lines, 0.0).is_err());
+    // No whole GOP inside: everything is re-encoded.
+    assert_eq!(
+        plan_segments(&keyframes, 2.5, 3.5),
+        vec![segment(2.5, 3.5, false)]
+    );
+}
+
+#[test]
+fn test_part_args() {
+    let source = SourceVideo {
+        codec: "h264".to_string(),
+        profile: Some("High".to_string()),
+        level: Some(41),
+        pix_fmt: Some("yuv420p".to_string()),
+        bit_rate: Some(8_000_000),
+    };
+    let segment = |start: f64, end: f64, copy: bool| CutSegment { start, end, copy };
+    let path = Path::new("/media/source.mp4");
+    let part = Path::new("/tmp/part0.ts");
+
+    let args = part_args(&source, &segment(1.5, 2.0, false), path, part)
+        .unwrap()
+        .join(" ");
+    assert_eq!(
+        args,
+        "-hide_banner -nostats -y -ss 1.500000 -i /media/source.mp4 -t 0.500000 \
+         -map 0:v:0 -an -c:v libx264 -profile:v high -pix_fmt yuv420p -level:v 4.1 \
+         -x264-params repeat-headers=1 -b:v 8000000 -f mpegts /tmp/part0.ts"
+    );
+    let args = part_args(&source, &segment(2.0, 6.0, true), path, part)
+        .unwrap()
+        .join(" ");
+    assert!(args.contains("-ss 2.000500 "));
+    assert!(args.contains("-c:v copy -bsf:v h264_mp4toannexb -f mpegts"));
+
+    let hevc = SourceVideo {
+        codec: "hevc".to_string(),
+        profile: Some("Main 10".to_string()),
+        level: Some(123),
+        pix_fmt: Some("yuv420p10le".to_string()),
+        bit_rate: None,
+    };
+    let args = part_args(&hevc, &segment(1.5, 2.0, false), path, part)
+        .unwrap()
+        .join(" ");
+    assert!(args.contains(
+        "-c:v libx265 -profile:v main10 -pix_fmt yuv420p10le \
+         -x265-params repeat-headers=1:level-idc=4.1 -crf 16"
+    ));
+
+    let unsupported = SourceVideo {
+        profile: Some("High 4:4:4 Predictive".to_string()),
+        ..source
+    };
+    assert!(part_args(&unsupported, &segment(1.5, 2.0, false), path, part).is_err());
+}
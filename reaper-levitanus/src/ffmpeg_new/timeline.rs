@@ -0,0 +1,631 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use super::geometry::{pad_color_or_default, VideoGeometry};
+#[cfg(test)]
+use crate::ffmpeg_common::xfade::XFadeTransition;
+use crate::transitions::FadeTransition;
+
+/// Times closer than this are considered equal.
+const TIMELINE_EPSILON: f64 = 1e-6;
+
+fn same_time(a: f64, b: f64) -> bool {
+    (a - b).abs() <= TIMELINE_EPSILON
+}
+
+/// Video item, placed on render target timeline.
+///
+/// Times are in seconds relative to render bound start.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct VideoSegment {
+    pub(super) file: PathBuf,
+    pub(super) timeline_start: f64,
+    pub(super) timeline_end: f64,
+    pub(super) source_offset: f64,
+    pub(super) fade_in: f64,
+    pub(super) fade_out: f64,
+    pub(super) fade_in_transition: FadeTransition,
+    pub(super) fade_out_transition: FadeTransition,
+}
+
+/// Compositing tree of a render target.
+///
+/// Every node covers `start..end` of the timeline, children of
+/// `Concat` and `XFade` are adjacent or overlap by fade duration.
+#[derive(Debug, Clone, PartialEq)]
+struct TimelineContent {
+    kind: ContentKind,
+    start: f64,
+    end: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ContentKind {
+    Background,
+    Video {
+        file: PathBuf,
+        source_offset: f64,
+    },
+    Concat {
+        left: Box<TimelineContent>,
+        right: Box<TimelineContent>,
+    },
+    XFade {
+        left: Box<TimelineContent>,
+        right: Box<TimelineContent>,
+        duration: f64,
+        transition: FadeTransition,
+    },
+    /// Window `start..end` of the shared content, e.g. a part of a
+    /// crossfade, which is split by an upper video.
+    Trim {
+        source: Arc<TimelineContent>,
+    },
+}
+
+impl TimelineContent {
+    fn background(start: f64, end: f64) -> Self {
+        Self {
+            kind: ContentKind::Background,
+            start,
+            end,
+        }
+    }
+
+    fn video(segment: &VideoSegment) -> Self {
+        Self {
+            kind: ContentKind::Video {
+                file: segment.file.clone(),
+                source_offset: segment.source_offset,
+            },
+            start: segment.timeline_start,
+            end: segment.timeline_end,
+        }
+    }
+
+    fn concat(left: Self, right: Self) -> Self {
+        debug_assert!(same_time(left.end, right.start), "wrong connection");
+        Self {
+            start: left.start,
+            end: right.end,
+            kind: ContentKind::Concat {
+                left: Box::new(left),
+                right: Box::new(right),
+            },
+        }
+    }
+
+    fn xfade(left: Self, right: Self, duration: f64, transition: FadeTransition) -> Self {
+        debug_assert!(
+            same_time(left.end - duration, right.start),
+            "wrong fade duration: {duration}"
+        );
+        Self {
+            start: left.start,
+            end: right.end,
+            kind: ContentKind::XFade {
+                left: Box::new(left),
+                right: Box::new(right),
+                duration,
+                transition,
+            },
+        }
+    }
+
+    fn trim(source: &Arc<Self>, start: f64, end: f64) -> Self {
+        Self {
+            kind: ContentKind::Trim {
+                source: Arc::clone(source),
+            },
+            start,
+            end,
+        }
+    }
+
+    /// Places video on top of the content. Video fades become
+    /// crossfades with the content below.
+    fn push_video(&mut self, video: &VideoSegment) {
+        let has_fade_in = video.fade_in > TIMELINE_EPSILON;
+        let has_fade_out = video.fade_out > TIMELINE_EPSILON;
+        if !has_fade_in
+            && !has_fade_out
+            && same_time(video.timeline_start, self.start)
+            && same_time(video.timeline_end, self.end)
+        {
+            *self = Self::video(video);
+            return;
+        }
+
+        let solid_start = video.timeline_start + video.fade_in;
+        let solid_end = video.timeline_end - video.fade_out;
+        let self_left = (!same_time(solid_start, self.start)).then(|| self.split(solid_start).0);
+        let self_right = (!same_time(solid_end, self.end)).then(|| self.split(solid_end).1);
+
+        let left = match self_left {
+            None => Self::video(video),
+            Some(left) if has_fade_in => Self::xfade(
+                left,
+                Self::video(video),
+                video.fade_in,
+                video.fade_in_transition.clone(),
+            ),
+            Some(left) => Self::concat(left, Self::video(video)),
+        };
+        *self = match self_right {
+            None => left,
+            Some(right) if has_fade_out => Self::xfade(
+                left,
+                right,
+                video.fade_out,
+                video.fade_out_transition.clone(),
+            ),
+            Some(right) => Self::concat(left, right),
+        };
+    }
+
+    /// Splits content at `position`, which lies strictly inside of it.
+    fn split(&self, position: f64) -> (Self, Self) {
+        match &self.kind {
+            ContentKind::Background => (
+                Self::background(self.start, position),
+                Self::background(position, self.end),
+            ),
+            ContentKind::Video {
+                file,
+                source_offset,
+            } => {
+                let left = Self {
+                    kind: ContentKind::Video {
+                        file: file.clone(),
+                        source_offset: *source_offset,
+                    },
+                    start: self.start,
+                    end: position,
+                };
+                let right = Self {
+                    kind: ContentKind::Video {
+                        file: file.clone(),
+                        source_offset: source_offset + (position - self.start),
+                    },
+                    start: position,
+                    end: self.end,
+                };
+                (left, right)
+            }
+            ContentKind::Concat { left, right } => {
+                if same_time(position, left.end) {
+                    (left.as_ref().clone(), right.as_ref().clone())
+                } else if position < left.end {
+                    let (left, center) = left.split(position);
+                    (left, Self::concat(center, right.as_ref().clone()))
+                } else {
+                    let (center, right) = right.split(position);
+                    (Self::concat(left.as_ref().clone(), center), right)
+                }
+            }
+            ContentKind::XFade {
+                left,
+                right,
+                duration,
+                transition,
+            } => {
+                if position <= right.start + TIMELINE_EPSILON {
+                    let (left, center) = left.split(position);
+                    let right = right.as_ref().clone();
+                    (
+                        left,
+                        Self::xfade(center, right, *duration, transition.clone()),
+                    )
+                } else if position >= left.end - TIMELINE_EPSILON {
+                    let (center, right) = right.split(position);
+                    let left = left.as_ref().clone();
+                    (
+                        Self::xfade(left, center, *duration, transition.clone()),
+                        right,
+                    )
+                } else {
+                    // Inside of the crossfade: the whole crossfade is rendered
+                    // once, and both parts are trimmed from it.
+                    let source = Arc::new(self.clone());
+                    (
+                        Self::trim(&source, self.start, position),
+                        Self::trim(&source, position, self.end),
+                    )
+                }
+            }
+            ContentKind::Trim { source } => (
+                Self::trim(source, self.start, position),
+                Self::trim(source, position, self.end),
+            ),
+        }
+    }
+
+    fn leaves(&self) -> Vec<&Self> {
+        match &self.kind {
+            ContentKind::Background | ContentKind::Video { .. } => vec![self],
+            ContentKind::Concat { left, right } | ContentKind::XFade { left, right, .. } => {
+                let mut leaves = left.leaves();
+                leaves.extend(right.leaves());
+                leaves
+            }
+            ContentKind::Trim { source } => source.leaves(),
+        }
+    }
+
+    /// Counts nodes, which use every shared content.
+    fn count_shared(&self, uses: &mut HashMap<*const Self, usize>) {
+        match &self.kind {
+            ContentKind::Background | ContentKind::Video { .. } => (),
+            ContentKind::Concat { left, right } | ContentKind::XFade { left, right, .. } => {
+                left.count_shared(uses);
+                right.count_shared(uses);
+            }
+            ContentKind::Trim { source } => {
+                let count = uses.entry(Arc::as_ptr(source)).or_default();
+                *count += 1;
+                if *count == 1 {
+                    source.count_shared(uses);
+                }
+            }
+        }
+    }
+
+    /// Adds node inputs and filters to the graph, returns its output label.
+    fn render(&self, graph: &mut FilterGraph) -> String {
+        let VideoGeometry {
+            width,
+            height,
+            fps,
+            pixel_format,
+            ..
+        } = graph.geometry.clone();
+        let color = graph.color.clone();
+        let duration = self.end - self.start;
+        match &self.kind {
+            ContentKind::Background => {
+                let label = graph.label("bg");
+                graph.chains.push(format!(
+                    "color=c={color}:s={width}x{height}:r={fps}:d={duration:.6},\
+                     setsar=1,format={pixel_format}[{label}]"
+                ));
+                label
+            }
+            ContentKind::Video {
+                file,
+                source_offset,
+            } => {
+                let input_idx = graph.input(file, *source_offset, duration);
+                let label = graph.label("vf");
+                graph.chains.push(format!(
+                    "[{input_idx}:v:0]{},setpts=PTS-STARTPTS[{label}]",
+                    graph.geometry.normalize_filter()
+                ));
+                label
+            }
+            ContentKind::Concat { left, right } => {
+                let l_id = left.render(graph);
+                let r_id = right.render(graph);
+                let label = graph.label("conc");
+                graph.chains.push(format!(
+                    "[{l_id}][{r_id}]concat=n=2:v=1:a=0,fps={fps}[{label}]"
+                ));
+                label
+            }
+            ContentKind::XFade {
+                left,
+                right,
+                duration,
+                transition,
+            } => {
+                let l_id = left.render(graph);
+                let r_id = right.render(graph);
+                let label = graph.label("xfade");
+                // Offset is counted from the start of the left stream.
+                let offset = right.start - left.start;
+                graph.chains.push(format!(
+                    "[{l_id}][{r_id}]xfade={}:duration={duration:.6}:offset={offset:.6}[{label}]",
+                    transition.xfade_options()
+                ));
+                label
+            }
+            ContentKind::Trim { source } => {
+                let input = graph.shared_output(source);
+                let label = graph.label("trim");
+                let start = self.start - source.start;
+                let end = self.end - source.start;
+                graph.chains.push(format!(
+                    "[{input}]trim=start={start:.6}:end={end:.6},setpts=PTS-STARTPTS[{label}]"
+                ));
+                label
+            }
+        }
+    }
+}
+
+/// Accumulates inputs and filter chains while rendering content tree.
+struct FilterGraph {
+    geometry: VideoGeometry,
+    color: String,
+    inputs: Vec<String>,
+    chains: Vec<String>,
+    next_input: usize,
+    labels: usize,
+    /// Users of every shared content.
+    shared_uses: HashMap<*const TimelineContent, usize>,
+    /// Not yet taken outputs of rendered shared content.
+    shared_outputs: HashMap<*const TimelineContent, Vec<String>>,
+}
+
+impl FilterGraph {
+    fn label(&mut self, prefix: &str) -> String {
+        let label = format!("{prefix}{}", self.labels);
+        self.labels += 1;
+        label
+    }
+
+    /// Output of the shared content. It is rendered once, and split
+    /// between all its users.
+    fn shared_output(&mut self, source: &Arc<TimelineContent>) -> String {
+        let key = Arc::as_ptr(source);
+        if !self.shared_outputs.contains_key(&key) {
+            let out = source.render(self);
+            let uses = self.shared_uses.get(&key).copied().unwrap_or(1);
+            let outputs = if uses > 1 {
+                let outputs = (0..uses).map(|_| self.label("sh")).collect::<Vec<_>>();
+                self.chains.push(format!(
+                    "[{out}]split={uses}{}",
+                    outputs
+                        .iter()
+                        .map(|label| format!("[{label}]"))
+                        .collect::<String>()
+                ));
+                outputs
+            } else {
+                vec![out]
+            };
+            self.shared_outputs.insert(key, outputs);
+        }
+        self.shared_outputs
+            .get_mut(&key)
+            .and_then(Vec::pop)
+            .expect("shared content has more users than counted")
+    }
+
+    fn input(&mut self, file: &Path, offset: f64, duration: f64) -> usize {
+        self.inputs.extend([
+            "-ss".to_string(),
+            format!("{offset:.6}"),
+            "-t".to_string(),
+            format!("{duration:.6}"),
+            "-i".to_string(),
+            file.to_string_lossy().to_string(),
+        ]);
+        let idx = self.next_input;
+        self.next_input += 1;
+        idx
+    }
+}
+
+/// Video of a render target: items of all tracks, composed with
+/// crossfades. Not covered parts of the timeline are filled with pad color.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct VideoTimeline {
+    pub(super) duration: f64,
+    content: TimelineContent,
+}
+
+impl VideoTimeline {
+    pub(super) fn new(duration: f64) -> Self {
+        let duration = duration.max(0.0);
+        Self {
+            duration,
+            content: TimelineContent::background(0.0, duration),
+        }
+    }
+
+    /// Places segment over already pushed ones, so lower tracks should be
+    /// pushed first.
+    ///
+    /// Segment is clipped to the timeline, fades are shortened to fit it.
+    pub(super) fn push(&mut self, segment: VideoSegment) {
+        let start = segment.timeline_start.max(0.0);
+        let end = segment.timeline_end.min(self.duration);
+        let length = end - start;
+        if length <= TIMELINE_EPSILON {
+            return;
+        }
+        let fade_in = (segment.fade_in - (start - segment.timeline_start)).clamp(0.0, length);
+        let fade_out =
+            (segment.fade_out - (segment.timeline_end - end)).clamp(0.0, length - fade_in);
+        let significant = |fade: f64| if fade > TIMELINE_EPSILON { fade } else { 0.0 };
+        let segment = VideoSegment {
+            timeline_start: start,
+            timeline_end: end,
+            source_offset: segment.source_offset + (start - segment.timeline_start),
+            fade_in: significant(fade_in),
+            fade_out: significant(fade_out),
+            ..segment
+        };
+        self.content.push_video(&segment);
+    }
+
+    /// Pushes items of a single track.
+    ///
+    /// Overlap of two items is a crossfade with the fade-in of the incoming
+    /// item, or a cut, if it has no fade-in. Fade-out of the outgoing item
+    /// is ignored there, like in OTIO export.
+    pub(super) fn push_track(&mut self, mut segments: Vec<VideoSegment>) {
+        segments.sort_by(|a, b| a.timeline_start.total_cmp(&b.timeline_start));
+        for idx in 1..segments.len() {
+            if segments[idx].timeline_start < segments[idx - 1].timeline_end - TIMELINE_EPSILON {
+                segments[idx - 1].fade_out = 0.0;
+            }
+        }
+        for segment in segments {
+            self.push(segment);
+        }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.sources().is_empty()
+    }
+
+    pub(super) fn has_gaps(&self) -> bool {
+        self.content
+            .leaves()
+            .iter()
+            .any(|leaf| matches!(leaf.kind, ContentKind::Background))
+    }
+
+    /// File and offset, if single video covers the whole timeline.
+    pub(super) fn single_clip(&self) -> Option<(&Path, f64)> {
+        match &self.content.kind {
+            ContentKind::Video {
+                file,
+                source_offset,
+            } => Some((file.as_path(), *source_offset)),
+            _ => None,
+        }
+    }
+
+    /// Video files in timeline order, without duplicates.
+    pub(super) fn sources(&self) -> Vec<&Path> {
+        let mut sources: Vec<&Path> = Vec::new();
+        for leaf in self.content.leaves() {
+            if let ContentKind::Video { file, .. } = &leaf.kind {
+                if !sources.contains(&file.as_path()) {
+                    sources.push(file);
+                }
+            }
+        }
+        sources
+    }
+
+    /// Input arguments and `-filter_complex` graph, which renders timeline
+    /// into `[vout]`.
+    ///
+    /// Video inputs get indices from `first_input_idx`.
+    pub(super) fn build_filter_graph(
+        &self,
+        geometry: &VideoGeometry,
+        first_input_idx: usize,
+    ) -> (Vec<String>, String) {
+        let mut graph = FilterGraph {
+            geometry: geometry.clone(),
+            color: pad_color_or_default(&geometry.pad_color).to_string(),
+            inputs: Vec::new(),
+            chains: Vec::new(),
+            next_input: first_input_idx,
+            labels: 0,
+            shared_uses: HashMap::new(),
+            shared_outputs: HashMap::new(),
+        };
+        self.content.count_shared(&mut graph.shared_uses);
+        let out = self.content.render(&mut graph);
+        graph.chains.push(format!("[{out}]null[vout]"));
+        (graph.inputs, graph.chains.join(";"))
+    }
+}
+
+#[test]
+fn test_timeline_compose() {
+    let segment = |file: &str, start: f64, end: f64, fade_in: f64, fade_out: f64| VideoSegment {
+        file: PathBuf::from(file),
+        timeline_start: start,
+        timeline_end: end,
+        source_offset: 1.0,
+        fade_in,
+        fade_out,
+        fade_in_transition: FadeTransition::default(),
+        fade_out_transition: FadeTransition::Xfade(XFadeTransition::wipeleft),
+    };
+    let mut timeline = VideoTimeline::new(10.0);
+    timeline.push(segment("low", -1.0, 6.0, 2.0, 0.0));
+    assert_eq!(timeline.sources(), vec![Path::new("low")]);
+    assert!(timeline.has_gaps());
+    timeline.push(segment("top", 2.0, 5.0, 1.0, 2.0));
+    assert_eq!(timeline.sources(), vec![Path::new("low"), Path::new("top")]);
+    assert!(timeline.single_clip().is_none());
+
+    let leaves = timeline
+        .content
+        .leaves()
+        .iter()
+        .map(|leaf| (leaf.start, leaf.end))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        leaves,
+        vec![(0.0, 1.0), (0.0, 3.0), (2.0, 5.0), (3.0, 6.0), (6.0, 10.0)]
+    );
+    let (inputs, graph) = timeline.build_filter_graph(&VideoGeometry::default(), 0);
+    assert_eq!(inputs.iter().filter(|a| *a == "-i").count(), 3);
+    assert!(graph.contains("xfade=transition=fade:duration=1.000000:offset=2.000000"));
+    assert!(graph.contains("xfade=transition=wipeleft:duration=2.000000:offset=3.000000"));
+    assert!(graph.ends_with("null[vout]"));
+
+    let mut full = VideoTimeline::new(4.0);
+    full.push(segment("only", 0.0, 4.0, 0.0, 0.0));
+    assert_eq!(full.single_clip(), Some((Path::new("only"), 1.0)));
+}
+
+#[test]
+fn test_track_crossfade() {
+    let segment = |file: &str, start: f64, end: f64, fade_in: f64, fade_out: f64| VideoSegment {
+        file: PathBuf::from(file),
+        timeline_start: start,
+        timeline_end: end,
+        source_offset: 0.0,
+        fade_in,
+        fade_out,
+        fade_in_transition: FadeTransition::default(),
+        fade_out_transition: FadeTransition::Xfade(XFadeTransition::wipeleft),
+    };
+    // Insertion order does not matter, the incoming fade-in wins.
+    let mut timeline = VideoTimeline::new(6.0);
+    timeline.push_track(vec![
+        segment("right", 3.0, 6.0, 1.0, 0.0),
+        segment("left", 0.0, 4.0, 0.0, 1.0),
+    ]);
+    assert!(!timeline.has_gaps());
+    let (_, graph) = timeline.build_filter_graph(&VideoGeometry::default(), 0);
+    assert_eq!(graph.matches("xfade=").count(), 1);
+    assert!(graph.contains("xfade=transition=fade:duration=1.000000:offset=3.000000"));
+
+    // Overlap without fade-in is a cut.
+    let mut timeline = VideoTimeline::new(6.0);
+    timeline.push_track(vec![
+        segment("left", 0.0, 4.0, 0.0, 1.0),
+        segment("right", 3.0, 6.0, 0.0, 0.0),
+    ]);
+    let (_, graph) = timeline.build_filter_graph(&VideoGeometry::default(), 0);
+    assert!(!graph.contains("xfade="));
+    assert!(graph.contains("concat=n=2"));
+}
+
+#[test]
+fn test_split_inside_crossfade() {
+    let content = TimelineContent::xfade(
+        TimelineContent::background(0.0, 4.0),
+        TimelineContent::background(2.0, 6.0),
+        2.0,
+        FadeTransition::default(),
+    );
+    let (left, right) = content.split(3.0);
+    assert_eq!((left.start, left.end), (0.0, 3.0));
+    assert_eq!((right.start, right.end), (3.0, 6.0));
+    let timeline = VideoTimeline {
+        duration: 6.0,
+        content: TimelineContent::concat(left, right),
+    };
+    let (_, graph) = timeline.build_filter_graph(&VideoGeometry::default(), 0);
+    // Fade runs once over its whole window, the split point only cuts its
+    // output: progress is continuous across the split.
+    assert_eq!(graph.matches("xfade=").count(), 1);
+    assert!(graph.contains("xfade=transition=fade:duration=2.000000:offset=2.000000"));
+    assert!(graph.contains("split=2"));
+    assert!(graph.contains("trim=start=0.000000:end=3.000000"));
+    assert!(graph.contains("trim=start=3.000000:end=6.000000"));
+}
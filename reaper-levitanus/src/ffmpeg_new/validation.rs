@@ -0,0 +1,525 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use super::{
+    binaries::ffprobe_command,
+    capabilities::FfmpegCapabilities,
+    overlays::{OverlayCounter, OverlaySettings},
+    presets::EncoderPreset,
+    render::{render_duration_secs, JobVideoInput, RenderJobDefinition},
+    smart_cut::check_smart_cut,
+};
+
+/// Kind of output stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum StreamKind {
+    Video,
+    Audio,
+    Subtitle,
+}
+
+impl StreamKind {
+    fn ffprobe_selector(&self) -> &'static str {
+        match self {
+            Self::Video => "v:0",
+            Self::Audio => "a:0",
+            Self::Subtitle => "s:0",
+        }
+    }
+}
+
+impl fmt::Display for StreamKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Video => write!(f, "video"),
+            Self::Audio => write!(f, "audio"),
+            Self::Subtitle => write!(f, "subtitle"),
+        }
+    }
+}
+
+/// Why a job can not be rendered with chosen settings.
+///
+/// Nothing is changed automatically: user has to pick another preset or
+/// container.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub(super) enum ValidationError {
+    #[error("can not probe {}: {reason}", .path.display())]
+    ProbeFailed { path: PathBuf, reason: String },
+    #[error("{} has no {kind} stream", .path.display())]
+    MissingStream { path: PathBuf, kind: StreamKind },
+    #[error("this ffmpeg has no {kind} encoder {encoder}")]
+    UnknownEncoder { kind: StreamKind, encoder: String },
+    #[error(
+        "{kind} codec {codec} (from {origin}) can not be muxed into {container}, \
+         supported: {supported}"
+    )]
+    CodecNotInContainer {
+        kind: StreamKind,
+        codec: String,
+        origin: String,
+        container: String,
+        supported: String,
+    },
+    #[error("{encoder} does not support pixel format {pixel_format}, supported: {supported}")]
+    UnsupportedPixelFormat {
+        encoder: String,
+        pixel_format: String,
+        supported: String,
+    },
+    #[error("this ffmpeg can not output pixel format {0}")]
+    UnknownPixelFormat(String),
+    #[error("{0}: loudness normalization needs audio encoder, not copy")]
+    LoudnessOfCopy(String),
+    #[error("this ffmpeg has no {0} filter")]
+    MissingFilter(String),
+    #[error("{0}: scaled video needs video encoder, not copy")]
+    ScaleOfCopy(String),
+    #[error("{0}: burn-in overlay needs video encoder, not copy")]
+    OverlayOfCopy(String),
+    #[error("overlay logo {} does not exist", .0.display())]
+    MissingOverlayFile(PathBuf),
+    #[error("{} can not be smart cut: {reason}", .path.display())]
+    SmartCut { path: PathBuf, reason: String },
+}
+
+/// All problems of one render target.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("{target}:\n    {}", .errors.iter().join("\n    "))]
+pub(super) struct JobValidationError {
+    pub(super) target: String,
+    pub(super) errors: Vec<ValidationError>,
+}
+
+/// Codecs, which can be muxed into container of the output extension.
+///
+/// `None` means the container is not restricted (e.g. Matroska), or it is
+/// not known well enough to reject anything.
+fn container_codecs(extension: &str) -> Option<&'static [&'static str]> {
+    match extension {
+        "mp4" | "m4v" => Some(&[
+            "h264",
+            "hevc",
+            "av1",
+            "vp9",
+            "mpeg4",
+            "mpeg2video",
+            "mjpeg",
+            "aac",
+            "mp3",
+            "mp2",
+            "alac",
+            "flac",
+            "opus",
+            "ac3",
+            "eac3",
+            "mov_text",
+        ]),
+        "mov" => Some(&[
+            "h264",
+            "hevc",
+            "av1",
+            "vp9",
+            "mpeg4",
+            "mpeg2video",
+            "prores",
+            "dnxhd",
+            "mjpeg",
+            "png",
+            "qtrle",
+            "rawvideo",
+            "aac",
+            "mp3",
+            "alac",
+            "flac",
+            "ac3",
+            "eac3",
+            "pcm_s16le",
+            "pcm_s16be",
+            "pcm_s24le",
+            "pcm_s24be",
+            "pcm_s32le",
+            "pcm_f32le",
+            "mov_text",
+        ]),
+        "webm" => Some(&["vp8", "vp9", "av1", "opus", "vorbis", "webvtt"]),
+        _ => None,
+    }
+}
+
+/// Codec, produced by well-known encoders, when capabilities are not read.
+fn fallback_codec_of_encoder(encoder: &str) -> &str {
+    match encoder {
+        "libx264" | "libx264rgb" | "h264_nvenc" | "h264_qsv" | "h264_amf" | "h264_vaapi"
+        | "h264_videotoolbox" => "h264",
+        "libx265" | "hevc_nvenc" | "hevc_qsv" | "hevc_amf" | "hevc_vaapi" | "hevc_videotoolbox" => {
+            "hevc"
+        }
+        "prores_ks" | "prores_aw" | "prores_videotoolbox" => "prores",
+        "libvpx" => "vp8",
+        "libvpx-vp9" => "vp9",
+        "libaom-av1" | "libsvtav1" | "librav1e" => "av1",
+        "libmp3lame" => "mp3",
+        "libopus" => "opus",
+        "libvorbis" => "vorbis",
+        "libfdk_aac" | "aac_at" => "aac",
+        other => other,
+    }
+}
+
+/// Stream of source file, as reported by ffprobe.
+#[derive(Debug, Clone, PartialEq)]
+struct ProbedStream {
+    codec: String,
+}
+
+/// Runs ffprobe once per source file and stream kind.
+#[derive(Debug, Default)]
+struct SourceProbe {
+    cache: HashMap<(PathBuf, StreamKind), Result<Option<ProbedStream>, String>>,
+}
+
+impl SourceProbe {
+    fn stream(&mut self, path: &Path, kind: StreamKind) -> Result<ProbedStream, ValidationError> {
+        let result = self
+            .cache
+            .entry((path.to_path_buf(), kind))
+            .or_insert_with(|| probe_stream(path, kind))
+            .clone();
+        match result {
+            Ok(Some(stream)) => Ok(stream),
+            Ok(None) => Err(ValidationError::MissingStream {
+                path: path.to_path_buf(),
+                kind,
+            }),
+            Err(reason) => Err(ValidationError::ProbeFailed {
+                path: path.to_path_buf(),
+                reason,
+            }),
+        }
+    }
+}
+
+fn probe_stream(path: &Path, kind: StreamKind) -> Result<Option<ProbedStream>, String> {
+    let output = ffprobe_command()
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            kind.ffprobe_selector(),
+            "-show_entries",
+            "stream=codec_name",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let codec = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|line| line.trim().trim_end_matches(',').to_string())
+        .unwrap_or_default();
+    Ok((!codec.is_empty()).then_some(ProbedStream { codec }))
+}
+
+struct JobValidator<'a> {
+    capabilities: Option<&'a FfmpegCapabilities>,
+    probe: &'a mut SourceProbe,
+    container: String,
+    errors: Vec<ValidationError>,
+}
+
+impl JobValidator<'_> {
+    fn check_in_container(&mut self, kind: StreamKind, codec: &str, origin: String) {
+        let Some(supported) = container_codecs(&self.container) else {
+            return;
+        };
+        if !supported.contains(&codec) {
+            self.errors.push(ValidationError::CodecNotInContainer {
+                kind,
+                codec: codec.to_string(),
+                origin,
+                container: self.container.clone(),
+                supported: supported
+                    .iter()
+                    .filter(|c| match kind {
+                        StreamKind::Video => !is_audio_codec(c) && !is_subtitle_codec(c),
+                        StreamKind::Audio => is_audio_codec(c),
+                        StreamKind::Subtitle => is_subtitle_codec(c),
+                    })
+                    .join(", "),
+            });
+        }
+    }
+
+    fn check_loudness(&mut self, preset: &EncoderPreset) {
+        if preset.is_copy() {
+            self.errors
+                .push(ValidationError::LoudnessOfCopy(preset.name.clone()));
+        }
+        self.check_filter("loudnorm");
+    }
+
+    fn check_filter(&mut self, filter: &str) {
+        if let Some(capabilities) = self.capabilities {
+            if !capabilities.filters.iter().any(|f| f == filter) {
+                self.errors
+                    .push(ValidationError::MissingFilter(filter.to_string()));
+            }
+        }
+    }
+
+    fn check_overlay(&mut self, preset: &EncoderPreset, overlay: &OverlaySettings) {
+        if preset.is_copy() {
+            self.errors
+                .push(ValidationError::OverlayOfCopy(preset.name.clone()));
+        }
+        if let Some(logo) = overlay.logo() {
+            if !logo.is_file() {
+                self.errors
+                    .push(ValidationError::MissingOverlayFile(logo.to_path_buf()));
+            }
+            self.check_filter("overlay");
+            self.check_filter("colorchannelmixer");
+        }
+        if overlay.has_text() {
+            self.check_filter("drawtext");
+        }
+        if overlay.counter == Some(OverlayCounter::BarsBeats) {
+            self.check_filter("sendcmd");
+        }
+    }
+
+    /// Checks stream, which is copied from every source as is.
+    fn check_copy(&mut self, kind: StreamKind, sources: &[&Path]) {
+        for source in sources {
+            match self.probe.stream(source, kind) {
+                Ok(stream) => {
+                    let origin = format!("copy of {}", source.display());
+                    self.check_in_container(kind, &stream.codec, origin);
+                }
+                Err(error) => self.errors.push(error),
+            }
+        }
+    }
+
+    /// Checks, that every source has the stream to be encoded.
+    fn check_sources_have(&mut self, kind: StreamKind, sources: &[&Path]) {
+        for source in sources {
+            if let Err(error) = self.probe.stream(source, kind) {
+                self.errors.push(error);
+            }
+        }
+    }
+
+    fn check_encoder(&mut self, kind: StreamKind, preset: &EncoderPreset) {
+        let encoder = self.capabilities.and_then(|c| c.encoder(&preset.codec));
+        if self.capabilities.is_some() && encoder.is_none() {
+            self.errors.push(ValidationError::UnknownEncoder {
+                kind,
+                encoder: preset.codec.clone(),
+            });
+            return;
+        }
+        let codec = match encoder {
+            Some(encoder) => encoder.codec(),
+            None => fallback_codec_of_encoder(&preset.codec).to_string(),
+        };
+        self.check_in_container(kind, &codec, format!("preset {}", preset.name));
+
+        let Some(pixel_format) = preset
+            .pixel_format
+            .as_ref()
+            .filter(|_| kind == StreamKind::Video)
+        else {
+            return;
+        };
+        if let Some(supported) = encoder.and_then(|e| e.supported_pixel_formats.as_ref()) {
+            if !supported.contains(pixel_format) {
+                self.errors.push(ValidationError::UnsupportedPixelFormat {
+                    encoder: preset.codec.clone(),
+                    pixel_format: pixel_format.clone(),
+                    supported: supported.join(", "),
+                });
+            }
+        } else {
+            self.check_pixel_format_known(pixel_format);
+        }
+    }
+
+    fn check_pixel_format_known(&mut self, pixel_format: &str) {
+        if let Some(capabilities) = self.capabilities {
+            let known = capabilities
+                .pix_fmts
+                .iter()
+                .any(|f| f.name == pixel_format && f.output_support);
+            if !known {
+                self.errors.push(ValidationError::UnknownPixelFormat(
+                    pixel_format.to_string(),
+                ));
+            }
+        }
+    }
+
+    fn check_stream(&mut self, kind: StreamKind, preset: &EncoderPreset, sources: &[&Path]) {
+        if preset.is_copy() {
+            self.check_copy(kind, sources);
+        } else {
+            self.check_sources_have(kind, sources);
+            self.check_encoder(kind, preset);
+        }
+    }
+}
+
+fn is_audio_codec(codec: &str) -> bool {
+    codec.starts_with("pcm_")
+        || [
+            "aac", "mp3", "mp2", "alac", "flac", "opus", "vorbis", "ac3", "eac3",
+        ]
+        .contains(&codec)
+}
+
+fn is_subtitle_codec(codec: &str) -> bool {
+    ["mov_text", "webvtt", "srt", "subrip", "ass"].contains(&codec)
+}
+
+fn validate_job(
+    job: &RenderJobDefinition,
+    capabilities: Option<&FfmpegCapabilities>,
+    probe: &mut SourceProbe,
+) -> Vec<ValidationError> {
+    let container = job
+        .output_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let mut validator = JobValidator {
+        capabilities,
+        probe,
+        container,
+        errors: Vec::new(),
+    };
+
+    let video_sources = match &job.video {
+        JobVideoInput::File(path) => vec![path.as_path()],
+        JobVideoInput::Window { path, .. } => vec![path.as_path()],
+        JobVideoInput::Concat(list) => vec![list.as_path()],
+        JobVideoInput::Timeline { timeline, .. } => timeline.sources(),
+        JobVideoInput::AudioOnly => Vec::new(),
+    };
+    if !video_sources.is_empty() {
+        validator.check_stream(StreamKind::Video, &job.video_preset, &video_sources);
+    }
+    if let JobVideoInput::Window { path, offset } = &job.video {
+        if job.smart_cut && job.video_preset.is_copy() {
+            let end = offset + render_duration_secs(&job.render_target.duration);
+            if let Err(error) = check_smart_cut(path, *offset, end) {
+                validator.errors.push(ValidationError::SmartCut {
+                    path: path.clone(),
+                    reason: error.to_string(),
+                });
+            }
+        }
+    }
+    if let Some(geometry) = &job.geometry {
+        validator.check_pixel_format_known(&geometry.pixel_format);
+    }
+    if job.scale_height.is_some() && job.video_preset.is_copy() {
+        validator
+            .errors
+            .push(ValidationError::ScaleOfCopy(job.video_preset.name.clone()));
+    }
+    if let Some(overlay) = job.overlay.as_ref().filter(|_| !video_sources.is_empty()) {
+        validator.check_overlay(&job.video_preset, overlay);
+    }
+    validator.check_stream(
+        StreamKind::Audio,
+        &job.audio_preset,
+        &[job.render_target.path.as_path()],
+    );
+    if let Some(subtitles) = &job.subtitles {
+        let origin = format!("{}", subtitles.path.display());
+        validator.check_in_container(StreamKind::Subtitle, &subtitles.codec, origin);
+    }
+    if job.audio_preset.loudness.is_some() {
+        validator.check_loudness(&job.audio_preset);
+    }
+    validator.errors
+}
+
+/// Checks every job before render, sources are probed by ffprobe.
+///
+/// Checks, which need parsed capabilities (encoder existence, pixel
+/// formats), are skipped if capabilities are not read yet.
+pub(super) fn validate_jobs(
+    jobs: &[RenderJobDefinition],
+    capabilities: Option<&FfmpegCapabilities>,
+) -> Vec<JobValidationError> {
+    let mut probe = SourceProbe::default();
+    jobs.iter()
+        .filter_map(|job| {
+            let errors = validate_job(job, capabilities, &mut probe);
+            if errors.is_empty() {
+                return None;
+            }
+            let target = job
+                .output_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| job.output_path.display().to_string());
+            Some(JobValidationError { target, errors })
+        })
+        .collect()
+}
+
+#[test]
+fn test_codec_in_container() {
+    let mut probe = SourceProbe::default();
+    let mut validator = JobValidator {
+        capabilities: None,
+        probe: &mut probe,
+        container: "mp4".to_string(),
+        errors: Vec::new(),
+    };
+    let preset = |codec: &str| EncoderPreset {
+        name: codec.to_string(),
+        encoder_type: super::options::EncoderType::Video,
+        codec: codec.to_string(),
+        options: Vec::new(),
+        pixel_format: None,
+        rate_control: super::presets::RateControl::Default,
+        loudness: None,
+        overlay: None,
+    };
+    validator.check_encoder(StreamKind::Video, &preset("libx264"));
+    assert!(validator.errors.is_empty());
+    validator.check_encoder(StreamKind::Video, &preset("prores_ks"));
+    assert!(matches!(
+        &validator.errors[..],
+        [ValidationError::CodecNotInContainer { codec, container, .. }]
+            if codec == "prores" && container == "mp4"
+    ));
+    validator.container = "mkv".to_string();
+    validator.check_in_container(StreamKind::Audio, "pcm_s16le", String::new());
+    assert_eq!(validator.errors.len(), 1);
+    validator.errors.clear();
+    validator.check_overlay(
+        &EncoderPreset::copy(super::options::EncoderType::Video),
+        &OverlaySettings::default(),
+    );
+    assert!(matches!(
+        &validator.errors[..],
+        [ValidationError::OverlayOfCopy(_)]
+    ));
+}
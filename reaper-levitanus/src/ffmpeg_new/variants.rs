@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use rea_rs::gui;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    options::EncoderType, presets::EncoderPreset, with_suffix_before_extension, GuiElementsState,
+};
+
+/// Heights, offered by variant editor. Width follows the aspect ratio.
+const VARIANT_HEIGHTS: [u32; 5] = [2160, 1440, 1080, 720, 480];
+
+/// Additional output of every render target, e.g. a preview or audio-only
+/// deliverable, rendered next to the main output.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(default)]
+pub(super) struct OutputVariant {
+    /// Appended to the file name of the render target.
+    pub(super) suffix: String,
+    pub(super) muxer: String,
+    /// Name of the video preset. Empty means audio-only output.
+    pub(super) video_preset: String,
+    pub(super) audio_preset: String,
+    /// Video is scaled to this height. None keeps the source size.
+    pub(super) height: Option<u32>,
+}
+
+impl OutputVariant {
+    pub(super) fn is_audio_only(&self) -> bool {
+        self.video_preset.is_empty()
+    }
+
+    /// Checks the variant against available muxers and presets.
+    ///
+    /// Returns video preset, None for audio-only variant, and audio preset.
+    pub(super) fn resolve(
+        &self,
+        elements: &GuiElementsState,
+    ) -> anyhow::Result<(Option<EncoderPreset>, EncoderPreset)> {
+        if !elements.muxers.contains(&self.muxer) {
+            return Err(anyhow!(
+                "output variant '{}': unknown muxer '{}'",
+                self.suffix,
+                self.muxer
+            ));
+        }
+        let preset = |name: &str, encoder_type: EncoderType| {
+            elements
+                .preset(name, encoder_type)
+                .cloned()
+                .ok_or_else(|| anyhow!("output variant '{}': no preset {name}", self.suffix))
+        };
+        let video = if self.is_audio_only() {
+            None
+        } else {
+            Some(preset(&self.video_preset, EncoderType::Video)?)
+        };
+        Ok((video, preset(&self.audio_preset, EncoderType::Audio)?))
+    }
+
+    pub(super) fn output_path(&self, base: &Path) -> PathBuf {
+        with_suffix_before_extension(base, &self.suffix, Some(&self.muxer))
+    }
+
+    fn height_text(height: Option<u32>) -> String {
+        height
+            .map(|height| format!("{height}p"))
+            .unwrap_or_else(|| "source".to_string())
+    }
+}
+
+/// Output paths, written by more than one job.
+pub(super) fn duplicate_outputs<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Vec<&'a Path> {
+    let mut seen = Vec::new();
+    let mut duplicates = Vec::new();
+    for path in paths {
+        if seen.contains(&path) {
+            if !duplicates.contains(&path) {
+                duplicates.push(path);
+            }
+        } else {
+            seen.push(path);
+        }
+    }
+    duplicates
+}
+
+/// Edits list of output variants in place.
+pub(super) fn ui_output_variants(
+    variants: &mut Vec<OutputVariant>,
+    elements: &GuiElementsState,
+    ui: &mut gui::egui::Ui,
+) {
+    let mut removed = None;
+    for (idx, variant) in variants.iter_mut().enumerate() {
+        ui.push_id(("output_variant", idx), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Suffix");
+                ui.add(
+                    gui::egui::TextEdit::singleline(&mut variant.suffix)
+                        .hint_text(" 720p")
+                        .desired_width(80.0),
+                );
+                gui::egui::ComboBox::from_label("Muxer")
+                    .selected_text(variant.muxer.as_str())
+                    .show_ui(ui, |ui| {
+                        for muxer in elements.muxers.iter() {
+                            ui.selectable_value(&mut variant.muxer, muxer.clone(), muxer);
+                        }
+                    });
+                let video_text = if variant.is_audio_only() {
+                    "no video"
+                } else {
+                    variant.video_preset.as_str()
+                };
+                gui::egui::ComboBox::from_label("Video")
+                    .selected_text(video_text)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut variant.video_preset, String::new(), "no video");
+                        for preset in elements.presets_of_type(EncoderType::Video) {
+                            ui.selectable_value(
+                                &mut variant.video_preset,
+                                preset.name.clone(),
+                                &preset.name,
+                            );
+                        }
+                    });
+                ui.add_enabled_ui(!variant.is_audio_only(), |ui| {
+                    gui::egui::ComboBox::from_label("Height")
+                        .selected_text(OutputVariant::height_text(variant.height))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut variant.height, None, "source");
+                            for height in VARIANT_HEIGHTS {
+                                ui.selectable_value(
+                                    &mut variant.height,
+                                    Some(height),
+                                    OutputVariant::height_text(Some(height)),
+                                );
+                            }
+                        });
+                });
+                gui::egui::ComboBox::from_label("Audio")
+                    .selected_text(variant.audio_preset.as_str())
+                    .show_ui(ui, |ui| {
+                        for preset in elements.presets_of_type(EncoderType::Audio) {
+                            ui.selectable_value(
+                                &mut variant.audio_preset,
+                                preset.name.clone(),
+                                &preset.name,
+                            );
+                        }
+                    });
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(idx);
+                }
+            });
+        });
+    }
+    if let Some(idx) = removed {
+        variants.remove(idx);
+    }
+    if ui.button("Add variant").clicked() {
+        variants.push(OutputVariant {
+            suffix: format!(" variant {}", variants.len() + 1),
+            muxer: elements.muxers.first().cloned().unwrap_or_default(),
+            video_preset: elements
+                .presets_of_type(EncoderType::Video)
+                .next()
+                .map(|preset| preset.name.clone())
+                .unwrap_or_default(),
+            audio_preset: elements
+                .presets_of_type(EncoderType::Audio)
+                .next()
+                .map(|preset| preset.name.clone())
+                .unwrap_or_default(),
+            height: None,
+        });
+    }
+}
+
+#[test]
+fn test_variant_outputs() {
+    let preview = OutputVariant {
+        suffix: " 720p".to_string(),
+        muxer: "mp4".to_string(),
+        video_preset: "h264".to_string(),
+        audio_preset: "aac".to_string(),
+        height: Some(720),
+    };
+    let audio = OutputVariant {
+        suffix: " audio".to_string(),
+        muxer: "m4a".to_string(),
+        ..OutputVariant::default()
+    };
+    assert!(audio.is_audio_only());
+    let base = Path::new("/music/song.wav");
+    let paths = [
+        base.with_extension("mkv"),
+        preview.output_path(base),
+        audio.output_path(base),
+        preview.output_path(base),
+    ];
+    assert_eq!(paths[1], PathBuf::from("/music/song 720p.mp4"));
+    assert_eq!(paths[2], PathBuf::from("/music/song audio.m4a"));
+    assert_eq!(
+        duplicate_outputs(paths.iter().map(PathBuf::as_path)),
+        vec![paths[1].as_path()]
+    );
+}
@@ -0,0 +1,200 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::{
+    binaries::ffprobe_command,
+    render::{parse_ffmpeg_timestamp, render_duration_secs, JobVideoInput, RenderJobDefinition},
+    validation::StreamKind,
+};
+
+/// Allowed difference of durations, seconds.
+///
+/// Covers frame granularity and encoder delay of audio codecs.
+const DURATION_TOLERANCE: f64 = 0.5;
+
+/// Why ffmpeg output can not be trusted, even if ffmpeg exited with 0.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub(super) enum VerificationError {
+    #[error("can not probe output: {0}")]
+    ProbeFailed(String),
+    #[error("output has no {0} stream")]
+    MissingStream(StreamKind),
+    #[error("{kind} is {actual:.3}s long, expected {expected:.3}s")]
+    DurationMismatch {
+        kind: StreamKind,
+        actual: f64,
+        expected: f64,
+    },
+    #[error("audio is {audio:.3}s long, but video is {video:.3}s")]
+    AudioVideoMismatch { audio: f64, video: f64 },
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbedOutput {
+    #[serde(default)]
+    streams: Vec<ProbedStream>,
+    format: Option<ProbedFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbedStream {
+    codec_type: String,
+    duration: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+impl ProbedStream {
+    fn kind(&self) -> Option<StreamKind> {
+        match self.codec_type.as_str() {
+            "video" => Some(StreamKind::Video),
+            "audio" => Some(StreamKind::Audio),
+            "subtitle" => Some(StreamKind::Subtitle),
+            _ => None,
+        }
+    }
+
+    /// Matroska keeps stream duration only in `DURATION` tag.
+    fn duration(&self) -> Option<f64> {
+        self.duration
+            .as_ref()
+            .and_then(|duration| duration.parse().ok())
+            .or_else(|| {
+                self.tags
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case("duration"))
+                    .and_then(|(_, value)| parse_ffmpeg_timestamp(value.trim()))
+            })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbedFormat {
+    duration: Option<String>,
+}
+
+/// Probes rendered file and checks its streams and durations.
+pub(super) fn verify_output(job: &RenderJobDefinition) -> Result<(), VerificationError> {
+    let probed = probe_output(&job.output_path)?;
+    let expected = render_duration_secs(&job.render_target.duration);
+    let with_video = !matches!(job.video, JobVideoInput::AudioOnly);
+    check_output(&probed, expected, with_video, job.subtitles.is_some())
+}
+
+fn probe_output(path: &Path) -> Result<ProbedOutput, VerificationError> {
+    let output = ffprobe_command()
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "stream=codec_type,duration:stream_tags=DURATION:format=duration",
+            "-of",
+            "json",
+        ])
+        .arg(path)
+        .output()
+        .map_err(|e| VerificationError::ProbeFailed(e.to_string()))?;
+    if !output.status.success() {
+        return Err(VerificationError::ProbeFailed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| VerificationError::ProbeFailed(e.to_string()))
+}
+
+fn check_output(
+    probed: &ProbedOutput,
+    expected: f64,
+    with_video: bool,
+    with_subtitles: bool,
+) -> Result<(), VerificationError> {
+    let stream = |kind: StreamKind| {
+        probed
+            .streams
+            .iter()
+            .find(|stream| stream.kind() == Some(kind))
+            .ok_or(VerificationError::MissingStream(kind))
+    };
+    let container_duration = probed
+        .format
+        .as_ref()
+        .and_then(|format| format.duration.as_ref())
+        .and_then(|duration| duration.parse::<f64>().ok());
+    // Streams without own duration last as long as the container.
+    let duration = |stream: &ProbedStream| stream.duration().or(container_duration);
+
+    let video = if with_video {
+        duration(stream(StreamKind::Video)?)
+    } else {
+        None
+    };
+    let audio = duration(stream(StreamKind::Audio)?);
+    if with_subtitles {
+        stream(StreamKind::Subtitle)?;
+    }
+
+    if expected > f64::EPSILON {
+        for (kind, actual) in [(StreamKind::Video, video), (StreamKind::Audio, audio)] {
+            if let Some(actual) =
+                actual.filter(|actual| (actual - expected).abs() > DURATION_TOLERANCE)
+            {
+                return Err(VerificationError::DurationMismatch {
+                    kind,
+                    actual,
+                    expected,
+                });
+            }
+        }
+    }
+    if let (Some(audio), Some(video)) = (audio, video) {
+        if (audio - video).abs() > DURATION_TOLERANCE {
+            return Err(VerificationError::AudioVideoMismatch { audio, video });
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_check_output() {
+    let probed: ProbedOutput = serde_json::from_str(
+        r#"{
+            "streams": [
+                {"codec_type": "video", "tags": {"DURATION": "00:00:09.500000000"}},
+                {"codec_type": "audio", "duration": "6.000000"}
+            ],
+            "format": {"duration": "10.000000"}
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        check_output(&probed, 10.0, true, false),
+        Err(VerificationError::DurationMismatch {
+            kind: StreamKind::Audio,
+            actual: 6.0,
+            expected: 10.0
+        })
+    );
+    assert_eq!(
+        check_output(&probed, 0.0, true, false),
+        Err(VerificationError::AudioVideoMismatch {
+            audio: 6.0,
+            video: 9.5
+        })
+    );
+    assert_eq!(
+        check_output(&probed, 10.0, true, true),
+        Err(VerificationError::MissingStream(StreamKind::Subtitle))
+    );
+    let audio_only: ProbedOutput = serde_json::from_str(
+        r#"{"streams": [{"codec_type": "audio"}], "format": {"duration": "10.02"}}"#,
+    )
+    .unwrap();
+    assert_eq!(check_output(&audio_only, 10.0, false, false), Ok(()));
+    assert_eq!(
+        check_output(&audio_only, 10.0, true, false),
+        Err(VerificationError::MissingStream(StreamKind::Video))
+    );
+}
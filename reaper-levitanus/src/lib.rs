@@ -1,9 +1,16 @@
 use thiserror::Error;
 
+pub mod chapters;
 pub mod envelope_snap;
+pub mod ffmpeg_common;
 // pub mod ffmpeg;
 pub mod normalization;
+pub mod otio_auto_export;
+pub mod otio_conform;
 pub mod otio_export;
+pub mod subtitles;
+pub mod tempo_map;
+pub mod transitions;
 pub mod ffmpeg_new;
 
 #[derive(Debug, Error)]
@@ -0,0 +1,206 @@
+use std::{
+    cell::RefCell,
+    error::Error,
+    ffi::c_int,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+use rea_rs::{ControlSurface, ExtState, Project, Reaper, WithReaperPtr};
+
+use crate::otio_export::{
+    collect_otio_export, collect_youtube_timecodes, write_export_files, ExportMode,
+    OtioOverwritePolicy, OTIO_EXT_SECTION,
+};
+
+pub static AUTO_EXPORT_ID_STRING: &str = "LevitanusOtioAutoExport";
+const AUTO_EXPORT_OVERWRITE_KEY: &str = "auto_export_overwrite";
+/// Edits are exported only after project stays untouched for this time.
+const EDIT_SETTLE_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Watches current project and re-exports OTIO and YouTube timecodes
+/// after project is saved or edited.
+///
+/// Project data is collected on the main thread, probing and writing of
+/// files run in the background. Failed export is retried on the next save
+/// or edit: a read-only folder or missing ffprobe would fail again anyway.
+#[derive(Debug, Default)]
+struct OtioAutoExportSurface {
+    project: usize,
+    state_change_count: c_int,
+    dirty: bool,
+    changed_at: Option<Instant>,
+    /// Collected data of the last written export, unchanged data is not
+    /// exported.
+    last_export: Option<String>,
+    /// Collected data of the export, being written by `worker`.
+    pending_export: Option<String>,
+    /// Returns true, if all files are written.
+    worker: Option<JoinHandle<bool>>,
+}
+
+impl OtioAutoExportSurface {
+    /// Takes result of the finished worker.
+    fn poll_worker(&mut self) {
+        if !self.worker.as_ref().is_some_and(|w| w.is_finished()) {
+            return;
+        }
+        let written = self
+            .worker
+            .take()
+            .is_some_and(|worker| worker.join().unwrap_or(false));
+        let pending = self.pending_export.take();
+        if written {
+            self.last_export = pending;
+        }
+    }
+
+    fn export(&mut self, pr: &Project) {
+        if self.worker.is_some() {
+            // Previous export is still running: try again later.
+            self.changed_at = Some(Instant::now());
+            return;
+        }
+        let mode = ExportMode::Silent(get_overwrite_policy(pr));
+        let otio = collect_otio_export(pr, mode)
+            .map_err(|err| warn!("OTIO auto export failed: {err}"))
+            .ok();
+        let timecodes = collect_youtube_timecodes(pr, false)
+            .map_err(|err| warn!("timecodes auto export failed: {err}"))
+            .ok();
+        let collected = format!("{mode:?}\n{otio:?}\n{timecodes:?}");
+        if self.last_export.as_ref() == Some(&collected) {
+            debug!("OTIO auto export: project data is unchanged");
+            return;
+        }
+        self.pending_export = Some(collected);
+
+        self.worker = Some(thread::spawn(move || {
+            let mut written = true;
+            if let Some(otio) = otio {
+                match otio
+                    .build()
+                    .and_then(|files| write_export_files(files, mode, "OTIO"))
+                {
+                    Ok(files) => debug!("OTIO auto export: {} file(s) changed", files.len()),
+                    Err(err) => {
+                        warn!("OTIO auto export failed: {err}");
+                        written = false;
+                    }
+                }
+            }
+            if let Some(timecodes) = timecodes {
+                match write_export_files(timecodes, mode, "YouTube timecodes") {
+                    Ok(files) => debug!("timecodes auto export: {} file(s) changed", files.len()),
+                    Err(err) => {
+                        warn!("timecodes auto export failed: {err}");
+                        written = false;
+                    }
+                }
+            }
+            written
+        }));
+    }
+}
+
+impl ControlSurface for OtioAutoExportSurface {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let rpr = Reaper::get();
+        let pr = rpr.current_project();
+        let project = pr.get() as usize;
+        let state_change_count = unsafe { rpr.low().GetProjectStateChangeCount(pr.get()) };
+        let dirty = unsafe { rpr.low().IsProjectDirty(pr.get()) } != 0;
+
+        if project != self.project {
+            // Another project tab: consider it as already exported.
+            *self = Self {
+                project,
+                state_change_count,
+                dirty,
+                ..Self::default()
+            };
+            return Ok(());
+        }
+
+        self.poll_worker();
+        if state_change_count != self.state_change_count {
+            self.state_change_count = state_change_count;
+            self.changed_at = Some(Instant::now());
+        }
+        let saved = self.dirty && !dirty;
+        self.dirty = dirty;
+        let settled = self
+            .changed_at
+            .map(|at| at.elapsed() >= EDIT_SETTLE_INTERVAL)
+            .unwrap_or(false);
+
+        if saved || settled {
+            self.changed_at = None;
+            self.export(&pr);
+        }
+        Ok(())
+    }
+
+    fn get_type_string(&self) -> String {
+        AUTO_EXPORT_ID_STRING.to_string()
+    }
+
+    fn get_desc_string(&self) -> String {
+        "OTIO auto export control surface".to_string()
+    }
+}
+
+/// Installs auto export control surface, or removes it if already installed.
+pub fn toggle_otio_auto_export() -> Result<(), Box<dyn Error>> {
+    let rpr = Reaper::get_mut();
+    let id_string = AUTO_EXPORT_ID_STRING.to_string();
+
+    if rpr.has_control_surface(&id_string) {
+        rpr.unregister_control_surface(id_string)?;
+        return Ok(());
+    }
+
+    rpr.register_control_surface(Arc::new(RefCell::new(OtioAutoExportSurface::default())));
+    Ok(())
+}
+
+pub fn set_auto_export_overwrite_policy() -> Result<(), Box<dyn Error>> {
+    let rpr = Reaper::get();
+    let pr = rpr.current_project();
+    let caption = "existing files: overwrite / backup / skip";
+    let response = rpr.get_user_inputs("OTIO auto export", vec![caption], None)?;
+    let value = response
+        .get(caption)
+        .map(|v| v.trim().to_lowercase())
+        .unwrap_or_default();
+    let policy = match value.as_str() {
+        "" | "overwrite" => OtioOverwritePolicy::Overwrite,
+        "backup" => OtioOverwritePolicy::Backup,
+        "skip" => OtioOverwritePolicy::Skip,
+        other => return Err(format!("unknown overwrite policy: {other}").into()),
+    };
+    let mut state = ExtState::new(
+        OTIO_EXT_SECTION,
+        AUTO_EXPORT_OVERWRITE_KEY,
+        Some(policy),
+        true,
+        &pr,
+        None,
+    );
+    state.set(policy);
+    Ok(())
+}
+
+fn get_overwrite_policy(pr: &Project) -> OtioOverwritePolicy {
+    let state: ExtState<OtioOverwritePolicy, Project> = ExtState::new(
+        OTIO_EXT_SECTION,
+        AUTO_EXPORT_OVERWRITE_KEY,
+        Some(OtioOverwritePolicy::default()),
+        true,
+        pr,
+        None,
+    );
+    state.get().ok().flatten().unwrap_or_default()
+}
@@ -0,0 +1,630 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use lazy_static::lazy_static;
+use log::{debug, info};
+use rea_rs::{MessageBoxType, Position, Project, Reaper, UndoFlags, GUID};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::otio_export::{build_render_target_plan, media_path_from_target_url};
+
+const CONFORM_EPSILON: f64 = 1e-4;
+const DEFAULT_EDL_FPS: f64 = 25.0;
+
+lazy_static! {
+    static ref EDL_TIMECODE_RE: Regex = Regex::new(r"^\d{1,2}[:;.]\d{2}[:;.]\d{2}[:;.]\d{2}$")
+        .expect("can not compile EDL timecode regex");
+}
+lazy_static! {
+    static ref EDL_CLIP_NAME_RE: Regex =
+        Regex::new(r"^\*\s*FROM CLIP NAME:\s*(?<name>.+)$").expect("can not compile EDL clip regex");
+}
+
+/// One picture event of a cut: a source range placed on the record timeline.
+///
+/// All times are in seconds, record times are relative to the cut start.
+#[derive(Debug, Clone, PartialEq)]
+struct CutEvent {
+    name: String,
+    source: String,
+    source_start: f64,
+    source_end: f64,
+    record_start: f64,
+    record_end: f64,
+}
+
+impl CutEvent {
+    fn record_rate(&self) -> f64 {
+        let source_len = self.source_end - self.source_start;
+        if source_len <= 0.0 {
+            return 1.0;
+        }
+        (self.record_end - self.record_start) / source_len
+    }
+
+    fn record_at(&self, source_pos: f64) -> f64 {
+        self.record_start + (source_pos - self.source_start) * self.record_rate()
+    }
+}
+
+/// Part of the old cut, that survived into the new cut.
+#[derive(Debug, Clone, PartialEq)]
+struct ConformSegment {
+    old_start: f64,
+    old_end: f64,
+    new_start: f64,
+}
+
+impl ConformSegment {
+    fn contains(&self, start: f64, end: f64) -> bool {
+        self.old_start <= start + CONFORM_EPSILON && end <= self.old_end + CONFORM_EPSILON
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct ConformMap {
+    segments: Vec<ConformSegment>,
+    report: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PieceAction {
+    Move(f64),
+    Delete,
+    Keep,
+}
+
+/// Conform audio items to picture re-cut, sent back by editor as OTIO or EDL.
+///
+/// Old cut is matched against the new one by clip source ranges. Audio items
+/// are split on the cut boundaries, moved to their new place, or deleted with
+/// the picture they belonged to. Everything that can not be placed is listed
+/// in the report, written next to the new cut file.
+pub fn conform_to_picture_change() -> Result<(), Box<dyn Error>> {
+    let rpr = Reaper::get();
+    let mut pr = rpr.current_project();
+    let plans = build_render_target_plan(&pr)?;
+    let origin = plans
+        .first()
+        .map(|plan| plan.bound.start.as_duration().as_secs_f64())
+        .unwrap_or(0.0);
+    let default_old_cut = plans
+        .first()
+        .map(|plan| plan.render_target.with_extension("otio"));
+
+    let inputs = rpr.get_user_inputs(
+        "Conform to picture change",
+        vec![
+            "old cut (otio/edl; empty = export)",
+            "new cut (otio/edl)",
+            "EDL fps",
+        ],
+        None,
+    )?;
+    let old_cut = match inputs
+        .get("old cut (otio/edl; empty = export)")
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+    {
+        Some(path) => PathBuf::from(path),
+        None => default_old_cut.ok_or("no render target to take the old cut from")?,
+    };
+    let new_cut = inputs
+        .get("new cut (otio/edl)")
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .ok_or("new cut file is not specified")?;
+    let edl_fps = inputs
+        .get("EDL fps")
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|v| v.is_finite() && *v > 0.0)
+        .unwrap_or(DEFAULT_EDL_FPS);
+
+    let old_events = read_cut(&old_cut, edl_fps)?;
+    let new_events = read_cut(&new_cut, edl_fps)?;
+    debug!(
+        "conform: old cut has {} events, new cut has {} events",
+        old_events.len(),
+        new_events.len()
+    );
+    let mut map = build_conform_map(&old_events, &new_events);
+
+    pr.begin_undo_block();
+    let result = apply_conform(&mut pr, origin, &old_events, &mut map);
+    pr.end_undo_block("Conform items to picture change", UndoFlags::all());
+    rpr.update_arrange();
+    result?;
+
+    let report_path = conform_report_path(&new_cut);
+    let report = if map.report.is_empty() {
+        "Everything was placed.".to_string()
+    } else {
+        map.report.join("\n")
+    };
+    std::fs::write(&report_path, &report)?;
+    info!("conform report written: {}", report_path.display());
+    rpr.show_message_box(
+        "Conform to picture change",
+        format!(
+            "Conform finished with {} report entries.\nReport: {}",
+            map.report.len(),
+            report_path.display()
+        ),
+        MessageBoxType::Ok,
+    )?;
+    Ok(())
+}
+
+fn conform_report_path(new_cut: &Path) -> PathBuf {
+    new_cut.with_extension("conform.txt")
+}
+
+fn read_cut(path: &Path, edl_fps: f64) -> anyhow::Result<Vec<CutEvent>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("can not read cut '{}': {e}", path.display()))?;
+    let is_edl = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("edl"))
+        .unwrap_or(false);
+    let mut events = if is_edl {
+        parse_edl_cut(&content, edl_fps)?
+    } else {
+        parse_otio_cut(&serde_json::from_str(&content)?)?
+    };
+    events.sort_by(|a, b| {
+        a.record_start
+            .partial_cmp(&b.record_start)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    Ok(events)
+}
+
+fn parse_otio_cut(timeline: &Value) -> anyhow::Result<Vec<CutEvent>> {
+    let tracks = timeline
+        .get("tracks")
+        .and_then(|stack| stack.get("children"))
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("OTIO timeline has no tracks"))?;
+
+    let mut events = Vec::new();
+    for track in tracks {
+        if track.get("kind").and_then(Value::as_str) != Some("Video") {
+            continue;
+        }
+        let mut cursor = 0.0_f64;
+        for child in track
+            .get("children")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let schema = child
+                .get("OTIO_SCHEMA")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            if schema.starts_with("Transition") {
+                continue;
+            }
+            let Some((start, duration)) = child.get("source_range").and_then(otio_range_seconds)
+            else {
+                continue;
+            };
+            if !schema.starts_with("Clip") {
+                cursor += duration;
+                continue;
+            }
+
+            let record_duration = duration / otio_clip_time_scalar(child);
+            let enabled = child.get("enabled").and_then(Value::as_bool).unwrap_or(true);
+            if enabled {
+                let name = child
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string();
+                let source = otio_clip_target_url(child)
+                    .map(|url| source_key(&media_path_from_target_url(&url)))
+                    .unwrap_or_else(|| name.to_lowercase());
+                events.push(CutEvent {
+                    name,
+                    source,
+                    source_start: start,
+                    source_end: start + duration,
+                    record_start: cursor,
+                    record_end: cursor + record_duration,
+                });
+            }
+            cursor += record_duration;
+        }
+    }
+    Ok(events)
+}
+
+fn otio_range_seconds(range: &Value) -> Option<(f64, f64)> {
+    let time = |key: &str| -> Option<f64> {
+        let time = range.get(key)?;
+        let value = time.get("value")?.as_f64()?;
+        let rate = time.get("rate")?.as_f64().filter(|r| *r > 0.0)?;
+        Some(value / rate)
+    };
+    Some((time("start_time")?, time("duration")?))
+}
+
+fn otio_clip_time_scalar(clip: &Value) -> f64 {
+    clip.get("effects")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|effect| {
+            effect
+                .get("OTIO_SCHEMA")
+                .and_then(Value::as_str)
+                .map(|s| s.starts_with("LinearTimeWarp"))
+                .unwrap_or(false)
+        })
+        .filter_map(|effect| effect.get("time_scalar").and_then(Value::as_f64))
+        .filter(|scalar| scalar.is_finite() && *scalar > 0.0)
+        .product()
+}
+
+fn otio_clip_target_url(clip: &Value) -> Option<String> {
+    let reference = match clip.get("media_references").and_then(Value::as_object) {
+        Some(references) => {
+            let key = clip
+                .get("active_media_reference_key")
+                .and_then(Value::as_str)
+                .unwrap_or("DEFAULT_MEDIA");
+            references.get(key)?
+        }
+        None => clip.get("media_reference")?,
+    };
+    reference
+        .get("target_url")
+        .and_then(Value::as_str)
+        .map(String::from)
+}
+
+fn source_key(path: &Path) -> String {
+    path.file_name()
+        .and_then(|f| f.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Parse CMX3600-like EDL.
+///
+/// Record times are shifted to the hour of the first event, so the
+/// traditional `01:00:00:00` program start becomes zero.
+fn parse_edl_cut(content: &str, fps: f64) -> anyhow::Result<Vec<CutEvent>> {
+    let mut events: Vec<CutEvent> = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if let Some(cap) = EDL_CLIP_NAME_RE.captures(line) {
+            if let Some(last) = events.last_mut() {
+                let name = cap["name"].trim().to_string();
+                last.source = source_key(Path::new(&name));
+                last.name = name;
+            }
+            continue;
+        }
+
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        if tokens.len() < 8 || !tokens[0].chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let timecodes = &tokens[tokens.len() - 4..];
+        if !timecodes.iter().all(|tc| EDL_TIMECODE_RE.is_match(tc)) {
+            continue;
+        }
+        if !tokens[2].to_uppercase().contains('V') {
+            continue;
+        }
+        let reel = tokens[1].to_string();
+        events.push(CutEvent {
+            name: reel.clone(),
+            source: reel.to_lowercase(),
+            source_start: edl_timecode_seconds(timecodes[0], fps)?,
+            source_end: edl_timecode_seconds(timecodes[1], fps)?,
+            record_start: edl_timecode_seconds(timecodes[2], fps)?,
+            record_end: edl_timecode_seconds(timecodes[3], fps)?,
+        });
+    }
+
+    let first_record = events
+        .iter()
+        .map(|e| e.record_start)
+        .fold(f64::INFINITY, f64::min);
+    if first_record.is_finite() {
+        let origin = (first_record / 3600.0).floor() * 3600.0;
+        for event in events.iter_mut() {
+            event.record_start -= origin;
+            event.record_end -= origin;
+        }
+    }
+    Ok(events)
+}
+
+fn edl_timecode_seconds(timecode: &str, fps: f64) -> anyhow::Result<f64> {
+    let parts = timecode
+        .split(|c| c == ':' || c == ';' || c == '.')
+        .map(|p| p.parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("can not parse timecode '{timecode}': {e}"))?;
+    let [h, m, s, f] = parts[..] else {
+        return Err(anyhow!("timecode '{timecode}' must have 4 fields"));
+    };
+    Ok(h * 3600.0 + m * 60.0 + s + f / fps)
+}
+
+/// Match old and new events by their sources and collect old record ranges,
+/// which are present in the new cut.
+fn build_conform_map(old: &[CutEvent], new: &[CutEvent]) -> ConformMap {
+    let mut candidates = Vec::new();
+    let mut report = Vec::new();
+    for old_event in old {
+        for new_event in new.iter().filter(|e| e.source == old_event.source) {
+            let lo = old_event.source_start.max(new_event.source_start);
+            let hi = old_event.source_end.min(new_event.source_end);
+            if hi - lo <= CONFORM_EPSILON {
+                continue;
+            }
+            if (old_event.record_rate() - new_event.record_rate()).abs() > CONFORM_EPSILON {
+                report.push(format!(
+                    "speed of '{}' changed in the new cut: audio is placed by its start only",
+                    new_event.name
+                ));
+            }
+            candidates.push(ConformSegment {
+                old_start: old_event.record_at(lo),
+                old_end: old_event.record_at(hi),
+                new_start: new_event.record_at(lo),
+            });
+        }
+    }
+    candidates.sort_by(|a, b| {
+        (a.old_start, a.new_start)
+            .partial_cmp(&(b.old_start, b.new_start))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut segments: Vec<ConformSegment> = Vec::new();
+    for candidate in candidates {
+        let overlaps = segments.iter().any(|s| {
+            candidate.old_start.max(s.old_start) + CONFORM_EPSILON
+                < candidate.old_end.min(s.old_end)
+        });
+        if overlaps {
+            report.push(format!(
+                "old cut {} - {} is repeated in the new cut at {}: only first occurrence is placed",
+                format_seconds(candidate.old_start),
+                format_seconds(candidate.old_end),
+                format_seconds(candidate.new_start)
+            ));
+            continue;
+        }
+        segments.push(candidate);
+    }
+
+    for new_event in new {
+        let covered = segments
+            .iter()
+            .map(|s| {
+                let new_end = s.new_start + (s.old_end - s.old_start);
+                (new_end.min(new_event.record_end) - s.new_start.max(new_event.record_start))
+                    .max(0.0)
+            })
+            .sum::<f64>();
+        let missing = (new_event.record_end - new_event.record_start) - covered;
+        if missing > CONFORM_EPSILON {
+            report.push(format!(
+                "new cut {} - {} ('{}'): {:.3}s of picture has no material in the old cut",
+                format_seconds(new_event.record_start),
+                format_seconds(new_event.record_end),
+                new_event.name,
+                missing
+            ));
+        }
+    }
+
+    ConformMap { segments, report }
+}
+
+fn piece_action(map: &ConformMap, old_events: &[CutEvent], start: f64, end: f64) -> PieceAction {
+    if let Some(segment) = map.segments.iter().find(|s| s.contains(start, end)) {
+        return PieceAction::Move(segment.new_start + (start - segment.old_start));
+    }
+    let in_old_picture = old_events.iter().any(|e| {
+        e.record_start <= start + CONFORM_EPSILON && end <= e.record_end + CONFORM_EPSILON
+    });
+    if in_old_picture {
+        PieceAction::Delete
+    } else {
+        PieceAction::Keep
+    }
+}
+
+fn apply_conform(
+    pr: &mut Project,
+    origin: f64,
+    old_events: &[CutEvent],
+    map: &mut ConformMap,
+) -> anyhow::Result<()> {
+    let old_cut_end = old_events
+        .iter()
+        .map(|e| e.record_end)
+        .fold(0.0_f64, f64::max);
+    let mut boundaries = map
+        .segments
+        .iter()
+        .flat_map(|s| [s.old_start, s.old_end])
+        .chain(old_events.iter().flat_map(|e| [e.record_start, e.record_end]))
+        .map(|t| origin + t)
+        .collect::<Vec<_>>();
+    boundaries.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    boundaries.dedup_by(|a, b| (*a - *b).abs() < CONFORM_EPSILON);
+
+    let selected = pr
+        .iter_selected_tracks()
+        .map(|tr| tr.index())
+        .collect::<Vec<_>>();
+    let tracks = if selected.is_empty() {
+        (0..pr.n_tracks()).collect::<Vec<_>>()
+    } else {
+        selected
+    };
+
+    for track_idx in tracks {
+        split_track_items(pr, track_idx, &boundaries)?;
+
+        let mut actions: Vec<(GUID, PieceAction)> = Vec::new();
+        {
+            let track = pr
+                .get_track(track_idx)
+                .ok_or_else(|| anyhow!("can not get track with index {track_idx}"))?;
+            let track_name = track.name();
+            for item_idx in 0..track.n_items() {
+                let item = track
+                    .get_item(item_idx)
+                    .ok_or_else(|| anyhow!("can not get item {item_idx} on track {track_idx}"))?;
+                if is_video_item(&item) {
+                    continue;
+                }
+                let start = item.position().as_duration().as_secs_f64() - origin;
+                let end = item.end_position().as_duration().as_secs_f64() - origin;
+                if end <= CONFORM_EPSILON {
+                    continue;
+                }
+                if start >= old_cut_end - CONFORM_EPSILON {
+                    map.report.push(format!(
+                        "track '{}': item at {} is after the old cut end and was left in place",
+                        track_name,
+                        format_seconds(start)
+                    ));
+                    continue;
+                }
+                let action = piece_action(map, old_events, start, end);
+                match action {
+                    PieceAction::Keep => map.report.push(format!(
+                        "track '{}': item {} - {} lies outside of the old picture and was left in place",
+                        track_name,
+                        format_seconds(start),
+                        format_seconds(end)
+                    )),
+                    PieceAction::Delete => map.report.push(format!(
+                        "track '{}': item {} - {} was removed with its picture",
+                        track_name,
+                        format_seconds(start),
+                        format_seconds(end)
+                    )),
+                    PieceAction::Move(_) => (),
+                }
+                actions.push((item.guid(), action));
+            }
+        }
+
+        for (guid, action) in actions {
+            let mut track = pr
+                .get_track_mut(track_idx)
+                .ok_or_else(|| anyhow!("can not get track with index {track_idx}"))?;
+            let Some(item_idx) = (0..track.n_items()).find(|idx| {
+                track
+                    .get_item(*idx)
+                    .map(|item| item.guid() == guid)
+                    .unwrap_or(false)
+            }) else {
+                continue;
+            };
+            let mut item = track
+                .get_item(item_idx)
+                .ok_or_else(|| anyhow!("can not get item {item_idx} on track {track_idx}"))?;
+            match action {
+                PieceAction::Move(new_start) => {
+                    item.set_position(Position::from(origin + new_start));
+                }
+                PieceAction::Delete => item.delete(),
+                PieceAction::Keep => (),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn split_track_items(pr: &mut Project, track_idx: usize, boundaries: &[f64]) -> anyhow::Result<()> {
+    let mut track = pr
+        .get_track_mut(track_idx)
+        .ok_or_else(|| anyhow!("can not get track with index {track_idx}"))?;
+    // Items, created by split, are placed after the original one, so only
+    // original items are walked here.
+    for item_idx in (0..track.n_items()).rev() {
+        let mut item = track
+            .get_item(item_idx)
+            .ok_or_else(|| anyhow!("can not get item {item_idx} on track {track_idx}"))?;
+        if is_video_item(&item) {
+            continue;
+        }
+        let start = item.position().as_duration().as_secs_f64();
+        let end = item.end_position().as_duration().as_secs_f64();
+        // boundaries are sorted descending, so the left part always stays
+        // in the current item.
+        for boundary in boundaries
+            .iter()
+            .filter(|b| **b > start + CONFORM_EPSILON && **b < end - CONFORM_EPSILON)
+        {
+            item.split(Position::from(*boundary))
+                .map_err(|e| anyhow!("can not split item at {boundary}: {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+fn is_video_item<T: rea_rs::ProbablyMutable>(item: &rea_rs::Item<T>) -> bool {
+    item.active_take()
+        .source()
+        .map(|source| source.type_string() == "VIDEO")
+        .unwrap_or(false)
+}
+
+fn format_seconds(seconds: f64) -> String {
+    let safe = seconds.max(0.0);
+    let minutes = (safe / 60.0).floor();
+    format!("{:02}:{:06.3}", minutes as u64, safe - minutes * 60.0)
+}
+
+#[test]
+fn test_conform_map() {
+    let event = |source: &str, src: (f64, f64), rec: f64| CutEvent {
+        name: source.to_string(),
+        source: source.to_string(),
+        source_start: src.0,
+        source_end: src.1,
+        record_start: rec,
+        record_end: rec + src.1 - src.0,
+    };
+    let old = vec![event("a", (0.0, 10.0), 0.0), event("b", (5.0, 10.0), 10.0)];
+    let new = vec![event("b", (5.0, 10.0), 0.0), event("a", (2.0, 6.0), 5.0)];
+    let map = build_conform_map(&old, &new);
+    assert_eq!(
+        map.segments,
+        vec![
+            ConformSegment {
+                old_start: 2.0,
+                old_end: 6.0,
+                new_start: 5.0
+            },
+            ConformSegment {
+                old_start: 10.0,
+                old_end: 15.0,
+                new_start: 0.0
+            },
+        ]
+    );
+    assert!(map.report.is_empty());
+    assert_eq!(piece_action(&map, &old, 3.0, 4.0), PieceAction::Move(6.0));
+    assert_eq!(piece_action(&map, &old, 7.0, 8.0), PieceAction::Delete);
+    assert_eq!(piece_action(&map, &old, 16.0, 17.0), PieceAction::Keep);
+}
@@ -1,8 +1,8 @@
 use std::{
-	collections::{HashMap, HashSet},
+	collections::{BTreeMap, HashMap, HashSet},
 	error::Error,
+	ffi::c_int,
 	path::{Path, PathBuf},
-	process::Command,
 };
 
 use anyhow::anyhow;
@@ -10,12 +10,23 @@ use log::{info, warn};
 use rea_rs::{
 	ExtState,
 	project_info::{BoundsMode, RenderMode},
-	CommandId, MessageBoxType, MessageBoxValue, Position, Project, Reaper, SoloMode, Take,
+	CommandId, Item, MessageBoxType, MessageBoxValue, Position, ProbablyMutable, Project, Reaper,
+	SoloMode, Take, Track, WithReaperPtr, FX,
 };
 use thiserror::Error;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
+use crate::chapters::{
+	chapters_output_path, collect_chapters, render_chapters, review_youtube_chapters,
+	validate_youtube_chapters, ChapterFormat,
+};
+use crate::ffmpeg_new::binaries::ffprobe_command;
+use crate::tempo_map::{
+	beat_grid, tempo_markers, time_signature_at, BeatGridPoint, TempoMarker,
+};
+use crate::transitions::{get_item_transitions, FadeTransition, ItemTransitions};
+
 const TIMELINE_PRECISION: u32 = 1_000_000;
 const DEFAULT_OTIO_RATE: f64 = 25.0;
 const PLAY_RATE_EFFECT_EPSILON: f64 = 1e-3;
@@ -23,13 +34,15 @@ const OTIO_FRAME_VALUE_PRECISION: f64 = 1.0;
 const MIN_SERIALIZED_GAP_FRAMES: f64 = 0.5;
 const KDENLIVE_COMPAT_DISABLE_TIMEWARP: bool = false;
 const RENDER_PROJECT_USING_LAST_SETTINGS_ACTION: u32 = 41824;
-const OTIO_EXT_SECTION: &str = "levitanus_otio_export";
+pub(crate) const OTIO_EXT_SECTION: &str = "levitanus_otio_export";
 const OTIO_FPS_POLICY_KEY: &str = "fps_policy";
+const OTIO_BEAT_GRID_KEY: &str = "beat_grid";
+const VIDEO_PROCESSOR_FX_NAME: &str = "video processor";
 
 #[derive(Debug, Clone)]
-struct RenderBound {
-	start: Position,
-	end: Position,
+pub(crate) struct RenderBound {
+	pub(crate) start: Position,
+	pub(crate) end: Position,
 	rendered_tracks: Vec<(usize, String)>,
 }
 
@@ -45,6 +58,44 @@ impl Default for OtioFpsPolicy {
 	}
 }
 
+/// What to do with already existing file, when export runs without asking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OtioOverwritePolicy {
+	Overwrite,
+	/// Keep previous file as `<name>.<ext>.bak`.
+	Backup,
+	/// Never touch existing files.
+	Skip,
+}
+impl Default for OtioOverwritePolicy {
+	fn default() -> Self {
+		Self::Overwrite
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExportMode {
+	/// Asks user about overwrite and missing audio.
+	Interactive,
+	/// Writes only files which content changed, never asks.
+	Silent(OtioOverwritePolicy),
+}
+
+/// Which musical grid is exported as timeline markers.
+///
+/// `Beats(n)` puts `n` markers on every beat of time signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OtioBeatGrid {
+	Off,
+	Bars,
+	Beats(u32),
+}
+impl Default for OtioBeatGrid {
+	fn default() -> Self {
+		Self::Off
+	}
+}
+
 
 #[derive(Debug, Clone, Copy, Serialize)]
 pub struct RationalTime {
@@ -142,6 +193,33 @@ impl LinearTimeWarp {
         })
     }
 }
+#[derive(Debug, Clone, Serialize)]
+pub struct Effect {
+    #[serde(rename = "OTIO_SCHEMA")]
+    schema: &'static str,
+    pub name: String,
+    pub effect_name: String,
+    pub metadata: serde_json::Value,
+}
+
+impl Effect {
+    pub fn new(name: impl Into<String>, effect_name: impl Into<String>) -> Self {
+        Self {
+            schema: "Effect.1",
+            name: name.into(),
+            effect_name: effect_name.into(),
+            metadata: serde_json::json!({}),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ClipEffect {
+    TimeWarp(LinearTimeWarp),
+    Effect(Effect),
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Clip {
     #[serde(rename = "OTIO_SCHEMA")]
@@ -149,8 +227,11 @@ pub struct Clip {
     pub name: String,
     pub source_range: TimeRange,
     pub media_reference: ExternalReference,
+    /// Non-active takes. Folded into `media_references` by kdenlive patch.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub alternate_media_references: BTreeMap<String, ExternalReference>,
     pub metadata: serde_json::Value,
-    pub effects: Vec<LinearTimeWarp>,
+    pub effects: Vec<ClipEffect>,
     pub markers: Vec<serde_json::Value>,
     pub enabled: bool,
 }
@@ -166,6 +247,7 @@ impl Clip {
             name: name.into(),
             source_range,
             media_reference,
+            alternate_media_references: BTreeMap::new(),
             metadata: serde_json::json!({}),
             effects: vec![],
             markers: vec![],
@@ -174,7 +256,8 @@ impl Clip {
     }
 
     pub fn with_time_stretch(mut self, time_scalar: f64) -> Result<Self, OtioError> {
-        self.effects.push(LinearTimeWarp::new(time_scalar)?);
+        self.effects
+            .push(ClipEffect::TimeWarp(LinearTimeWarp::new(time_scalar)?));
         Ok(self)
     }
 }
@@ -187,22 +270,57 @@ enum TargetTrackScope {
 }
 
 #[derive(Debug, Clone)]
-struct RenderTargetPlan {
-	render_target: PathBuf,
-	bound: RenderBound,
+pub(crate) struct RenderTargetPlan {
+	pub(crate) render_target: PathBuf,
+	pub(crate) bound: RenderBound,
 	scope: TargetTrackScope,
 }
 
 #[derive(Debug, Clone)]
 struct VideoSlice {
 	file: PathBuf,
+	track_idx: usize,
 	track_name: String,
 	timeline_start: f64,
 	timeline_end: f64,
 	source_start: f64,
 	source_end: f64,
 	enabled: bool,
-	source_fps: Option<f64>,
+	item_flags: VideoItemFlags,
+	video_fx: VideoFxChain,
+	alternate_takes: Vec<AlternateTake>,
+	transitions: ItemTransitions,
+	/// Fade-in of the item, left at the slice start.
+	fade_in: f64,
+	/// Overlap with previous item on the same track, removed by trim.
+	/// Set only for the slice with fade-in, otherwise the overlap is a cut.
+	crossfade_in: Option<f64>,
+}
+
+/// Non-active video take of the item, exported as alternate media reference.
+#[derive(Debug, Clone)]
+struct AlternateTake {
+	take_idx: usize,
+	name: String,
+	file: PathBuf,
+	start_offset: f64,
+	play_rate: f64,
+}
+
+/// Video processors of a take or a track.
+///
+/// Processors with known presets become OTIO effects, all others are kept
+/// in metadata as they are.
+#[derive(Debug, Clone, Default)]
+struct VideoFxChain {
+	effects: Vec<Effect>,
+	opaque: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct VideoItemFlags {
+	ignore_video: bool,
+	ignore_audio: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -284,16 +402,111 @@ struct OtioTimeline {
 }
 
 pub fn export_otio_project() -> Result<(), Box<dyn Error>> {
-	let rpr = Reaper::get();
-	let pr = rpr.current_project();
-	let fps_policy = get_project_fps_policy(&pr);
-	let plans = build_render_target_plan(&pr)?;
-	let audio_exists = ensure_render_audio_exists(&pr, &plans)?;
+	let pr = Reaper::get().current_project();
+	export_otio_timelines(&pr, ExportMode::Interactive)?;
+	Ok(())
+}
+
+/// Project data of one OTIO timeline, collected on the main thread.
+#[derive(Debug, Clone)]
+struct OtioTimelineSource {
+	plan: RenderTargetPlan,
+	name: String,
+	video_slices: Vec<VideoSlice>,
+	/// Video processors of every video track, by track name.
+	track_fx: BTreeMap<String, VideoFxChain>,
+	markers: Vec<TimelineMarker>,
+	tempo_map: Vec<TempoChange>,
+}
+
+/// Everything OTIO export needs from the project.
+///
+/// Frame rate probing, serialization and writing need no REAPER API, so
+/// they can run on another thread.
+#[derive(Debug, Clone)]
+pub(crate) struct OtioExport {
+	fps_policy: OtioFpsPolicy,
+	/// The first render target, probed by `Project` fps policy.
+	project_render_target: Option<PathBuf>,
+	audio_exists: bool,
+	timelines: Vec<OtioTimelineSource>,
+}
+
+/// Collects project data for OTIO export of every render target.
+pub(crate) fn collect_otio_export(pr: &Project, mode: ExportMode) -> anyhow::Result<OtioExport> {
+	let fps_policy = get_project_fps_policy(pr);
+	let beat_grid = get_project_beat_grid(pr);
+	let plans = build_render_target_plan(pr)?;
+	let audio_exists = match mode {
+		ExportMode::Interactive => ensure_render_audio_exists(pr, &plans)?,
+		ExportMode::Silent(_) => plans.iter().all(|p| p.render_target.exists()),
+	};
 
+	let mut timelines = Vec::new();
 	for plan in plans {
-		let mut video_slices = collect_video_slices(&pr, &plan)?;
-		let otio_rate = detect_otio_rate(&pr, &video_slices, fps_policy)
-			.unwrap_or(DEFAULT_OTIO_RATE);
+		let video_slices = collect_video_slices(pr, &plan)?;
+		let mut track_fx = BTreeMap::new();
+		for slice in video_slices.iter() {
+			if track_fx.contains_key(&slice.track_name) {
+				continue;
+			}
+			let fx = pr
+				.get_track(slice.track_idx)
+				.map(|tr| collect_track_video_fx(&tr))
+				.unwrap_or_default();
+			track_fx.insert(slice.track_name.clone(), fx);
+		}
+		let name = format!(
+			"{} [{}]",
+			pr.name(),
+			plan.render_target
+				.file_name()
+				.and_then(|f| f.to_str())
+				.unwrap_or("render_target")
+		);
+		let mut markers = collect_otio_markers(pr, &plan.bound);
+		markers.extend(collect_beat_grid_markers(pr, &plan.bound, beat_grid));
+		timelines.push(OtioTimelineSource {
+			tempo_map: collect_tempo_map(pr, &plan.bound),
+			plan,
+			name,
+			video_slices,
+			track_fx,
+			markers,
+		});
+	}
+	Ok(OtioExport {
+		fps_policy,
+		project_render_target: first_render_target(pr),
+		audio_exists,
+		timelines,
+	})
+}
+
+impl OtioExport {
+	/// Builds OTIO files: path and json of every timeline.
+	pub(crate) fn build(&self) -> anyhow::Result<Vec<(PathBuf, String)>> {
+		let mut fps_cache = HashMap::new();
+		self.timelines
+			.iter()
+			.map(|source| self.build_timeline(source, &mut fps_cache))
+			.collect()
+	}
+
+	fn build_timeline(
+		&self,
+		source: &OtioTimelineSource,
+		fps_cache: &mut HashMap<PathBuf, Option<f64>>,
+	) -> anyhow::Result<(PathBuf, String)> {
+		let plan = &source.plan;
+		let mut video_slices = source.video_slices.clone();
+		let otio_rate = detect_otio_rate(
+			self.project_render_target.as_deref(),
+			&video_slices,
+			self.fps_policy,
+			fps_cache,
+		)
+		.unwrap_or(DEFAULT_OTIO_RATE);
 		apply_crossfade_trim(&mut video_slices);
 
 		let mut track_map: HashMap<String, Vec<VideoSlice>> = HashMap::new();
@@ -308,8 +521,8 @@ pub fn export_otio_project() -> Result<(), Box<dyn Error>> {
 		}
 
 		let mut tracks = Vec::new();
-		if audio_exists {
-			if let Some(audio_clip) = make_audio_clip(&plan, otio_rate)? {
+		if self.audio_exists {
+			if let Some(audio_clip) = make_audio_clip(plan, otio_rate)? {
 				let mut audio_track = OtioTrack::new("audio", "Audio");
 				audio_track.children.push(serde_json::to_value(audio_clip)?);
 				tracks.push(audio_track);
@@ -318,45 +531,105 @@ pub fn export_otio_project() -> Result<(), Box<dyn Error>> {
 
 		for (video_track_idx, track_name) in track_order.into_iter().enumerate() {
 			let slices = track_map.remove(&track_name).unwrap_or_default();
+			let track_fx = source.track_fx.get(&track_name).cloned().unwrap_or_default();
 			let children = build_video_track_children(slices, otio_rate)?;
 			let exported_name = normalize_video_track_name(video_track_idx, &track_name);
 			let mut track = OtioTrack::new(exported_name, "Video");
 			track.children = children;
+			for effect in track_fx.effects {
+				track.effects.push(serde_json::to_value(effect)?);
+			}
+			if !track_fx.opaque.is_empty() {
+				track.metadata = json!({ "reaper": { "video_fx": track_fx.opaque } });
+			}
 			tracks.push(track);
 		}
 
-		let timeline_name = format!(
-			"{} [{}]",
-			pr.name(),
-			plan.render_target
-				.file_name()
-				.and_then(|f| f.to_str())
-				.unwrap_or("render_target")
-		);
 		let mut stack = OtioStack::new(tracks);
-		stack.markers = collect_otio_markers(&pr, &plan.bound, otio_rate);
+		stack.markers = source
+			.markers
+			.iter()
+			.map(|marker| marker.to_otio(otio_rate))
+			.collect();
 
 		let timeline = OtioTimeline {
 			schema: "Timeline.1",
-			name: timeline_name,
-			metadata: json!({}),
+			name: source.name.clone(),
+			metadata: json!({
+				"reaper": {
+					"tempo_map": source
+						.tempo_map
+						.iter()
+						.map(|change| change.to_otio(otio_rate))
+						.collect::<Vec<_>>(),
+				}
+			}),
 			global_start_time: Some(RationalTime::new(0.0, otio_rate)),
 			tracks: stack,
 		};
 
 		let out_file = plan.render_target.with_extension("otio");
-		if !confirm_overwrite(&out_file)? {
-			info!("OTIO export skipped: {}", out_file.display());
-			continue;
-		}
 		let mut value = serde_json::to_value(&timeline)?;
 		patch_otio_for_kdenlive(&mut value);
-		let json = serde_json::to_string_pretty(&value)?;
-		std::fs::write(&out_file, json)?;
-		info!("OTIO exported: {}", out_file.display());
+		Ok((out_file, serde_json::to_string_pretty(&value)?))
 	}
+}
 
-	Ok(())
+/// Exports OTIO for every render target and returns written files.
+pub(crate) fn export_otio_timelines(pr: &Project, mode: ExportMode) -> anyhow::Result<Vec<PathBuf>> {
+	let files = collect_otio_export(pr, mode)?.build()?;
+	write_export_files(files, mode, "OTIO")
+}
+
+/// Writes built export files, `kind` names them in the log.
+///
+/// Returns written files.
+pub(crate) fn write_export_files(
+	files: Vec<(PathBuf, String)>,
+	mode: ExportMode,
+	kind: &str,
+) -> anyhow::Result<Vec<PathBuf>> {
+	let mut written = Vec::new();
+	for (path, contents) in files {
+		if !write_export_file(&path, &contents, mode)? {
+			info!("{kind} export skipped: {}", path.display());
+			continue;
+		}
+		info!("{kind} exported: {}", path.display());
+		written.push(path);
+	}
+	Ok(written)
+}
+
+/// Writes exported file, respecting export mode.
+///
+/// Returns `false` if file was left untouched.
+pub(crate) fn write_export_file(path: &Path, contents: &str, mode: ExportMode) -> anyhow::Result<bool> {
+	match mode {
+		ExportMode::Interactive => {
+			if !confirm_overwrite(path)? {
+				return Ok(false);
+			}
+		}
+		ExportMode::Silent(policy) => {
+			if path.exists() {
+				if std::fs::read_to_string(path).ok().as_deref() == Some(contents) {
+					return Ok(false);
+				}
+				match policy {
+					OtioOverwritePolicy::Overwrite => (),
+					OtioOverwritePolicy::Skip => return Ok(false),
+					OtioOverwritePolicy::Backup => {
+						let mut backup = path.as_os_str().to_owned();
+						backup.push(".bak");
+						std::fs::rename(path, PathBuf::from(backup))?;
+					}
+				}
+			}
+		}
+	}
+	std::fs::write(path, contents)?;
+	Ok(true)
 }
 
 fn confirm_overwrite(path: &Path) -> anyhow::Result<bool> {
@@ -377,31 +650,39 @@ fn confirm_overwrite(path: &Path) -> anyhow::Result<bool> {
 
 pub fn export_youtube_timecodes() -> Result<(), Box<dyn Error>> {
 	let pr = Reaper::get().current_project();
-	let plans = build_render_target_plan(&pr)?;
-	for plan in plans {
-		let start = plan.bound.start.as_duration().as_secs_f64();
-		let end = plan.bound.end.as_duration().as_secs_f64();
+	for (path, contents) in collect_youtube_timecodes(&pr, true)? {
+		std::fs::write(&path, contents)?;
+		info!("YouTube timecodes exported: {}", path.display());
+	}
+	Ok(())
+}
 
-		let mut lines = Vec::new();
-		for marker in pr.iter_markers_and_regions().filter(|m| !m.is_region) {
-			let pos = marker.position.as_duration().as_secs_f64();
-			if pos < start || pos > end {
-				continue;
+/// YouTube timecodes of every render target: path and contents.
+///
+/// With `review` YouTube rules violations are shown before writing, and
+/// user can edit or skip chapters. Otherwise violations are only logged.
+pub(crate) fn collect_youtube_timecodes(
+	pr: &Project,
+	review: bool,
+) -> anyhow::Result<Vec<(PathBuf, String)>> {
+	let plans = build_render_target_plan(pr)?;
+	let mut files = Vec::new();
+	for plan in plans {
+		let mut chapters = collect_chapters(pr, &plan.bound, false);
+		if review {
+			match review_youtube_chapters(&plan.render_target, chapters)? {
+				Some(reviewed) => chapters = reviewed,
+				None => continue,
+			}
+		} else {
+			for issue in validate_youtube_chapters(&chapters) {
+				warn!("YouTube chapters of {}: {issue}", plan.render_target.display());
 			}
-			let rel = (pos - start).max(0.0);
-			let name = if marker.name.trim().is_empty() {
-				"Marker".to_string()
-			} else {
-				marker.name
-			};
-			lines.push(format!("{} - {}", format_youtube_timecode(rel), name));
 		}
-
-		let output_path = timecodes_output_path_for_target(&plan.render_target);
-		std::fs::write(&output_path, lines.join("\n"))?;
-		info!("YouTube timecodes exported: {}", output_path.display());
+		let output_path = chapters_output_path(&plan.render_target, ChapterFormat::YouTube);
+		files.push((output_path, render_chapters(&chapters, ChapterFormat::YouTube)));
 	}
-	Ok(())
+	Ok(files)
 }
 
 pub fn set_project_fps(policy: OtioFpsPolicy) -> Result<(), Box<dyn Error>> {
@@ -418,6 +699,51 @@ pub fn set_project_fps(policy: OtioFpsPolicy) -> Result<(), Box<dyn Error>> {
 	Ok(())
 }
 
+pub fn set_project_beat_grid() -> Result<(), Box<dyn Error>> {
+	let rpr = Reaper::get();
+	let pr = rpr.current_project();
+	let response = rpr.get_user_inputs(
+		"OTIO beat markers",
+		vec!["grid: off / bar / beat / markers per beat"],
+		None,
+	)?;
+	let value = response
+		.get("grid: off / bar / beat / markers per beat")
+		.map(|v| v.trim().to_lowercase())
+		.unwrap_or_default();
+	let grid = match value.as_str() {
+		"" | "off" => OtioBeatGrid::Off,
+		"bar" | "bars" => OtioBeatGrid::Bars,
+		"beat" | "beats" => OtioBeatGrid::Beats(1),
+		other => match other.parse::<u32>() {
+			Ok(n) if n > 0 => OtioBeatGrid::Beats(n),
+			_ => return Err(format!("can not parse beat grid: {other}").into()),
+		},
+	};
+	let mut state = ExtState::new(
+		OTIO_EXT_SECTION,
+		OTIO_BEAT_GRID_KEY,
+		Some(grid),
+		true,
+		&pr,
+		None,
+	);
+	state.set(grid);
+	Ok(())
+}
+
+fn get_project_beat_grid(pr: &Project) -> OtioBeatGrid {
+	let state: ExtState<OtioBeatGrid, Project> = ExtState::new(
+		OTIO_EXT_SECTION,
+		OTIO_BEAT_GRID_KEY,
+		Some(OtioBeatGrid::default()),
+		true,
+		pr,
+		None,
+	);
+	state.get().ok().flatten().unwrap_or_default()
+}
+
 fn get_project_fps_policy(pr: &Project) -> OtioFpsPolicy {
 	let state: ExtState<OtioFpsPolicy, Project> = ExtState::new(
 		OTIO_EXT_SECTION,
@@ -430,7 +756,7 @@ fn get_project_fps_policy(pr: &Project) -> OtioFpsPolicy {
 	state.get().ok().flatten().unwrap_or_default()
 }
 
-fn build_render_target_plan(pr: &Project) -> anyhow::Result<Vec<RenderTargetPlan>> {
+pub(crate) fn build_render_target_plan(pr: &Project) -> anyhow::Result<Vec<RenderTargetPlan>> {
 	let settings = pr.get_render_settings();
 	let bounds = collect_render_bounds(pr)?;
 	let targets = pr
@@ -678,7 +1004,6 @@ fn collect_video_slices(pr: &Project, plan: &RenderTargetPlan) -> anyhow::Result
 	let bound_end = plan.bound.end.with_precision(TIMELINE_PRECISION);
 
 	let mut slices = Vec::new();
-	let mut fps_cache: HashMap<PathBuf, Option<f64>> = HashMap::new();
 	for track_idx in tracks {
 		let track = pr
 			.get_track(track_idx)
@@ -725,15 +1050,12 @@ fn collect_video_slices(pr: &Project, plan: &RenderTargetPlan) -> anyhow::Result
 			}
 
 			let stretch_points = build_stretch_points(&take, item_len);
+			let flags = get_item_video_flags(&item);
+			let video_fx = collect_take_video_fx(&take);
+			let alternate_takes = collect_alternate_takes(&item, &take);
+			let transitions = get_item_transitions(&item);
 			let file = source.filename();
-			let source_fps = match fps_cache.get(&file) {
-				Some(v) => *v,
-				None => {
-					let v = probe_video_fps(&file);
-					fps_cache.insert(file.clone(), v);
-					v
-				}
-			};
+			let mut fade_in = (item.fade_in().length.as_secs_f64() - local_start).max(0.0);
 
 			for segment in segment_item_by_stretch(
 				&stretch_points,
@@ -747,13 +1069,19 @@ fn collect_video_slices(pr: &Project, plan: &RenderTargetPlan) -> anyhow::Result
 				}
 				slices.push(VideoSlice {
 					file: file.clone(),
+					track_idx,
 					track_name: track_name.clone(),
 					timeline_start: segment.timeline_start,
 					timeline_end: segment.timeline_end,
 					source_start: segment.source_start,
 					source_end: segment.source_end,
-					enabled: true,
-					source_fps,
+					enabled: !flags.ignore_video,
+					item_flags: flags,
+					video_fx: video_fx.clone(),
+					alternate_takes: alternate_takes.clone(),
+					transitions: transitions.clone(),
+					fade_in: std::mem::take(&mut fade_in),
+					crossfade_in: None,
 				});
 			}
 		}
@@ -762,6 +1090,132 @@ fn collect_video_slices(pr: &Project, plan: &RenderTargetPlan) -> anyhow::Result
 	Ok(slices)
 }
 
+fn collect_alternate_takes<T: ProbablyMutable>(
+	item: &Item<T>,
+	active: &Take<rea_rs::Immutable>,
+) -> Vec<AlternateTake> {
+	let mut takes = Vec::new();
+	for take_idx in 0..item.n_takes() {
+		let Some(take) = item.get_take(take_idx) else {
+			continue;
+		};
+		if take.get() == active.get() {
+			continue;
+		}
+		let Some(source) = take.source() else {
+			continue;
+		};
+		if source.type_string() != "VIDEO" {
+			continue;
+		}
+		takes.push(AlternateTake {
+			take_idx,
+			name: take.name(),
+			file: source.filename(),
+			start_offset: take.start_offset().as_secs_f64(),
+			play_rate: take.play_rate().into(),
+		});
+	}
+	takes
+}
+
+fn collect_take_video_fx(take: &Take<rea_rs::Immutable>) -> VideoFxChain {
+	let mut chain = VideoFxChain::default();
+	for fx_idx in 0..take.n_fx() {
+		if let Some(fx) = take.get_fx(fx_idx) {
+			push_video_fx(&mut chain, &fx);
+		}
+	}
+	chain
+}
+
+fn collect_track_video_fx<T: ProbablyMutable>(track: &Track<T>) -> VideoFxChain {
+	let mut chain = VideoFxChain::default();
+	for fx_idx in 0..track.n_fx() {
+		if let Some(fx) = track.get_fx(fx_idx) {
+			push_video_fx(&mut chain, &fx);
+		}
+	}
+	chain
+}
+
+fn push_video_fx<T: ProbablyMutable>(chain: &mut VideoFxChain, fx: &impl FX<T>) {
+	let fx_name = fx.name();
+	if !fx_name.to_lowercase().contains(VIDEO_PROCESSOR_FX_NAME) {
+		return;
+	}
+	let preset = fx.get_preset().ok().unwrap_or_default();
+	let parameters = (0..fx.n_params())
+		.filter_map(|idx| fx.get_param(idx))
+		.map(|param| (param.name(), json!(param.get())))
+		.collect::<serde_json::Map<_, _>>();
+	let reaper = json!({
+		"fx_name": fx_name,
+		"preset": preset,
+		"enabled": fx.is_enabled(),
+		"parameters": parameters,
+	});
+
+	match video_processor_effect_name(&preset) {
+		Some(effect_name) => {
+			let mut effect = Effect::new(preset.clone(), effect_name);
+			effect.metadata = json!({ "reaper": reaper });
+			chain.effects.push(effect);
+		}
+		None => chain.opaque.push(reaper),
+	}
+}
+
+/// Map preset of REAPER Video processor to generic effect name.
+///
+/// Whole words of the preset name are matched, so "panorama" is not a pan.
+fn video_processor_effect_name(preset: &str) -> Option<&'static str> {
+	let preset = preset.to_lowercase();
+	let words = preset
+		.split(|c: char| !c.is_alphanumeric())
+		.filter(|word| !word.is_empty())
+		.collect::<Vec<_>>();
+	let has = |names: &[&str]| words.iter().any(|word| names.contains(word));
+	if has(&["crop"]) {
+		Some("Crop")
+	} else if has(&["opacity", "zoom", "pan", "position"]) {
+		Some("Transform")
+	} else if has(&["fade"]) {
+		Some("Fade")
+	} else if has(&["text", "title"]) {
+		Some("TextOverlay")
+	} else if has(&["chroma", "chromakey"]) {
+		Some("ChromaKey")
+	} else if has(&["color", "colour"]) {
+		Some("ColorCorrection")
+	} else {
+		None
+	}
+}
+
+/// Read "ignore video" and "ignore audio" switches of video source,
+/// which are stored only in the item state chunk.
+fn get_item_video_flags<T: ProbablyMutable>(item: &Item<T>) -> VideoItemFlags {
+	let mut flags = VideoItemFlags::default();
+	let mut in_video_source = false;
+	for line in item.state_chunk().lines().map(str::trim) {
+		if line.starts_with("<SOURCE VIDEO") {
+			in_video_source = true;
+			continue;
+		}
+		if !in_video_source {
+			continue;
+		}
+		match line {
+			">" => in_video_source = false,
+			"VIDEO 0" => flags.ignore_video = true,
+			"AUDIO 0" => flags.ignore_audio = true,
+			_ => (),
+		}
+	}
+	flags
+}
+
 fn normalize_video_track_name(export_index: usize, original_name: &str) -> String {
 	let suffix = original_name
 		.trim_start_matches(|c: char| c.is_ascii_digit() || c.is_whitespace())
@@ -891,6 +1345,11 @@ fn apply_crossfade_trim(slices: &mut Vec<VideoSlice>) {
 			let right_idx = pair[1];
 			let left_end = slices[left_idx].timeline_end;
 			if slices[right_idx].timeline_start < left_end {
+				// Like in ffmpeg render, the incoming fade-in makes a crossfade.
+				if slices[right_idx].fade_in > 1e-6 {
+					let overlap = left_end - slices[right_idx].timeline_start;
+					slices[right_idx].crossfade_in = Some(overlap);
+				}
 				trim_slice_start(&mut slices[right_idx], left_end);
 			}
 		}
@@ -964,9 +1423,43 @@ fn slice_to_otio_clip(slice: &VideoSlice, otio_rate: f64) -> anyhow::Result<Clip
 		slice.source_start
 	);
 	let mut clip = Clip::new(unique_name, media, source_range);
+	for alt in slice.alternate_takes.iter() {
+		let mut reference = ExternalReference::new(path_to_target_url(&alt.file));
+		reference.metadata = json!({
+			"reaper": {
+				"take_index": alt.take_idx,
+				"take_name": alt.name,
+				"start_offset": alt.start_offset,
+				"play_rate": alt.play_rate,
+			}
+		});
+		clip.alternate_media_references
+			.insert(format!("TAKE_{}", alt.take_idx + 1), reference);
+	}
 	if use_timewarp {
 		clip = clip.with_time_stretch(time_scalar.abs())?;
 	}
+	clip.effects.extend(
+		slice
+			.video_fx
+			.effects
+			.iter()
+			.cloned()
+			.map(ClipEffect::Effect),
+	);
+	let mut reaper_metadata = serde_json::Map::new();
+	if !slice.video_fx.opaque.is_empty() {
+		reaper_metadata.insert("video_fx".to_string(), json!(slice.video_fx.opaque));
+	}
+	if slice.item_flags.ignore_video {
+		reaper_metadata.insert("ignore_video".to_string(), Value::Bool(true));
+	}
+	if slice.item_flags.ignore_audio {
+		reaper_metadata.insert("ignore_audio".to_string(), Value::Bool(true));
+	}
+	if !reaper_metadata.is_empty() {
+		clip.metadata = json!({ "reaper": reaper_metadata });
+	}
 	clip.enabled = slice.enabled;
 	Ok(clip)
 }
@@ -1025,6 +1518,8 @@ fn build_video_track_children(
 		let gap_len = slice.timeline_start - cursor;
 		if gap_len > 1e-6 {
 			children.push(make_gap(gap_len, otio_rate)?);
+		} else if let (Some(overlap), false) = (slice.crossfade_in, children.is_empty()) {
+			children.push(make_transition(overlap, &slice.transitions.fade_in, otio_rate));
 		}
 
 		let emitted_duration = serialized_timeline_duration_seconds(&slice, otio_rate);
@@ -1045,6 +1540,22 @@ fn serialized_timeline_duration_seconds(slice: &VideoSlice, otio_rate: f64) -> f
 	};
 	snap_seconds_to_timeline_frames(timeline_duration, otio_rate).max(min_timeline_duration)
 }
+/// Crossfade, which was trimmed to a cut: the whole transition lies before it.
+fn make_transition(duration_secs: f64, transition: &FadeTransition, rate: f64) -> serde_json::Value {
+	let xfade = match transition {
+		FadeTransition::Xfade(name) => json!({ "transition": name }),
+		FadeTransition::Custom(expr) => json!({ "transition": "custom", "expr": expr }),
+	};
+	json!({
+		"OTIO_SCHEMA": "Transition.1",
+		"name": transition.to_string(),
+		"transition_type": transition.otio_transition_type(),
+		"in_offset": otio_time_from_seconds(duration_secs, rate),
+		"out_offset": otio_time_from_seconds(0.0, rate),
+		"metadata": { "reaper": { "xfade": xfade } }
+	})
+}
+
 fn make_gap(duration_secs: f64, rate: f64) -> anyhow::Result<serde_json::Value> {
 	let source_range = TimeRange::new(
 		otio_time_from_seconds(0.0, rate),
@@ -1078,37 +1589,32 @@ fn snap_seconds_to_timeline_frames(seconds: f64, rate: f64) -> f64 {
 	(seconds * rate).round() / rate
 }
 
+/// Frame rate of the timeline. Every file is probed once per `fps_cache`.
 fn detect_otio_rate(
-	pr: &Project,
+	project_render_target: Option<&Path>,
 	slices: &[VideoSlice],
 	policy: OtioFpsPolicy,
+	fps_cache: &mut HashMap<PathBuf, Option<f64>>,
 ) -> Option<f64> {
-	let files = slices
-		.iter()
-		.map(|s| s.file.clone())
-		.collect::<HashSet<_>>()
+	let mut files: Vec<&Path> = Vec::new();
+	for slice in slices {
+		if !files.contains(&slice.file.as_path()) {
+			files.push(&slice.file);
+		}
+	}
+	let mut fps = files
 		.into_iter()
-		.collect::<Vec<_>>();
+		.filter_map(|f| {
+			*fps_cache
+				.entry(f.to_path_buf())
+				.or_insert_with(|| probe_video_fps(f))
+		})
+		.filter(|v| v.is_finite() && *v > 0.0);
 
 	match policy {
-		OtioFpsPolicy::FirstVideo => slices
-			.iter()
-			.filter_map(|s| s.source_fps)
-			.find(|v| v.is_finite() && *v > 0.0)
-			.or_else(|| files.into_iter().find_map(|f| probe_video_fps(&f))),
+		OtioFpsPolicy::FirstVideo => fps.next(),
 		OtioFpsPolicy::MedianVideo => {
-			let mut fps = files
-				.into_iter()
-				.filter_map(|f| probe_video_fps(&f))
-				.filter(|v| v.is_finite() && *v > 0.0)
-				.collect::<Vec<_>>();
-			if fps.is_empty() {
-				fps = slices
-					.iter()
-					.filter_map(|s| s.source_fps)
-					.filter(|v| v.is_finite() && *v > 0.0)
-					.collect::<Vec<_>>();
-			}
+			let mut fps = fps.collect::<Vec<_>>();
 			if fps.is_empty() {
 				None
 			} else {
@@ -1116,29 +1622,32 @@ fn detect_otio_rate(
 				Some(fps[fps.len() / 2])
 			}
 		}
-		OtioFpsPolicy::Project => detect_project_rate(pr),
+		OtioFpsPolicy::Project => detect_project_rate(project_render_target),
 	}
 }
 
-fn detect_project_rate(pr: &Project) -> Option<f64> {
-	let render_target = pr
-		.get_render_targets()
+fn first_render_target(pr: &Project) -> Option<PathBuf> {
+	pr.get_render_targets()
 		.ok()?
 		.into_iter()
-		.find(|p| !p.trim().is_empty())?;
-	let target = PathBuf::from(render_target);
+		.find(|p| !p.trim().is_empty())
+		.map(PathBuf::from)
+}
+
+fn detect_project_rate(render_target: Option<&Path>) -> Option<f64> {
+	let target = render_target?;
 	if !target.exists() {
 		warn!(
 			"OTIO FPS policy Project selected, but render target file does not exist; using default"
 		);
 		return None;
 	}
-	probe_video_fps(&target)
+	probe_video_fps(target)
 }
 
 fn probe_video_fps(file: &Path) -> Option<f64> {
 	let path = file.to_str()?;
-	let output = Command::new("ffprobe")
+	let output = ffprobe_command()
 		.args([
 			"-v",
 			"error",
@@ -1210,9 +1719,13 @@ fn patch_otio_for_kdenlive_inner(
 				.unwrap_or(false);
 			if is_clip2 {
 				map.entry("color".to_string()).or_insert(Value::Null);
+				let alternates = map.remove("alternate_media_references");
 				if let Some(media_ref) = map.remove("media_reference") {
 					let mut refs = serde_json::Map::new();
 					refs.insert("DEFAULT_MEDIA".to_string(), media_ref);
+					if let Some(Value::Object(alternates)) = alternates {
+						refs.extend(alternates);
+					}
 					map.insert("media_references".to_string(), Value::Object(refs));
 					map.insert(
 						"active_media_reference_key".to_string(),
@@ -1245,29 +1758,26 @@ fn patch_otio_for_kdenlive_inner(
 
 				if let Some(media_refs) = map.get_mut("media_references").and_then(Value::as_object_mut)
 				{
-					if let Some(default_media) = media_refs
-						.get_mut("DEFAULT_MEDIA")
-						.and_then(Value::as_object_mut)
-					{
-						let is_external_ref = default_media
+					for media in media_refs.values_mut().filter_map(Value::as_object_mut) {
+						let is_external_ref = media
 							.get("OTIO_SCHEMA")
 							.and_then(Value::as_str)
 							.map(|s| s == "ExternalReference.1")
 							.unwrap_or(false);
-						let has_available_range = default_media
+						let has_available_range = media
 							.get("available_range")
 							.map(|v| !v.is_null())
 							.unwrap_or(false);
 
 						if is_external_ref && !has_available_range {
-							if let Some(target_url) = default_media
+							if let Some(target_url) = media
 								.get("target_url")
 								.and_then(Value::as_str)
 							{
 								if let Some(duration_secs) =
 									probe_media_duration_cached(target_url, duration_cache)
 								{
-									default_media.insert(
+									media.insert(
 										"available_range".to_string(),
 										json!({
 											"OTIO_SCHEMA": "TimeRange.1",
@@ -1397,6 +1907,7 @@ fn library_key_order_for_schema(schema: &str) -> Option<&'static [&'static str]>
 			"enabled",
 			"color",
 		]),
+		"Effect.1" => Some(&["OTIO_SCHEMA", "metadata", "name", "effect_name"]),
 		"ExternalReference.1" => Some(&[
 			"OTIO_SCHEMA",
 			"metadata",
@@ -1423,7 +1934,7 @@ fn probe_media_duration_cached(
 	duration
 }
 
-fn media_path_from_target_url(target_url: &str) -> PathBuf {
+pub(crate) fn media_path_from_target_url(target_url: &str) -> PathBuf {
 	if target_url.starts_with("file://") {
 		if let Ok(url) = url::Url::parse(target_url) {
 			if let Ok(path) = url.to_file_path() {
@@ -1436,7 +1947,7 @@ fn media_path_from_target_url(target_url: &str) -> PathBuf {
 
 fn probe_media_duration(path: &Path) -> Option<f64> {
 	let src = path.to_str()?;
-	let output = Command::new("ffprobe")
+	let output = ffprobe_command()
 		.args([
 			"-v",
 			"error",
@@ -1460,10 +1971,38 @@ fn probe_media_duration(path: &Path) -> Option<f64> {
 	}
 }
 
-fn collect_otio_markers(pr: &Project, bound: &RenderBound, rate: f64) -> Vec<serde_json::Value> {
+/// Timeline marker, collected before OTIO rate is known.
+#[derive(Debug, Clone)]
+struct TimelineMarker {
+	/// Seconds from render bound start.
+	time: f64,
+	name: String,
+	color: &'static str,
+	/// Length in frames of OTIO rate.
+	frames: f64,
+	metadata: Value,
+}
+
+impl TimelineMarker {
+	fn to_otio(&self, rate: f64) -> Value {
+		let frames = if rate > 0.0 { self.frames } else { 0.0 };
+		json!({
+			"OTIO_SCHEMA": "Marker.2",
+			"name": self.name,
+			"color": self.color,
+			"marked_range": {
+				"OTIO_SCHEMA": "TimeRange.1",
+				"start_time": RationalTime::new(quantize_otio_frame_value(self.time * rate), rate),
+				"duration": RationalTime::new(quantize_otio_frame_value(frames), rate),
+			},
+			"metadata": self.metadata,
+		})
+	}
+}
+
+fn collect_otio_markers(pr: &Project, bound: &RenderBound) -> Vec<TimelineMarker> {
 	let start = bound.start.with_precision(TIMELINE_PRECISION);
 	let end = bound.end.with_precision(TIMELINE_PRECISION);
-	let frame = if rate > 0.0 { 1.0 } else { 0.0 };
 
 	pr.iter_markers_and_regions()
 		.filter(|m| !m.is_region)
@@ -1472,34 +2011,145 @@ fn collect_otio_markers(pr: &Project, bound: &RenderBound, rate: f64) -> Vec<ser
 			pos >= start && pos <= end
 		})
 		.map(|marker| {
-			let rel = quantize_otio_frame_value(
-				(marker.position - start).as_duration().as_secs_f64() * rate,
-			);
 			let (r, g, b) = marker.color.get();
-			json!({
-				"OTIO_SCHEMA": "Marker.2",
-				"name": marker.name,
-				"color": otio_marker_color(r, g, b),
-				"marked_range": {
-					"OTIO_SCHEMA": "TimeRange.1",
-					"start_time": {
-						"OTIO_SCHEMA": "RationalTime.1",
-						"value": rel,
-						"rate": rate,
-					},
-					"duration": {
-						"OTIO_SCHEMA": "RationalTime.1",
-						"value": quantize_otio_frame_value(frame),
-						"rate": rate,
-					}
-				},
-				"metadata": {
+			TimelineMarker {
+				time: (marker.position - start).as_duration().as_secs_f64(),
+				name: marker.name.clone(),
+				color: otio_marker_color(r, g, b),
+				frames: 1.0,
+				metadata: json!({
 					"reaper": {
 						"color_rgb": [r, g, b],
 						"color_hex": format!("#{r:02X}{g:02X}{b:02X}")
 					}
-				}
-			})
+				}),
+			}
+		})
+		.collect()
+}
+
+/// Tempo and time signature change, collected before OTIO rate is known.
+#[derive(Debug, Clone)]
+struct TempoChange {
+	/// Seconds from render bound start.
+	time: f64,
+	bpm: f64,
+	num: c_int,
+	denom: c_int,
+	linear: bool,
+}
+
+impl TempoChange {
+	fn to_otio(&self, rate: f64) -> Value {
+		json!({
+			"time": otio_time_from_seconds(self.time, rate),
+			"seconds": self.time,
+			"bpm": self.bpm,
+			"time_signature": [self.num, self.denom],
+			"linear": self.linear,
+		})
+	}
+}
+
+/// Tempo and time signature changes inside render bound.
+fn collect_tempo_map(pr: &Project, bound: &RenderBound) -> Vec<TempoChange> {
+	let start = bound.start.as_duration().as_secs_f64();
+	let end = bound.end.as_duration().as_secs_f64();
+	tempo_changes(&tempo_markers(pr), start, end, |time| time_signature_at(pr, time))
+}
+
+/// Tempo changes of `markers` inside `start..=end`.
+///
+/// The first entry is always the tempo at `start`: the marker at `start`
+/// itself, or a point inside the segment of the previous marker.
+fn tempo_changes(
+	markers: &[TempoMarker],
+	start: f64,
+	end: f64,
+	time_signature_at: impl Fn(f64) -> (c_int, c_int, f64),
+) -> Vec<TempoChange> {
+	let epsilon = 1.0 / TIMELINE_PRECISION as f64;
+	let (num, denom, bpm) = time_signature_at(start);
+	let mut entries = vec![TempoChange {
+		time: 0.0,
+		bpm,
+		num,
+		denom,
+		linear: false,
+	}];
+	for marker in markers {
+		if marker.time < start - epsilon {
+			// Start is inside the ramp of this marker.
+			entries[0].linear = marker.linear;
+			continue;
+		}
+		if marker.time > end {
+			break;
+		}
+		// marker without time signature change keeps the previous one.
+		let (num, denom) = marker.time_signature.unwrap_or_else(|| {
+			let (num, denom, _) = time_signature_at(marker.time);
+			(num, denom)
+		});
+		let change = TempoChange {
+			time: (marker.time - start).max(0.0),
+			bpm: marker.bpm,
+			num,
+			denom,
+			linear: marker.linear,
+		};
+		if marker.time <= start + epsilon {
+			entries[0] = change;
+		} else {
+			entries.push(change);
+		}
+	}
+	entries
+}
+
+fn collect_beat_grid_markers(
+	pr: &Project,
+	bound: &RenderBound,
+	grid: OtioBeatGrid,
+) -> Vec<TimelineMarker> {
+	let per_beat = match grid {
+		OtioBeatGrid::Off => return Vec::new(),
+		OtioBeatGrid::Bars => None,
+		OtioBeatGrid::Beats(n) => Some(n.max(1)),
+	};
+	let start = bound.start.as_duration().as_secs_f64();
+	let end = bound.end.as_duration().as_secs_f64();
+	let epsilon = 1.0 / TIMELINE_PRECISION as f64;
+
+	beat_grid(pr, start, end + epsilon, per_beat)
+		.into_iter()
+		.filter(|point| point.time >= start - epsilon)
+		.map(|point| {
+			let BeatGridPoint {
+				measure,
+				step,
+				beats,
+				time,
+			} = point;
+			let name = match per_beat {
+				None => format!("{}", measure + 1),
+				Some(1) => format!("{}.{}", measure + 1, step + 1),
+				Some(n) => format!("{}.{}.{}", measure + 1, step / n + 1, step % n + 1),
+			};
+			TimelineMarker {
+				time: (time - start).max(0.0),
+				name,
+				color: if step == 0 { "PURPLE" } else { "CYAN" },
+				frames: 0.0,
+				metadata: json!({
+					"reaper": {
+						"beat_grid": {
+							"measure": measure + 1,
+							"beat": beats + 1.0,
+						}
+					}
+				}),
+			}
 		})
 		.collect()
 }
@@ -1534,22 +2184,6 @@ fn otio_marker_color(r: u8, g: u8, b: u8) -> &'static str {
 	}
 }
 
-fn timecodes_output_path_for_target(render_target: &Path) -> PathBuf {
-	render_target.with_extension("txt")
-}
-
-fn format_youtube_timecode(seconds: f64) -> String {
-	let total = seconds.floor().max(0.0) as u64;
-	let h = total / 3600;
-	let m = (total % 3600) / 60;
-	let s = total % 60;
-	if h > 0 {
-		format!("{h:02}:{m:02}:{s:02}")
-	} else {
-		format!("{m:02}:{s:02}")
-	}
-}
-
 fn path_to_target_url(path: &Path) -> String {
 	let resolved = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
 	resolved.to_string_lossy().replace('\\', "/")
@@ -1561,3 +2195,78 @@ fn clip_name(path: &Path) -> String {
 		.map(String::from)
 		.unwrap_or_else(|| "clip".to_string())
 }
+
+#[test]
+fn test_video_processor_effect_name() {
+	assert_eq!(video_processor_effect_name("Zoom/pan"), Some("Transform"));
+	assert_eq!(video_processor_effect_name("Pan & Zoom"), Some("Transform"));
+	assert_eq!(video_processor_effect_name("Crop: top"), Some("Crop"));
+	assert_eq!(video_processor_effect_name("Title text overlay"), Some("TextOverlay"));
+	assert_eq!(video_processor_effect_name("Chroma-key"), Some("ChromaKey"));
+	assert_eq!(video_processor_effect_name("Color correction"), Some("ColorCorrection"));
+	assert_eq!(video_processor_effect_name("Fade in/out"), Some("Fade"));
+	assert_eq!(video_processor_effect_name("Panorama"), None);
+	assert_eq!(video_processor_effect_name("Expand"), None);
+	assert_eq!(video_processor_effect_name("Companion"), None);
+}
+
+#[test]
+fn test_tempo_changes() {
+	let marker = |time: f64, bpm: f64, linear: bool| TempoMarker {
+		time,
+		bpm,
+		time_signature: None,
+		linear,
+	};
+	let signature = |time: f64| (4, 4, if time < 10.0 { 100.0 } else { 120.0 });
+
+	// Ramp, starting exactly at the bound start, stays linear.
+	let changes = tempo_changes(
+		&[marker(0.0, 90.0, false), marker(10.0, 100.0, true), marker(20.0, 140.0, false)],
+		10.0,
+		30.0,
+		signature,
+	);
+	assert_eq!(changes.len(), 2);
+	assert_eq!((changes[0].time, changes[0].bpm, changes[0].linear), (0.0, 100.0, true));
+	assert_eq!((changes[1].time, changes[1].bpm, changes[1].linear), (10.0, 140.0, false));
+
+	// Bound inside the ramp starts with the ramping tempo.
+	let changes = tempo_changes(
+		&[marker(10.0, 100.0, true), marker(20.0, 140.0, false)],
+		15.0,
+		18.0,
+		signature,
+	);
+	assert_eq!(changes.len(), 1);
+	assert_eq!((changes[0].bpm, changes[0].linear), (120.0, true));
+}
+
+#[test]
+fn test_apply_crossfade_trim() {
+	let slice = |start: f64, end: f64, fade_in: f64| VideoSlice {
+		file: PathBuf::from("clip.mp4"),
+		track_idx: 0,
+		track_name: "Video".to_string(),
+		timeline_start: start,
+		timeline_end: end,
+		source_start: 0.0,
+		source_end: end - start,
+		enabled: true,
+		item_flags: VideoItemFlags::default(),
+		video_fx: VideoFxChain::default(),
+		alternate_takes: Vec::new(),
+		transitions: ItemTransitions::default(),
+		fade_in,
+		crossfade_in: None,
+	};
+	let mut slices = vec![slice(0.0, 4.0, 0.0), slice(3.0, 6.0, 1.0), slice(5.0, 8.0, 0.0)];
+	apply_crossfade_trim(&mut slices);
+	assert_eq!(slices.len(), 3);
+	// Incoming slices start at the end of the outgoing ones.
+	assert_eq!((slices[0].timeline_end, slices[1].timeline_start), (4.0, 4.0));
+	assert_eq!((slices[1].timeline_end, slices[2].timeline_start), (6.0, 6.0));
+	assert_eq!(slices[1].crossfade_in, Some(1.0));
+	// Overlap without fade-in is a cut.
+	assert_eq!(slices[2].crossfade_in, None);
+}
@@ -0,0 +1,370 @@
+use std::{
+    error::Error,
+    ffi::{c_char, CStr, CString},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use log::info;
+use rea_rs::{ExtState, Item, Position, ProbablyMutable, Project, Reaper, UndoFlags, WithReaperPtr};
+
+use crate::{
+    chapters::{format_clock, import_origin, parse_clock},
+    otio_export::{build_render_target_plan, RenderBound},
+};
+
+const SUBTITLE_EXT_SECTION: &str = "levitanus_subtitles";
+const SUBTITLE_TRACK_KEY: &str = "subtitle_track";
+/// Used, when no track was set as subtitle track.
+const DEFAULT_SUBTITLE_TRACK_NAME: &str = "subtitles";
+const ASS_HEADER: &str = "[Script Info]
+ScriptType: v4.00+
+PlayResX: 1920
+PlayResY: 1080
+WrapStyle: 0
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding
+Style: Default,Arial,54,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,0,0,1,2,1,2,40,40,40,1
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+";
+
+/// One subtitle cue relative to render bound start, in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Cue {
+    pub(crate) start: f64,
+    pub(crate) end: f64,
+    pub(crate) text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SubtitleFormat {
+    Srt,
+    WebVtt,
+    Ass,
+}
+impl SubtitleFormat {
+    const ALL: [SubtitleFormat; 3] = [Self::Srt, Self::WebVtt, Self::Ass];
+
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "srt" => Some(Self::Srt),
+            "vtt" => Some(Self::WebVtt),
+            "ass" => Some(Self::Ass),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::WebVtt => "vtt",
+            Self::Ass => "ass",
+        }
+    }
+}
+
+/// Remembers the first selected track as subtitle track of the project.
+pub fn set_subtitle_track() -> Result<(), Box<dyn Error>> {
+    let pr = Reaper::get().current_project();
+    let track = pr
+        .iter_selected_tracks()
+        .next()
+        .ok_or("no track selected")?;
+    let mut state = ExtState::new(
+        SUBTITLE_EXT_SECTION,
+        SUBTITLE_TRACK_KEY,
+        Some(track.guid().to_string()),
+        true,
+        &pr,
+        None,
+    );
+    state.set(track.guid().to_string());
+    Ok(())
+}
+
+/// Writes subtitle files for every render target from notes of empty items
+/// on subtitle track.
+pub fn export_subtitles() -> Result<(), Box<dyn Error>> {
+    let rpr = Reaper::get();
+    let pr = rpr.current_project();
+    let caption = "formats: srt vtt ass (empty = all)";
+    let response = rpr.get_user_inputs("Export subtitles", vec![caption], None)?;
+    let input = response
+        .get(caption)
+        .map(|v| v.trim().to_lowercase())
+        .unwrap_or_default();
+    let formats = if input.is_empty() {
+        SubtitleFormat::ALL.to_vec()
+    } else {
+        input
+            .split_whitespace()
+            .map(|key| {
+                SubtitleFormat::from_key(key).ok_or_else(|| format!("unknown subtitle format: {key}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let track_idx = find_subtitle_track(&pr).ok_or("subtitle track is not found")?;
+    for plan in build_render_target_plan(&pr)? {
+        let cues = collect_cues(&pr, track_idx, &plan.bound)?;
+        for format in formats.iter() {
+            let output_path = subtitle_path_for_target(&plan.render_target, *format);
+            std::fs::write(&output_path, render_subtitles(&cues, *format))?;
+            info!("subtitles exported: {}", output_path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Creates empty items with notes on subtitle track from SRT or WebVTT file.
+///
+/// Cues are offset to the time selection, or to the first render bound.
+pub fn import_subtitles() -> Result<(), Box<dyn Error>> {
+    let rpr = Reaper::get();
+    let mut pr = rpr.current_project();
+    let caption = "subtitles file (srt/vtt)";
+    let response = rpr.get_user_inputs("Import subtitles", vec![caption], None)?;
+    let path = response
+        .get(caption)
+        .map(|v| v.trim().trim_matches('"'))
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .ok_or("subtitles file is not specified")?;
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("can not read subtitles '{}': {e}", path.display()))?;
+    let cues = parse_subtitles(&content);
+    if cues.is_empty() {
+        return Err(format!("no subtitles found in {}", path.display()).into());
+    }
+    let track_idx = find_subtitle_track(&pr).ok_or("subtitle track is not found")?;
+    let (origin, _) = import_origin(&pr);
+
+    pr.begin_undo_block();
+    let result = add_cue_items(&mut pr, track_idx, origin, &cues);
+    pr.end_undo_block("Import subtitles", UndoFlags::all());
+    rpr.update_arrange();
+    result?;
+    info!("imported {} subtitles from {}", cues.len(), path.display());
+    Ok(())
+}
+
+/// Subtitle file next to render target.
+pub(crate) fn subtitle_path_for_target(render_target: &Path, format: SubtitleFormat) -> PathBuf {
+    render_target.with_extension(format.extension())
+}
+
+fn find_subtitle_track(pr: &Project) -> Option<usize> {
+    let state: ExtState<String, Project> = ExtState::new(
+        SUBTITLE_EXT_SECTION,
+        SUBTITLE_TRACK_KEY,
+        None,
+        true,
+        pr,
+        None,
+    );
+    match state.get().ok().flatten() {
+        Some(guid) => pr
+            .iter_tracks()
+            .find(|tr| tr.guid().to_string() == guid)
+            .map(|tr| tr.index()),
+        None => pr
+            .iter_tracks()
+            .find(|tr| tr.name().trim().to_lowercase() == DEFAULT_SUBTITLE_TRACK_NAME)
+            .map(|tr| tr.index()),
+    }
+}
+
+fn collect_cues(pr: &Project, track_idx: usize, bound: &RenderBound) -> anyhow::Result<Vec<Cue>> {
+    let track = pr
+        .get_track(track_idx)
+        .ok_or_else(|| anyhow!("can not get track with index {track_idx}"))?;
+    let bound_start = bound.start.as_duration().as_secs_f64();
+    let bound_end = bound.end.as_duration().as_secs_f64();
+
+    let mut cues = Vec::new();
+    for item_idx in 0..track.n_items() {
+        let item = track
+            .get_item(item_idx)
+            .ok_or_else(|| anyhow!("can not get item {item_idx} on track {track_idx}"))?;
+        if item.is_muted() || item.n_takes() > 0 {
+            continue;
+        }
+        let start = item.position().as_duration().as_secs_f64().max(bound_start);
+        let end = item.end_position().as_duration().as_secs_f64().min(bound_end);
+        let text = item_notes(&item).trim().replace("\r\n", "\n");
+        if end <= start || text.is_empty() {
+            continue;
+        }
+        cues.push(Cue {
+            start: start - bound_start,
+            end: end - bound_start,
+            text,
+        });
+    }
+    cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(cues)
+}
+
+fn add_cue_items(
+    pr: &mut Project,
+    track_idx: usize,
+    origin: f64,
+    cues: &[Cue],
+) -> anyhow::Result<()> {
+    let mut track = pr
+        .get_track_mut(track_idx)
+        .ok_or_else(|| anyhow!("can not get track with index {track_idx}"))?;
+    for cue in cues {
+        let item = track.add_item(
+            Position::from(origin + cue.start),
+            Duration::from_secs_f64((cue.end - cue.start).max(0.0)),
+        );
+        set_item_notes(&item, &cue.text)?;
+    }
+    Ok(())
+}
+
+/// Notes are read through the pointer to the item string, so notes of any
+/// length are neither cut nor copied into a fixed buffer.
+fn item_notes<T: ProbablyMutable>(item: &Item<T>) -> String {
+    let parm = CString::new("P_NOTES").expect("can not make notes parameter");
+    let notes = unsafe {
+        Reaper::get()
+            .low()
+            .GetSetMediaItemInfo(item.get(), parm.as_ptr(), std::ptr::null_mut())
+    };
+    if notes.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(notes as *const c_char) }
+        .to_string_lossy()
+        .to_string()
+}
+
+fn set_item_notes<T: ProbablyMutable>(item: &Item<T>, notes: &str) -> anyhow::Result<()> {
+    let parm = CString::new("P_NOTES").expect("can not make notes parameter");
+    let value = CString::new(notes)?;
+    let ok = unsafe {
+        Reaper::get().low().GetSetMediaItemInfo_String(
+            item.get(),
+            parm.as_ptr(),
+            value.as_ptr() as *mut c_char,
+            true,
+        )
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(anyhow!("can not set item notes"))
+    }
+}
+
+pub(crate) fn render_subtitles(cues: &[Cue], format: SubtitleFormat) -> String {
+    match format {
+        SubtitleFormat::Srt => cues
+            .iter()
+            .enumerate()
+            .map(|(idx, cue)| {
+                format!(
+                    "{}\n{} --> {}\n{}\n",
+                    idx + 1,
+                    format_clock(cue.start, ','),
+                    format_clock(cue.end, ','),
+                    cue.text
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        SubtitleFormat::WebVtt => {
+            let mut out = String::from("WEBVTT\n");
+            for cue in cues {
+                out.push_str(&format!(
+                    "\n{} --> {}\n{}\n",
+                    format_clock(cue.start, '.'),
+                    format_clock(cue.end, '.'),
+                    cue.text.replace("-->", "->")
+                ));
+            }
+            out
+        }
+        SubtitleFormat::Ass => {
+            let mut out = String::from(ASS_HEADER);
+            for cue in cues {
+                out.push_str(&format!(
+                    "Dialogue: 0,{},{},Default,,0,0,0,,{}\n",
+                    format_ass_time(cue.start),
+                    format_ass_time(cue.end),
+                    cue.text.replace('\n', "\\N")
+                ));
+            }
+            out
+        }
+    }
+}
+
+/// Parses SRT and WebVTT cues. Cue settings and identifiers are dropped.
+fn parse_subtitles(content: &str) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    let mut lines = content.trim_start_matches('\u{feff}').lines();
+    while let Some(line) = lines.next() {
+        let Some((start, end)) = line.split_once("-->") else {
+            continue;
+        };
+        let (Some(start), Some(end)) = (
+            parse_clock(start),
+            end.split_whitespace().next().and_then(parse_clock),
+        ) else {
+            continue;
+        };
+        let text = lines
+            .by_ref()
+            .take_while(|l| !l.trim().is_empty())
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n");
+        cues.push(Cue { start, end, text });
+    }
+    cues
+}
+
+/// `H:MM:SS.cc`
+fn format_ass_time(seconds: f64) -> String {
+    let total_cs = (seconds.max(0.0) * 100.0).round() as u64;
+    let total = total_cs / 100;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        total / 3600,
+        (total % 3600) / 60,
+        total % 60,
+        total_cs % 100
+    )
+}
+
+#[test]
+fn test_subtitles_roundtrip() {
+    let cues = vec![
+        Cue {
+            start: 1.5,
+            end: 3.25,
+            text: "Hello,\nworld".to_string(),
+        },
+        Cue {
+            start: 3661.0,
+            end: 3662.0,
+            text: "Bye".to_string(),
+        },
+    ];
+    let srt = render_subtitles(&cues, SubtitleFormat::Srt);
+    assert!(srt.starts_with("1\n00:00:01,500 --> 00:00:03,250\nHello,\nworld\n\n2\n"));
+    assert_eq!(parse_subtitles(&srt), cues);
+    assert_eq!(
+        parse_subtitles(&render_subtitles(&cues, SubtitleFormat::WebVtt)),
+        cues
+    );
+    assert!(render_subtitles(&cues, SubtitleFormat::Ass)
+        .ends_with("Dialogue: 0,1:01:01.00,1:01:02.00,Default,,0,0,0,,Bye\n"));
+}
@@ -0,0 +1,150 @@
+use std::ffi::c_int;
+
+use rea_rs::{Project, Reaper, WithReaperPtr};
+
+/// Beat grid walk stops after this measure.
+const MAX_MEASURES: c_int = 100_000;
+
+/// Tempo and time signature marker of the project.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TempoMarker {
+    /// Project time, seconds.
+    pub(crate) time: f64,
+    pub(crate) bpm: f64,
+    /// Numerator and denominator, if the marker changes time signature.
+    pub(crate) time_signature: Option<(c_int, c_int)>,
+    /// Tempo changes linearly to the next marker.
+    pub(crate) linear: bool,
+}
+
+/// All tempo markers of the project, sorted by time.
+pub(crate) fn tempo_markers(pr: &Project) -> Vec<TempoMarker> {
+    let low = Reaper::get().low();
+    let count = unsafe { low.CountTempoTimeSigMarkers(pr.get()) };
+    (0..count)
+        .filter_map(|idx| {
+            let mut time = 0.0;
+            let mut measure: c_int = 0;
+            let mut beat = 0.0;
+            let mut bpm = 0.0;
+            let mut num: c_int = 0;
+            let mut denom: c_int = 0;
+            let mut linear = false;
+            let found = unsafe {
+                low.GetTempoTimeSigMarker(
+                    pr.get(),
+                    idx,
+                    &mut time,
+                    &mut measure,
+                    &mut beat,
+                    &mut bpm,
+                    &mut num,
+                    &mut denom,
+                    &mut linear,
+                )
+            };
+            found.then_some(TempoMarker {
+                time,
+                bpm,
+                time_signature: (num > 0 && denom > 0).then_some((num, denom)),
+                linear,
+            })
+        })
+        .collect()
+}
+
+/// Numerator, denominator and tempo at the project time.
+pub(crate) fn time_signature_at(pr: &Project, time: f64) -> (c_int, c_int, f64) {
+    let mut num: c_int = 4;
+    let mut denom: c_int = 4;
+    let mut bpm = 120.0;
+    unsafe {
+        Reaper::get().low().TimeMap_GetTimeSigAtTime(
+            pr.get(),
+            time,
+            &mut num,
+            &mut denom,
+            &mut bpm,
+        );
+    }
+    (num.max(1), denom.max(1), bpm)
+}
+
+/// Zero-based measure, playing at the project time.
+fn measure_at(pr: &Project, time: f64) -> c_int {
+    let mut measure: c_int = 0;
+    unsafe {
+        Reaper::get().low().TimeMap2_timeToBeats(
+            pr.get(),
+            time,
+            &mut measure,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        );
+    }
+    measure
+}
+
+/// Project time of `beats` from the start of zero-based `measure`.
+fn measure_beat_time(pr: &Project, measure: c_int, beats: f64) -> f64 {
+    unsafe {
+        Reaper::get()
+            .low()
+            .TimeMap2_beatsToTime(pr.get(), beats, &measure)
+    }
+}
+
+/// Point of the project beat grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BeatGridPoint {
+    /// Zero-based measure.
+    pub(crate) measure: c_int,
+    /// Step inside the measure, counting subdivisions of beats.
+    pub(crate) step: u32,
+    /// Beats from the measure start.
+    pub(crate) beats: f64,
+    /// Project time, seconds.
+    pub(crate) time: f64,
+}
+
+/// Beat grid of the measures, which overlap `start..=end`.
+///
+/// Every beat is split into `subdivisions` steps, `None` gives only the
+/// downbeats. Points of the first measure may lie before `start`.
+pub(crate) fn beat_grid(
+    pr: &Project,
+    start: f64,
+    end: f64,
+    subdivisions: Option<u32>,
+) -> Vec<BeatGridPoint> {
+    let mut points = Vec::new();
+    for measure in measure_at(pr, start)..MAX_MEASURES {
+        let measure_start = measure_beat_time(pr, measure, 0.0);
+        if measure_start > end {
+            break;
+        }
+        let (num, _, _) = time_signature_at(pr, measure_start);
+        let steps = match subdivisions {
+            None => 1,
+            Some(n) => num as u32 * n.max(1),
+        };
+        for step in 0..steps {
+            let beats = match subdivisions {
+                None => 0.0,
+                Some(n) => step as f64 / n.max(1) as f64,
+            };
+            let time = measure_beat_time(pr, measure, beats);
+            if time > end {
+                break;
+            }
+            points.push(BeatGridPoint {
+                measure,
+                step,
+                beats,
+                time,
+            });
+        }
+    }
+    points
+}
@@ -0,0 +1,191 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use log::error;
+use rea_rs::{ExtState, HasExtState, Reaper, UndoFlags};
+use serde::{Deserialize, Serialize};
+
+use crate::ffmpeg_common::xfade::XFadeTransition;
+
+const TRANSITIONS_EXT_SECTION: &str = "levitanus_transitions";
+const ITEM_TRANSITIONS_KEY: &str = "transitions";
+const CUSTOM_PREFIX: &str = "custom:";
+
+/// Transition, used for item fade.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FadeTransition {
+    /// Any [XFadeTransition], except `custom`.
+    Xfade(XFadeTransition),
+    /// Expression of `xfade=transition=custom`.
+    Custom(String),
+}
+
+impl Default for FadeTransition {
+    fn default() -> Self {
+        Self::Xfade(XFadeTransition::fade)
+    }
+}
+
+impl fmt::Display for FadeTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Xfade(name) => write!(f, "{name}"),
+            Self::Custom(expr) => write!(f, "{CUSTOM_PREFIX}{expr}"),
+        }
+    }
+}
+
+impl FadeTransition {
+    /// Parses transition name, or `custom:<expression>`.
+    ///
+    /// As commas can not be typed in REAPER user inputs, `;` in expression
+    /// is replaced by `,`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if let Some(expr) = value.strip_prefix(CUSTOM_PREFIX) {
+            let expr = expr.trim().replace(';', ",");
+            if expr.is_empty() {
+                return Err("custom transition needs an expression".to_string());
+            }
+            if expr.contains('\'') {
+                return Err("custom transition expression can not contain quotes".to_string());
+            }
+            return Ok(Self::Custom(expr));
+        }
+        match XFadeTransition::from_str(&value.to_lowercase()) {
+            Ok(XFadeTransition::custom) | Err(_) => Err(format!("unknown transition: {value}")),
+            Ok(transition) => Ok(Self::Xfade(transition)),
+        }
+    }
+
+    /// Options of ffmpeg `xfade` filter, selecting this transition.
+    pub(crate) fn xfade_options(&self) -> String {
+        match self {
+            Self::Xfade(name) => format!("transition={name}"),
+            Self::Custom(expr) => format!("transition=custom:expr='{expr}'"),
+        }
+    }
+
+    /// OTIO `transition_type`. Everything except plain fades is exported
+    /// as custom transition with details in metadata.
+    pub(crate) fn otio_transition_type(&self) -> &'static str {
+        match self {
+            Self::Xfade(XFadeTransition::fade | XFadeTransition::dissolve) => "SMPTE_Dissolve",
+            _ => "Custom_Transition",
+        }
+    }
+}
+
+/// Transitions of item fade-in and fade-out.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ItemTransitions {
+    pub fade_in: FadeTransition,
+    pub fade_out: FadeTransition,
+}
+
+pub fn get_item_transitions<T>(item: &T) -> ItemTransitions
+where
+    T: HasExtState,
+{
+    match ExtState::new(
+        TRANSITIONS_EXT_SECTION,
+        ITEM_TRANSITIONS_KEY,
+        None,
+        true,
+        item,
+        None,
+    )
+    .get()
+    {
+        Ok(transitions) => transitions.unwrap_or_default(),
+        Err(e) => {
+            error!("can not get item transitions: {:?}", e);
+            ItemTransitions::default()
+        }
+    }
+}
+
+pub fn set_item_transitions<T>(item: &T, transitions: ItemTransitions)
+where
+    T: HasExtState,
+{
+    ExtState::new(
+        TRANSITIONS_EXT_SECTION,
+        ITEM_TRANSITIONS_KEY,
+        None,
+        true,
+        item,
+        None,
+    )
+    .set(transitions);
+}
+
+/// Asks for fade-in and fade-out transitions and stores them on selected
+/// items. Empty input keeps transition of every item as is.
+pub fn set_selected_items_transitions() -> Result<(), Box<dyn Error>> {
+    let rpr = Reaper::get_mut();
+    let mut pr = rpr.current_project();
+    if pr.n_selected_items() == 0 {
+        return Err("no selected items".into());
+    }
+    let fade_in_caption = "fade-in (name or custom:expr)";
+    let fade_out_caption = "fade-out (name or custom:expr)";
+    let response = rpr.get_user_inputs(
+        "Item fade transitions",
+        vec![fade_in_caption, fade_out_caption],
+        None,
+    )?;
+    let parse = |caption: &str| -> Result<Option<FadeTransition>, Box<dyn Error>> {
+        match response.get(caption).map(|v| v.trim()) {
+            None | Some("") => Ok(None),
+            Some(value) => Ok(Some(FadeTransition::parse(value)?)),
+        }
+    };
+    let fade_in = parse(fade_in_caption)?;
+    let fade_out = parse(fade_out_caption)?;
+
+    pr.begin_undo_block();
+    for item_idx in 0..pr.n_selected_items() {
+        let item = pr
+            .get_selected_item_mut(item_idx)
+            .ok_or("can not get selected item")?;
+        let mut transitions = get_item_transitions(&item);
+        if let Some(fade_in) = &fade_in {
+            transitions.fade_in = fade_in.clone();
+        }
+        if let Some(fade_out) = &fade_out {
+            transitions.fade_out = fade_out.clone();
+        }
+        set_item_transitions(&item, transitions);
+    }
+    pr.end_undo_block("Set item fade transitions", UndoFlags::all());
+    Ok(())
+}
+
+#[test]
+fn test_parse_transition() {
+    assert_eq!(
+        FadeTransition::parse(" WipeLeft "),
+        Ok(FadeTransition::Xfade(XFadeTransition::wipeleft))
+    );
+    let custom = FadeTransition::parse("custom:if(gt(X;W*P);A;B)").unwrap();
+    assert_eq!(
+        custom.xfade_options(),
+        "transition=custom:expr='if(gt(X,W*P),A,B)'"
+    );
+    assert_eq!(custom.to_string(), "custom:if(gt(X,W*P),A,B)");
+    assert!(FadeTransition::parse("swirl").is_err());
+}
+
+#[test]
+fn test_parse_every_xfade_transition() {
+    use strum::IntoEnumIterator;
+    for transition in XFadeTransition::iter() {
+        let parsed = FadeTransition::parse(&transition.to_string());
+        if transition == XFadeTransition::custom {
+            assert!(parsed.is_err());
+        } else {
+            assert_eq!(parsed, Ok(FadeTransition::Xfade(transition)));
+        }
+    }
+}